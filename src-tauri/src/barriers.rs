@@ -0,0 +1,301 @@
+// src/barriers.rs
+
+// Timing statistics of the light barriers. A trigger is a received
+// LIGHT_BARRIER_TOGGLE frame switching a barrier from inactive to active; the
+// statistics describe the intervals between the triggers of each barrier over
+// a rolling window. The same tracker runs live on the serial pipeline and
+// offline over structured recordings.
+
+// Importing necessary crates and modules.
+use std::collections::{BTreeMap, VecDeque}; // Barriers by ID and their recent triggers.
+use std::path::Path; // Struct for handling filesystem paths.
+use std::sync::{Arc, Mutex}; // Shared, thread-safe tracker state.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio::sync::mpsc::Receiver; // Channel of pipeline events.
+use tokio_util::sync::CancellationToken; // Token used to stop the statistics task.
+
+use crate::convert::{self, Decoded};
+use crate::pipeline::SinkEvent;
+use crate::protocol::{Frame, LIGHT_BARRIER_TOGGLE};
+use crate::recording::{self, Direction};
+
+/// Name of the pipeline sink feeding the live statistics.
+pub const BARRIER_SINK: &str = "lightBarriers";
+
+/// Configuration of the light barrier statistics, applied on connect.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierStatsConfig {
+    /// Length of the rolling window, in seconds.
+    pub window_secs: u64,
+    /// Time between two `lightBarrierStats` events, in milliseconds.
+    pub emit_interval_ms: u64,
+}
+
+impl Default for BarrierStatsConfig {
+    fn default() -> Self {
+        BarrierStatsConfig {
+            window_secs: 60,
+            emit_interval_ms: 1000,
+        }
+    }
+}
+
+/// Statistics of one barrier. Intervals and jitter are in milliseconds.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierStats {
+    pub hardware_id: u8,
+    pub active: bool,
+    /// Triggers since tracking started.
+    pub triggers: u64,
+    pub last_trigger_us: Option<u64>,
+    /// Triggers inside the window.
+    pub window_triggers: u64,
+    /// Triggers per second over the window.
+    pub rate_hz: Option<f64>,
+    pub interval_min_ms: Option<f64>,
+    pub interval_max_ms: Option<f64>,
+    pub interval_mean_ms: Option<f64>,
+    pub interval_stddev_ms: Option<f64>,
+    /// Mean absolute difference between consecutive intervals.
+    pub jitter_ms: Option<f64>,
+}
+
+/// Payload of the `lightBarrierStats` event and of `get_light_barrier_stats`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierReport {
+    /// End of the window, in microseconds since tracking started.
+    pub t_us: u64,
+    pub window_secs: Option<u64>,
+    pub barriers: Vec<BarrierStats>,
+}
+
+/// Statistics of a recording, returned by `analyze_light_barriers`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BarrierAnalysis {
+    /// Statistics over the whole recording.
+    pub total: BarrierReport,
+    /// Rolling-window statistics at the end of every window, when a window is given.
+    pub windows: Vec<BarrierReport>,
+}
+
+// Trigger history of one barrier.
+#[derive(Default)]
+struct Barrier {
+    active: bool,
+    triggers: u64,
+    last_trigger_us: Option<u64>,
+    /// Trigger times inside the window, oldest first.
+    recent: VecDeque<u64>,
+}
+
+/// Tracks the triggers of every barrier.
+pub struct BarrierTracker {
+    /// `None` keeps every trigger.
+    window_us: Option<u64>,
+    first_us: Option<u64>,
+    barriers: BTreeMap<u8, Barrier>,
+}
+
+impl BarrierTracker {
+    pub fn new(window: Option<Duration>) -> Self {
+        BarrierTracker {
+            window_us: window.map(|window| window.as_micros() as u64),
+            first_us: None,
+            barriers: BTreeMap::new(),
+        }
+    }
+
+    /// Feeds a received frame seen `t_us` after tracking started.
+    pub fn on_frame(&mut self, t_us: u64, frame: &Frame) {
+        if frame.command_id != LIGHT_BARRIER_TOGGLE {
+            return;
+        }
+        self.first_us.get_or_insert(t_us);
+        let barrier = self.barriers.entry(frame.hardware_id).or_default();
+        let active = frame.value != 0;
+        if active && !barrier.active {
+            barrier.triggers += 1;
+            barrier.last_trigger_us = Some(t_us);
+            barrier.recent.push_back(t_us);
+        }
+        barrier.active = active;
+        self.prune(t_us);
+    }
+
+    // Drops the triggers that left the window.
+    fn prune(&mut self, now_us: u64) {
+        if let Some(window_us) = self.window_us {
+            let oldest = now_us.saturating_sub(window_us);
+            for barrier in self.barriers.values_mut() {
+                while barrier.recent.front().is_some_and(|&t_us| t_us < oldest) {
+                    barrier.recent.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Statistics of every barrier at `now_us`.
+    pub fn report(&mut self, now_us: u64) -> BarrierReport {
+        self.prune(now_us);
+        // A window that started before tracking did only covers the time tracked.
+        let span_us = match (self.window_us, self.first_us) {
+            (Some(window_us), Some(first_us)) => window_us.min(now_us.saturating_sub(first_us)),
+            (None, Some(first_us)) => now_us.saturating_sub(first_us),
+            (_, None) => 0,
+        };
+        let barriers = self
+            .barriers
+            .iter()
+            .map(|(&hardware_id, barrier)| statistics(hardware_id, barrier, span_us))
+            .collect();
+        BarrierReport {
+            t_us: now_us,
+            window_secs: self.window_us.map(|window_us| window_us / 1_000_000),
+            barriers,
+        }
+    }
+}
+
+// Computes the statistics of one barrier over a window of `span_us`.
+fn statistics(hardware_id: u8, barrier: &Barrier, span_us: u64) -> BarrierStats {
+    let intervals: Vec<f64> = barrier
+        .recent
+        .iter()
+        .zip(barrier.recent.iter().skip(1))
+        .map(|(earlier, later)| (later - earlier) as f64 / 1000.0)
+        .collect();
+    let count = intervals.len() as f64;
+    let mean = (!intervals.is_empty()).then(|| intervals.iter().sum::<f64>() / count);
+    let stddev = mean.map(|mean| (intervals.iter().map(|i| (i - mean).powi(2)).sum::<f64>() / count).sqrt());
+    let jitter = (intervals.len() > 1).then(|| {
+        intervals.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum::<f64>() / (count - 1.0)
+    });
+
+    BarrierStats {
+        hardware_id,
+        active: barrier.active,
+        triggers: barrier.triggers,
+        last_trigger_us: barrier.last_trigger_us,
+        window_triggers: barrier.recent.len() as u64,
+        rate_hz: (span_us > 0).then(|| barrier.recent.len() as f64 / (span_us as f64 / 1_000_000.0)),
+        interval_min_ms: intervals.iter().copied().reduce(f64::min),
+        interval_max_ms: intervals.iter().copied().reduce(f64::max),
+        interval_mean_ms: mean,
+        interval_stddev_ms: stddev,
+        jitter_ms: jitter,
+    }
+}
+
+/// Live tracker of a connection, shared between its task and the query command.
+pub struct BarrierMonitor {
+    started_at: Instant,
+    tracker: Mutex<BarrierTracker>,
+}
+
+impl BarrierMonitor {
+    pub fn new(config: &BarrierStatsConfig) -> Arc<Self> {
+        Arc::new(BarrierMonitor {
+            started_at: Instant::now(),
+            tracker: Mutex::new(BarrierTracker::new(Some(Duration::from_secs(config.window_secs)))),
+        })
+    }
+
+    /// Current statistics.
+    pub fn report(&self) -> BarrierReport {
+        let now_us = recording::elapsed_us(self.started_at, Instant::now());
+        self.tracker.lock().unwrap().report(now_us)
+    }
+
+    fn on_event(&self, event: &SinkEvent) {
+        if let (Direction::Rx, Some(frame)) = (event.direction, &event.frame) {
+            let t_us = recording::elapsed_us(self.started_at, event.at);
+            self.tracker.lock().unwrap().on_frame(t_us, frame);
+        }
+    }
+}
+
+/// Starts the task feeding `monitor` and emitting `lightBarrierStats`.
+pub fn start_barrier_task(
+    app: tauri::AppHandle,
+    config: BarrierStatsConfig,
+    monitor: Arc<BarrierMonitor>,
+    mut events: Receiver<SinkEvent>,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(config.emit_interval_ms.max(100)));
+        let mut changed = false;
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                event = events.recv() => match event {
+                    Some(event) => {
+                        changed |= event.frame.is_some_and(|frame| frame.command_id == LIGHT_BARRIER_TOGGLE);
+                        monitor.on_event(&event);
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    // Keep reporting while triggers are in the window, so rates decay to zero.
+                    let report = monitor.report();
+                    if changed || report.barriers.iter().any(|barrier| barrier.window_triggers > 0) {
+                        changed = false;
+                        if let Err(e) = app.emit_all("lightBarrierStats", report) {
+                            println!("Failed to emit light barrier stats to the frontend: {:?}", e);
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Computes the statistics of a structured recording. Blocking.
+///
+/// With a window, a rolling-window report is also taken at the end of every window.
+pub fn analyze(path: &Path, window: Option<Duration>) -> Result<BarrierAnalysis, String> {
+    if !convert::is_structured(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))? {
+        return Err("Raw recordings carry no timestamps; convert them first.".to_string());
+    }
+    let mut total = BarrierTracker::new(None);
+    let mut rolling = window.map(|window| (BarrierTracker::new(Some(window)), window.as_micros() as u64));
+    let mut windows = Vec::new();
+    let mut next_end = rolling.as_ref().map(|(_, window_us)| *window_us);
+    let mut last_us = 0;
+
+    convert::decode(path, |item| {
+        if let Decoded::Frame {
+            t_us: Some(t_us),
+            direction: Some(Direction::Rx),
+            frame,
+            ..
+        } = item
+        {
+            if let Some((tracker, window_us)) = rolling.as_mut() {
+                while let Some(end) = next_end.filter(|&end| end <= t_us) {
+                    windows.push(tracker.report(end));
+                    next_end = Some(end + *window_us);
+                }
+                tracker.on_frame(t_us, &frame);
+            }
+            total.on_frame(t_us, &frame);
+            last_us = t_us;
+        }
+        Ok(())
+    })
+    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+    if let Some((tracker, _)) = rolling.as_mut() {
+        windows.push(tracker.report(last_us));
+    }
+    Ok(BarrierAnalysis {
+        total: total.report(last_us),
+        windows,
+    })
+}
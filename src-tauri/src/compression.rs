@@ -0,0 +1,170 @@
+// src/compression.rs
+
+// Importing necessary crates and modules.
+use std::fs::File; // Struct for reading recordings.
+use std::io::{BufRead, BufReader, Read, Write}; // Sync I/O traits used by the codecs.
+use std::path::Path; // Struct for handling filesystem paths.
+
+/// Compression applied to recording files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Extension appended to the `.txt` name of a recording.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Configuration of the recording compression.
+///
+/// Compressed data is flushed as a complete block every `block_bytes` of input
+/// or `block_ms` milliseconds, so a crash loses at most the last block.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressionConfig {
+    pub algorithm: Compression,
+    /// Compression level: 0-9 for gzip, 1-22 for zstd.
+    pub level: i32,
+    pub block_bytes: u64,
+    pub block_ms: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            algorithm: Compression::None,
+            level: 3,
+            block_bytes: 64 * 1024,
+            block_ms: 1000,
+        }
+    }
+}
+
+/// Streaming encoder producing the bytes to append to a recording file.
+///
+/// Compressed output is collected in memory and handed back to the caller, so
+/// the file itself can be written asynchronously.
+pub enum Encoder {
+    Plain,
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>),
+}
+
+impl Encoder {
+    pub fn new(config: &CompressionConfig) -> std::io::Result<Self> {
+        Ok(match config.algorithm {
+            Compression::None => Encoder::Plain,
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(config.level.clamp(0, 9) as u32),
+            )),
+            Compression::Zstd => Encoder::Zstd(zstd::stream::write::Encoder::new(
+                Vec::new(),
+                config.level.clamp(1, 22),
+            )?),
+        })
+    }
+
+    /// Compresses `data` and returns whatever output is ready.
+    pub fn write(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoder::Plain => Ok(data.to_vec()),
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.write_all(data)?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// Ends the current block so everything written so far can be decoded.
+    pub fn flush(&mut self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoder::Plain => Ok(Vec::new()),
+            Encoder::Gzip(encoder) => {
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+            Encoder::Zstd(encoder) => {
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            }
+        }
+    }
+
+    /// Finishes the stream and returns the remaining output, trailer included.
+    pub fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoder::Plain => Ok(Vec::new()),
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, Encoder::Plain)
+    }
+}
+
+// Magic numbers at the start of compressed files.
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// Detects the compression of a file from its first bytes.
+pub fn detect(path: &Path) -> std::io::Result<Compression> {
+    let mut magic = [0u8; 4];
+    let mut file = File::open(path)?;
+    let mut read = 0;
+    while read < magic.len() {
+        match file.read(&mut magic[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(if read >= 4 && magic == ZSTD_MAGIC {
+        Compression::Zstd
+    } else if read >= 2 && magic[..2] == GZIP_MAGIC {
+        Compression::Gzip
+    } else {
+        Compression::None
+    })
+}
+
+// Ends the stream at a truncated tail instead of failing, so a recording cut
+// short by a crash can still be read up to its last complete block.
+struct Tolerant<R>(R);
+
+impl<R: Read> Read for Tolerant<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.0.read(buf) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(0),
+            result => result,
+        }
+    }
+}
+
+/// Opens a recording for reading, decompressing it transparently.
+pub fn open_recording(path: &Path) -> std::io::Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)?;
+    Ok(match detect(path)? {
+        Compression::None => Box::new(BufReader::new(file)),
+        Compression::Gzip => Box::new(BufReader::new(Tolerant(flate2::read::MultiGzDecoder::new(
+            BufReader::new(file),
+        )))),
+        Compression::Zstd => Box::new(BufReader::new(Tolerant(zstd::stream::read::Decoder::new(file)?))),
+    })
+}
+
@@ -0,0 +1,475 @@
+// src/convert.rs
+
+// Converts recordings into decoded CSV or NDJSON. Both kinds of recording are
+// understood: the structured text format written since version 1, compressed
+// or not, and the raw binary dumps written by older versions, which are run
+// through the same frame decoder as the live port.
+//
+// Also available without the UI:
+//
+//   app convert <recording> [--format csv|ndjson] [--output <path>]
+
+// Importing necessary crates and modules.
+use std::fs::File; // Struct for reading and writing files.
+use std::io::{BufRead, BufWriter, Read, Write}; // Sync I/O traits.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::compression;
+use crate::protocol::{self, FrameDecoder, DecodeEvent, Frame, FRAME_LEN};
+use crate::recording::{self, Direction, RECORDING_MAGIC};
+
+/// Output format of a conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutputFormat {
+    Csv,
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Csv => "csv",
+            OutputFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Bytes of a recording that could not be decoded into frames.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MalformedRegion {
+    /// Byte offset in the recording, in the decompressed text for structured recordings.
+    pub offset: Option<u64>,
+    /// Line number (from 1) in a structured recording.
+    pub line: Option<u64>,
+    pub t_us: Option<u64>,
+    pub length: usize,
+    pub detail: String,
+}
+
+/// Result of a conversion, returned by `convert_recording`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionReport {
+    pub input: String,
+    pub output: String,
+    pub format: OutputFormat,
+    /// "raw" for legacy binary dumps, "structured" for text recordings.
+    pub source: &'static str,
+    pub frames: u64,
+    pub malformed_bytes: u64,
+    /// Number of malformed regions; only the first `MAX_REPORTED_REGIONS` are listed.
+    pub malformed_count: u64,
+    pub malformed: Vec<MalformedRegion>,
+}
+
+/// Item produced while decoding a recording.
+pub enum Decoded {
+    /// A frame; `line`, `t_us` and `direction` are only known in structured recordings.
+    Frame {
+        offset: u64,
+        line: Option<u64>,
+        t_us: Option<u64>,
+        direction: Option<Direction>,
+        frame: Frame,
+    },
+    Malformed(MalformedRegion),
+}
+
+/// Malformed regions listed in a report; the rest are only counted.
+const MAX_REPORTED_REGIONS: usize = 1000;
+
+/// One decoded frame of the output.
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Row {
+    offset: u64,
+    t_us: Option<u64>,
+    direction: Option<&'static str>,
+    command: &'static str,
+    command_id: u8,
+    hardware_id: u8,
+    value: u32,
+    unit: &'static str,
+    label: &'static str,
+}
+
+impl Row {
+    pub fn new(frame: &Frame, offset: u64, t_us: Option<u64>, direction: Option<Direction>) -> Self {
+        Row {
+            offset,
+            t_us,
+            direction: direction.map(|direction| direction.as_str()),
+            command: frame.command_name(),
+            command_id: frame.command_id,
+            hardware_id: frame.hardware_id,
+            value: frame.value,
+            unit: unit(frame.command_id),
+            label: label(frame),
+        }
+    }
+}
+
+/// Unit of the value of a command, as shown in the UI.
+pub fn unit(command_id: u8) -> &'static str {
+    match command_id {
+        protocol::MOTOR_SPEED => "Hz",
+        protocol::LED_INTENSITY => "%",
+        _ => "",
+    }
+}
+
+// Meaning of values that encode a state.
+fn label(frame: &Frame) -> &'static str {
+    match (frame.command_id, frame.value) {
+        (protocol::MOTOR_DIRECTION, 0) => "CW",
+        (protocol::MOTOR_DIRECTION, _) => "CCW",
+        (protocol::MOTOR_ON_OFF | protocol::LED_ON_OFF | protocol::LIGHT_BARRIER_TOGGLE, 0) => "OFF",
+        (protocol::MOTOR_ON_OFF | protocol::LED_ON_OFF | protocol::LIGHT_BARRIER_TOGGLE, _) => "ON",
+        _ => "",
+    }
+}
+
+/// Writes rows in the chosen format.
+pub struct RowWriter {
+    out: BufWriter<File>,
+    format: OutputFormat,
+}
+
+impl RowWriter {
+    pub fn create(path: &Path, format: OutputFormat) -> std::io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        if format == OutputFormat::Csv {
+            out.write_all(b"offset,t_us,dir,command,command_id,hardware_id,value,unit,label\n")?;
+        }
+        Ok(RowWriter { out, format })
+    }
+
+    pub fn write(&mut self, row: &Row) -> std::io::Result<()> {
+        match self.format {
+            OutputFormat::Csv => {
+                let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+                writeln!(
+                    self.out,
+                    "{},{},{},{},{},{},{},{},{}",
+                    row.offset,
+                    optional(row.t_us),
+                    row.direction.unwrap_or_default(),
+                    row.command,
+                    row.command_id,
+                    row.hardware_id,
+                    row.value,
+                    row.unit,
+                    row.label
+                )
+            }
+            OutputFormat::Ndjson => {
+                serde_json::to_writer(&mut self.out, row)?;
+                self.out.write_all(b"\n")
+            }
+        }
+    }
+
+    /// Writes the buffered rows out, without syncing.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Writes the buffered rows out and syncs the file to disk.
+    pub fn finish(mut self) -> std::io::Result<()> {
+        self.out.flush()?;
+        self.out.get_ref().sync_all()
+    }
+}
+
+// Collects the malformed regions found while converting.
+#[derive(Default)]
+struct Malformed {
+    bytes: u64,
+    count: u64,
+    regions: Vec<MalformedRegion>,
+}
+
+impl Malformed {
+    fn push(&mut self, region: MalformedRegion) {
+        self.bytes += region.length as u64;
+        self.count += 1;
+        if self.regions.len() < MAX_REPORTED_REGIONS {
+            self.regions.push(region);
+        }
+    }
+}
+
+/// Default output path: the recording name with its extensions replaced.
+pub fn default_output(input: &Path, format: OutputFormat) -> PathBuf {
+    output_with_extension(input, format.extension())
+}
+
+/// The recording name with its extensions replaced by `extension`.
+pub fn output_with_extension(input: &Path, extension: &str) -> PathBuf {
+    let name = input
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = name.strip_suffix(".gz").or_else(|| name.strip_suffix(".zst")).unwrap_or(&name);
+    let stem = stem.strip_suffix(".txt").unwrap_or(stem);
+    input.with_file_name(format!("{}.{}", stem, extension))
+}
+
+/// True when the file starts with the structured recording header.
+pub fn is_structured(path: &Path) -> std::io::Result<bool> {
+    let mut reader = compression::open_recording(path)?;
+    let mut start = vec![0u8; RECORDING_MAGIC.len()];
+    let mut read = 0;
+    while read < start.len() {
+        match reader.read(&mut start[read..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => read += n,
+        }
+    }
+    Ok(start[..read] == *RECORDING_MAGIC.as_bytes())
+}
+
+// Decodes a legacy binary recording.
+fn decode_raw(path: &Path, on: &mut impl FnMut(Decoded) -> std::io::Result<()>) -> std::io::Result<()> {
+    let mut file = File::open(path)?;
+    let mut decoder = FrameDecoder::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut offset: u64 = 0; // Offset of the next byte the decoder reports.
+    let mut total: u64 = 0;
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        total += read as u64;
+        for event in decoder.feed(&buffer[..read]) {
+            match event {
+                DecodeEvent::Frame(frame, _) => {
+                    on(Decoded::Frame {
+                        offset,
+                        line: None,
+                        t_us: None,
+                        direction: None,
+                        frame,
+                    })?;
+                    offset += FRAME_LEN as u64;
+                }
+                DecodeEvent::Discarded(bytes) => {
+                    on(Decoded::Malformed(MalformedRegion {
+                        offset: Some(offset),
+                        line: None,
+                        t_us: None,
+                        length: bytes.len(),
+                        detail: format!("no frame terminator: {}", recording::hex(&bytes)),
+                    }))?;
+                    offset += bytes.len() as u64;
+                }
+            }
+        }
+    }
+
+    // Whatever the decoder still holds never formed a complete frame.
+    if total > offset {
+        on(Decoded::Malformed(MalformedRegion {
+            offset: Some(offset),
+            line: None,
+            t_us: None,
+            length: (total - offset) as usize,
+            detail: "incomplete frame at end of file".to_string(),
+        }))?;
+    }
+    Ok(())
+}
+
+// Parses the fields of a frame line of a structured recording.
+fn parse_frame_fields(fields: &[&str]) -> Option<Frame> {
+    Some(Frame::new(
+        fields[4].parse().ok()?,
+        fields[5].parse().ok()?,
+        fields[6].parse().ok()?,
+    ))
+}
+
+// Decodes a structured text recording.
+fn decode_structured(path: &Path, on: &mut impl FnMut(Decoded) -> std::io::Result<()>) -> std::io::Result<()> {
+    let reader = compression::open_recording(path)?;
+    let mut next_offset: u64 = 0;
+
+    for (index, line) in reader.split(b'\n').enumerate() {
+        let line_number = index as u64 + 1;
+        let offset = next_offset;
+        let line = match line {
+            Ok(line) => {
+                next_offset += line.len() as u64 + 1;
+                String::from_utf8_lossy(&line).trim_end_matches('\r').to_string()
+            }
+            Err(e) => {
+                on(Decoded::Malformed(MalformedRegion {
+                    offset: Some(offset),
+                    line: Some(line_number),
+                    t_us: None,
+                    length: 0,
+                    detail: format!("could not read the rest of the file: {}", e),
+                }))?;
+                break;
+            }
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        let t_us = fields[0].parse::<u64>().ok();
+        let direction = match fields.get(1) {
+            Some(&"RX") => Some(Direction::Rx),
+            Some(&"TX") => Some(Direction::Tx),
+            _ => None,
+        };
+        let region = |detail: String, length: usize| MalformedRegion {
+            offset: Some(offset),
+            line: Some(line_number),
+            t_us,
+            length,
+            detail,
+        };
+
+        match (t_us, direction, fields.len()) {
+            (Some(_), Some(_), 7) => match parse_frame_fields(&fields) {
+                Some(frame) => on(Decoded::Frame {
+                    offset,
+                    line: Some(line_number),
+                    t_us,
+                    direction,
+                    frame,
+                })?,
+                None => on(Decoded::Malformed(region(format!("invalid frame fields: {}", line), FRAME_LEN)))?,
+            },
+            (Some(_), Some(_), 3) => {
+                let length = fields[2].split_whitespace().count();
+                on(Decoded::Malformed(region(format!("bytes outside a frame: {}", fields[2]), length)))?;
+            }
+            // Markers, pauses and resumes carry no frame.
+            (Some(_), _, _) if matches!(fields.get(1), Some(&"MARK" | &"PAUSE" | &"RESUME")) => {}
+            _ => on(Decoded::Malformed(region(format!("unreadable line: {}", line), line.len())))?,
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a recording, raw or structured, calling `on` for every frame and malformed region.
+///
+/// Blocking. Returns whether the recording is structured.
+pub fn decode(path: &Path, mut on: impl FnMut(Decoded) -> std::io::Result<()>) -> std::io::Result<bool> {
+    let structured = is_structured(path)?;
+    if structured {
+        decode_structured(path, &mut on)?;
+    } else {
+        decode_raw(path, &mut on)?;
+    }
+    Ok(structured)
+}
+
+/// Converts a recording into `output`, or next to it when `output` is `None`.
+///
+/// Blocking; run it on a blocking thread from async code.
+pub fn convert(input: &Path, format: OutputFormat, output: Option<PathBuf>) -> Result<ConversionReport, String> {
+    let output = output.unwrap_or_else(|| default_output(input, format));
+    if output == input {
+        return Err("The output would overwrite the recording.".to_string());
+    }
+    std::fs::File::open(input).map_err(|e| format!("Could not open {}: {}", input.display(), e))?;
+
+    let mut writer = RowWriter::create(&output, format)
+        .map_err(|e| format!("Could not create {}: {}", output.display(), e))?;
+    let mut malformed = Malformed::default();
+    let mut frames = 0;
+    let structured = decode(input, |item| match item {
+        Decoded::Frame {
+            offset,
+            t_us,
+            direction,
+            frame,
+            ..
+        } => {
+            frames += 1;
+            writer.write(&Row::new(&frame, offset, t_us, direction))
+        }
+        Decoded::Malformed(region) => {
+            malformed.push(region);
+            Ok(())
+        }
+    })
+    .and_then(|structured| writer.finish().map(|_| structured))
+    .map_err(|e| format!("Conversion of {} failed: {}", input.display(), e))?;
+
+    Ok(ConversionReport {
+        input: input.to_string_lossy().to_string(),
+        output: output.to_string_lossy().to_string(),
+        format,
+        source: if structured { "structured" } else { "raw" },
+        frames,
+        malformed_bytes: malformed.bytes,
+        malformed_count: malformed.count,
+        malformed: malformed.regions,
+    })
+}
+
+/// Runs `convert` from the command line and returns the process exit code.
+///
+/// `args` are the arguments following `convert`.
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut format = OutputFormat::Csv;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("csv") => format = OutputFormat::Csv,
+                Some("ndjson") => format = OutputFormat::Ndjson,
+                other => {
+                    eprintln!("Unknown format {:?}; use csv or ndjson.", other.unwrap_or_default());
+                    return 2;
+                }
+            },
+            "--output" => output = args.next().map(PathBuf::from),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument {}.", arg);
+                return 2;
+            }
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("Usage: app convert <recording> [--format csv|ndjson] [--output <path>]");
+            return 2;
+        }
+    };
+
+    match convert(&input, format, output) {
+        Ok(report) => {
+            for region in &report.malformed {
+                let position = match (region.line, region.offset) {
+                    (Some(line), _) => format!("line {}", line),
+                    (_, Some(offset)) => format!("offset {}", offset),
+                    _ => String::new(),
+                };
+                eprintln!("Malformed at {} ({} bytes): {}", position, region.length, region.detail);
+            }
+            println!(
+                "{} frames written to {}; {} malformed region(s), {} bytes.",
+                report.frames, report.output, report.malformed_count, report.malformed_bytes
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
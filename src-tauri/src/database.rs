@@ -0,0 +1,723 @@
+// src/database.rs
+
+// SQLite export of decoded sessions. A session is either exported live from
+// the port, as a sink of the serial pipeline, or imported from an existing
+// recording. Every table carries the session ID and is indexed on it, so one
+// database can hold months of test runs and still be queried quickly:
+//
+//   sessions  one row per live export or imported recording
+//   frames    decoded frames; `t_us` since the session start, `unix_us` wall clock
+//   markers   operator markers
+//   logs      backend log entries written while a live export runs
+//
+// Bytes that never formed a frame are only counted, in `sessions.malformed`.
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local, SecondsFormat}; // Crate for date and time handling.
+use rusqlite::{params, Connection, OptionalExtension}; // SQLite bindings.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+use std::sync::Mutex; // Mutex for the log tap.
+use std::time::Instant; // Struct for monotonic timestamps.
+use tauri::Manager; // Tauri Manager for accessing the log tap.
+use tokio::sync::mpsc::{self, Receiver, Sender}; // Channel feeding the database thread.
+
+use crate::convert::{self, Decoded};
+use crate::library;
+use crate::pipeline::{SinkEvent, SinkRegistry, SINK_CAPACITY};
+use crate::protocol;
+use crate::recording::{self, RecordingHeader};
+use crate::serial_wrapper::backend_log;
+use crate::sidecar::SessionInfo;
+
+/// Name of the pipeline sink feeding a live export.
+pub const DATABASE_SINK: &str = "database";
+/// File name of the database when no path is configured.
+pub const DATABASE_FILE: &str = "sessions.sqlite";
+
+// Messages written in one transaction at most.
+const BATCH_SIZE: usize = 1000;
+// Rows returned by a query when no limit is given, and at most.
+const DEFAULT_QUERY_LIMIT: u32 = 1000;
+const MAX_QUERY_LIMIT: u32 = 100_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY,
+    source TEXT NOT NULL,
+    file TEXT,
+    port TEXT,
+    baud_rate INTEGER,
+    started TEXT,
+    started_unix_us INTEGER,
+    ended TEXT,
+    app_version TEXT,
+    operator TEXT,
+    unit_serial TEXT,
+    firmware TEXT,
+    test_run TEXT,
+    notes TEXT,
+    frames INTEGER NOT NULL DEFAULT 0,
+    malformed INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS frames (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    t_us INTEGER,
+    unix_us INTEGER,
+    byte_offset INTEGER,
+    direction TEXT,
+    command_id INTEGER NOT NULL,
+    hardware_id INTEGER NOT NULL,
+    value INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS frames_session_time ON frames(session_id, t_us);
+CREATE INDEX IF NOT EXISTS frames_command ON frames(command_id, hardware_id, session_id);
+CREATE INDEX IF NOT EXISTS frames_unix_time ON frames(unix_us);
+CREATE TABLE IF NOT EXISTS markers (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    t_us INTEGER NOT NULL,
+    unix_us INTEGER,
+    text TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS markers_session_time ON markers(session_id, t_us);
+CREATE TABLE IF NOT EXISTS logs (
+    session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    unix_us INTEGER NOT NULL,
+    level TEXT NOT NULL,
+    message TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS logs_session_time ON logs(session_id, unix_us);
+";
+
+/// Configuration of the SQLite export.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseConfig {
+    /// Database file; `sessions.sqlite` in the recording folder when unset.
+    pub path: Option<PathBuf>,
+}
+
+impl DatabaseConfig {
+    /// Database file to use, if one can be determined.
+    pub fn resolve(&self, folder: Option<&Path>) -> Option<PathBuf> {
+        self.path.clone().or_else(|| folder.map(|folder| folder.join(DATABASE_FILE)))
+    }
+}
+
+/// A session of the database, as returned by `list_database_sessions`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRow {
+    pub id: i64,
+    /// "live" or "import".
+    pub source: String,
+    pub file: Option<String>,
+    pub port: Option<String>,
+    pub baud_rate: Option<u32>,
+    pub started: Option<String>,
+    pub ended: Option<String>,
+    pub app_version: Option<String>,
+    pub session: SessionInfo,
+    pub frames: u64,
+    pub malformed: u64,
+}
+
+/// Filter of `query_database`. Every field is optional.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameQuery {
+    pub session_id: Option<i64>,
+    pub command_id: Option<u8>,
+    pub hardware_id: Option<u8>,
+    pub direction: Option<String>,
+    /// Range in microseconds since the session start.
+    pub from_us: Option<u64>,
+    pub to_us: Option<u64>,
+    /// Wall-clock range, RFC 3339.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// A frame returned by `query_database`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameRow {
+    pub session_id: i64,
+    pub t_us: Option<u64>,
+    /// Wall-clock time, RFC 3339.
+    pub time: Option<String>,
+    /// Byte offset in an imported recording, in the decompressed text for structured ones.
+    pub byte_offset: Option<u64>,
+    pub direction: Option<String>,
+    pub command: &'static str,
+    pub command_id: u8,
+    pub hardware_id: u8,
+    pub value: u32,
+    pub unit: &'static str,
+}
+
+/// Result of an import, returned by `import_recording_to_database`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub session_id: i64,
+    pub database: String,
+    pub frames: u64,
+    pub markers: u64,
+    pub malformed: u64,
+}
+
+/// Opens the database, creating the tables on first use.
+pub fn open(path: &Path) -> rusqlite::Result<Connection> {
+    let connection = Connection::open(path)?;
+    // WAL lets queries run while a live export writes.
+    connection.pragma_update(None, "journal_mode", "WAL")?;
+    connection.pragma_update(None, "foreign_keys", true)?;
+    connection.busy_timeout(std::time::Duration::from_secs(5))?;
+    connection.execute_batch(SCHEMA)?;
+    Ok(connection)
+}
+
+// Values of a new `sessions` row.
+struct NewSession<'a> {
+    source: &'static str,
+    file: Option<String>,
+    port: Option<&'a str>,
+    baud_rate: Option<u32>,
+    started: Option<String>,
+    ended: Option<String>,
+    app_version: Option<&'a str>,
+    session: &'a SessionInfo,
+}
+
+// Inserts a session and returns its ID.
+fn insert_session(connection: &Connection, session: &NewSession) -> rusqlite::Result<i64> {
+    let started_unix_us = session.started.as_deref().and_then(unix_us_of);
+    connection.execute(
+        "INSERT INTO sessions (source, file, port, baud_rate, started, started_unix_us, ended, app_version,
+             operator, unit_serial, firmware, test_run, notes)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            session.source,
+            session.file,
+            session.port,
+            session.baud_rate,
+            session.started,
+            started_unix_us,
+            session.ended,
+            session.app_version,
+            session.session.operator,
+            session.session.unit_serial,
+            session.session.firmware,
+            session.session.test_run,
+            session.session.notes,
+        ],
+    )?;
+    Ok(connection.last_insert_rowid())
+}
+
+// Microseconds since the Unix epoch of an RFC 3339 timestamp.
+fn unix_us_of(time: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(time).ok().map(|time| time.timestamp_micros())
+}
+
+// RFC 3339 timestamp of microseconds since the Unix epoch.
+fn time_of(unix_us: i64) -> Option<String> {
+    DateTime::from_timestamp_micros(unix_us)
+        .map(|time| time.with_timezone(&Local).to_rfc3339_opts(SecondsFormat::Micros, false))
+}
+
+// Inserts one frame.
+fn insert_frame(
+    connection: &Connection,
+    session_id: i64,
+    t_us: Option<u64>,
+    unix_us: Option<i64>,
+    byte_offset: Option<u64>,
+    direction: Option<&str>,
+    frame: &protocol::Frame,
+) -> rusqlite::Result<()> {
+    connection
+        .prepare_cached(
+            "INSERT INTO frames (session_id, t_us, unix_us, byte_offset, direction, command_id, hardware_id, value)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?
+        .execute(params![
+            session_id,
+            t_us,
+            unix_us,
+            byte_offset,
+            direction,
+            frame.command_id,
+            frame.hardware_id,
+            frame.value,
+        ])?;
+    Ok(())
+}
+
+// Inserts one marker.
+fn insert_marker(
+    connection: &Connection,
+    session_id: i64,
+    t_us: u64,
+    unix_us: Option<i64>,
+    text: &str,
+) -> rusqlite::Result<()> {
+    connection
+        .prepare_cached("INSERT INTO markers (session_id, t_us, unix_us, text) VALUES (?1, ?2, ?3, ?4)")?
+        .execute(params![session_id, t_us, unix_us, text])?;
+    Ok(())
+}
+
+// Stores the counts and end of a session.
+fn finish_session(connection: &Connection, session_id: i64, frames: u64, malformed: u64) -> rusqlite::Result<()> {
+    connection.execute(
+        "UPDATE sessions SET ended = ?1, frames = ?2, malformed = ?3 WHERE id = ?4",
+        params![
+            Local::now().to_rfc3339_opts(SecondsFormat::Micros, false),
+            frames,
+            malformed,
+            session_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Imports a recording, raw or structured, as a new session. Blocking.
+pub fn import(database: &Path, recording: &Path) -> Result<ImportReport, String> {
+    let details = library::details(recording)?;
+    let mut connection = open(database).map_err(|e| format!("Could not open {}: {}", database.display(), e))?;
+    let file = details.summary.name.clone();
+    let existing: Option<i64> = connection
+        .query_row(
+            "SELECT id FROM sessions WHERE source = 'import' AND file = ?1",
+            params![file],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    if let Some(id) = existing {
+        return Err(format!("{} was already imported as session {}.", file, id));
+    }
+
+    let failed = |e: &dyn std::fmt::Display| format!("Import of {} failed: {}", recording.display(), e);
+    let transaction = connection.transaction().map_err(|e| failed(&e))?;
+    let meta = details.summary.sidecar.as_ref();
+    let session_id = insert_session(
+        &transaction,
+        &NewSession {
+            source: "import",
+            file: Some(file),
+            port: details.port.as_deref(),
+            baud_rate: details.baud_rate,
+            started: meta.map(|meta| meta.session_started.clone()).or(details.summary.started.clone()),
+            ended: details.summary.ended.clone(),
+            app_version: meta.map(|meta| meta.app_version.as_str()),
+            session: &meta.map(|meta| meta.session.clone()).unwrap_or_default(),
+        },
+    )
+    .map_err(|e| failed(&e))?;
+    // Structured recordings count `t_us` from the session start in their header.
+    let started_unix_us: Option<i64> = transaction
+        .query_row("SELECT started_unix_us FROM sessions WHERE id = ?1", params![session_id], |row| row.get(0))
+        .map_err(|e| failed(&e))?;
+
+    let (mut frames, mut malformed) = (0, 0);
+    convert::decode(recording, |item| {
+        match item {
+            Decoded::Frame {
+                offset,
+                t_us,
+                direction,
+                frame,
+                ..
+            } => {
+                let unix_us = t_us.zip(started_unix_us).map(|(t_us, started)| started + t_us as i64);
+                insert_frame(
+                    &transaction,
+                    session_id,
+                    t_us,
+                    unix_us,
+                    Some(offset),
+                    direction.map(|direction| direction.as_str()),
+                    &frame,
+                )
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+                frames += 1;
+            }
+            Decoded::Malformed(_) => malformed += 1,
+        }
+        Ok(())
+    })
+    .map_err(|e| failed(&e))?;
+
+    for marker in &details.markers {
+        let unix_us = started_unix_us.map(|started| started + marker.t_us as i64);
+        insert_marker(&transaction, session_id, marker.t_us, unix_us, &marker.text).map_err(|e| failed(&e))?;
+    }
+    transaction
+        .execute(
+            "UPDATE sessions SET frames = ?1, malformed = ?2 WHERE id = ?3",
+            params![frames, malformed, session_id],
+        )
+        .map_err(|e| failed(&e))?;
+    transaction.commit().map_err(|e| failed(&e))?;
+
+    Ok(ImportReport {
+        session_id,
+        database: database.to_string_lossy().to_string(),
+        frames,
+        markers: details.markers.len() as u64,
+        malformed,
+    })
+}
+
+/// Message handled by the thread of a live export.
+#[derive(Debug)]
+pub enum DatabaseMessage {
+    Event(SinkEvent),
+    Marker(Instant, String),
+    Log(DateTime<Local>, String, String),
+    Stop,
+}
+
+/// Sender of the running live export, used by `backend_log` to store log entries.
+#[derive(Default)]
+pub struct DatabaseLog(pub Mutex<Option<Sender<DatabaseMessage>>>);
+
+/// Stores a backend log entry in the running live export, if there is one.
+///
+/// The entry is skipped while the export is behind; it is still in the log file.
+pub fn log_entry(app: &tauri::AppHandle, level: &str, message: &str) {
+    if let Some(tap) = app.try_state::<DatabaseLog>() {
+        if let Some(sender) = tap.0.lock().unwrap().as_ref() {
+            let _ = sender.try_send(DatabaseMessage::Log(Local::now(), level.to_string(), message.to_string()));
+        }
+    }
+}
+
+/// Handle on a live export.
+pub struct DatabaseTask {
+    pub session_id: i64,
+    app: tauri::AppHandle,
+    sender: Sender<DatabaseMessage>,
+    forward: tauri::async_runtime::JoinHandle<()>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl DatabaseTask {
+    /// Adds a marker at the current time.
+    pub fn add_marker(&self, text: &str) {
+        if let Err(e) = self.sender.try_send(DatabaseMessage::Marker(Instant::now(), text.to_string())) {
+            println!("Failed to add marker to the database export: {}", e);
+        }
+    }
+
+    /// Stops the export once every event received so far is written.
+    pub async fn stop(self, sinks: &SinkRegistry) {
+        sinks.unregister(DATABASE_SINK);
+        let _ = self.forward.await;
+        if let Some(tap) = self.app.try_state::<DatabaseLog>() {
+            tap.0.lock().unwrap().take();
+        }
+        let _ = self.sender.send(DatabaseMessage::Stop).await;
+        let thread = self.thread;
+        let _ = tauri::async_runtime::spawn_blocking(move || thread.join()).await;
+    }
+}
+
+/// A new session of a live export, with the database opened but nothing exported yet.
+pub struct PreparedExport {
+    connection: Connection,
+    session_id: i64,
+}
+
+impl PreparedExport {
+    /// Removes the session again when the export does not start after all. Blocking.
+    pub fn abandon(self) {
+        if let Err(e) = self
+            .connection
+            .execute("DELETE FROM sessions WHERE id = ?1", params![self.session_id])
+        {
+            println!("Failed to remove database session {}: {}", self.session_id, e);
+        }
+    }
+}
+
+/// Opens the database and creates the session of a live export. Blocking,
+/// since the database may be locked by another writer for a while.
+pub fn prepare_export(
+    path: &Path,
+    header: &RecordingHeader,
+    app_version: &str,
+    session: &SessionInfo,
+) -> Result<PreparedExport, String> {
+    let connection = open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let started = header.started.to_rfc3339_opts(SecondsFormat::Micros, false);
+    let session_id = insert_session(
+        &connection,
+        &NewSession {
+            source: "live",
+            file: None,
+            port: Some(&header.port),
+            baud_rate: Some(header.baud_rate),
+            started: Some(started),
+            ended: None,
+            app_version: Some(app_version),
+            session,
+        },
+    )
+    .map_err(|e| format!("Could not create a session in {}: {}", path.display(), e))?;
+    Ok(PreparedExport { connection, session_id })
+}
+
+/// Starts a live export of the pipeline events into a prepared session.
+pub fn start_export(
+    app: tauri::AppHandle,
+    prepared: PreparedExport,
+    header: RecordingHeader,
+    sinks: &SinkRegistry,
+) -> DatabaseTask {
+    let PreparedExport { connection, session_id } = prepared;
+    // A slow database holds the forwarding back, so the sink drops and counts the excess.
+    let (sender, receiver) = mpsc::channel(SINK_CAPACITY);
+    let mut events = sinks.register(DATABASE_SINK);
+    let forward = {
+        let sender = sender.clone();
+        tauri::async_runtime::spawn(async move {
+            // Ends once the sink is unregistered and its pending events are read.
+            while let Some(event) = events.recv().await {
+                if sender.send(DatabaseMessage::Event(event)).await.is_err() {
+                    break;
+                }
+            }
+        })
+    };
+    if let Some(tap) = app.try_state::<DatabaseLog>() {
+        *tap.0.lock().unwrap() = Some(sender.clone());
+    }
+    let thread = {
+        let app = app.clone();
+        std::thread::spawn(move || run_export(app, connection, session_id, header, receiver))
+    };
+
+    DatabaseTask {
+        session_id,
+        app,
+        sender,
+        forward,
+        thread,
+    }
+}
+
+// Body of the export thread: writes the messages in batches, one transaction each.
+//
+// A batch that cannot be written stops the export, so a session never has
+// gaps; the rows lost with it are counted and reported.
+fn run_export(
+    app: tauri::AppHandle,
+    mut connection: Connection,
+    session_id: i64,
+    header: RecordingHeader,
+    mut receiver: Receiver<DatabaseMessage>,
+) {
+    let (mut frames, mut malformed) = (0, 0);
+    let mut stopped = false;
+
+    while !stopped {
+        let mut batch = match receiver.blocking_recv() {
+            Some(message) => vec![message],
+            None => break,
+        };
+        while batch.len() < BATCH_SIZE {
+            match receiver.try_recv() {
+                Ok(message) => batch.push(message),
+                Err(_) => break,
+            }
+        }
+        stopped = batch.iter().any(|message| matches!(message, DatabaseMessage::Stop));
+
+        match write_batch(&mut connection, session_id, &header, &batch) {
+            Ok((batch_frames, batch_malformed)) => {
+                frames += batch_frames;
+                malformed += batch_malformed;
+            }
+            Err(e) => {
+                // Nothing more is accepted; count what was already queued as lost too.
+                if let Some(tap) = app.try_state::<DatabaseLog>() {
+                    tap.0.lock().unwrap().take();
+                }
+                receiver.close();
+                let mut lost = rows(&batch);
+                while let Ok(message) = receiver.try_recv() {
+                    lost += rows(std::slice::from_ref(&message));
+                }
+                backend_log(
+                    &app,
+                    &format!(
+                        "Database export of session {} stopped: {}. {} row(s) were not written.",
+                        session_id, e, lost
+                    ),
+                    "ERROR",
+                );
+                break;
+            }
+        }
+    }
+
+    if let Err(e) = finish_session(&connection, session_id, frames, malformed) {
+        println!("Failed to close database session {}: {}", session_id, e);
+    }
+}
+
+// Writes a batch in one transaction. Returns the frames and malformed regions it held.
+fn write_batch(
+    connection: &mut Connection,
+    session_id: i64,
+    header: &RecordingHeader,
+    batch: &[DatabaseMessage],
+) -> rusqlite::Result<(u64, u64)> {
+    let started_unix_us = header.started.timestamp_micros();
+    let (mut frames, mut malformed) = (0, 0);
+    let transaction = connection.transaction()?;
+    for message in batch {
+        match message {
+            DatabaseMessage::Event(event) => match &event.frame {
+                Some(frame) => {
+                    let t_us = recording::elapsed_us(header.started_at, event.at);
+                    insert_frame(
+                        &transaction,
+                        session_id,
+                        Some(t_us),
+                        Some(started_unix_us + t_us as i64),
+                        None,
+                        Some(event.direction.as_str()),
+                        frame,
+                    )?;
+                    frames += 1;
+                }
+                None => malformed += 1,
+            },
+            DatabaseMessage::Marker(at, text) => {
+                let t_us = recording::elapsed_us(header.started_at, *at);
+                insert_marker(&transaction, session_id, t_us, Some(started_unix_us + t_us as i64), text)?;
+            }
+            DatabaseMessage::Log(time, level, message) => {
+                transaction
+                    .prepare_cached("INSERT INTO logs (session_id, unix_us, level, message) VALUES (?1, ?2, ?3, ?4)")?
+                    .execute(params![session_id, time.timestamp_micros(), level, message])?;
+            }
+            DatabaseMessage::Stop => {}
+        }
+    }
+    transaction.commit()?;
+    Ok((frames, malformed))
+}
+
+// Rows the messages would have written: frames, markers and log entries.
+fn rows(messages: &[DatabaseMessage]) -> u64 {
+    messages
+        .iter()
+        .filter(|message| match message {
+            DatabaseMessage::Event(event) => event.frame.is_some(),
+            DatabaseMessage::Marker(..) | DatabaseMessage::Log(..) => true,
+            DatabaseMessage::Stop => false,
+        })
+        .count() as u64
+}
+
+/// Lists the sessions of the database, newest first. Blocking.
+pub fn list_sessions(database: &Path) -> Result<Vec<SessionRow>, String> {
+    let connection = open(database).map_err(|e| format!("Could not open {}: {}", database.display(), e))?;
+    let mut statement = connection
+        .prepare(
+            "SELECT id, source, file, port, baud_rate, started, ended, app_version,
+                 operator, unit_serial, firmware, test_run, notes, frames, malformed
+             FROM sessions ORDER BY id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map([], |row| {
+            Ok(SessionRow {
+                id: row.get(0)?,
+                source: row.get(1)?,
+                file: row.get(2)?,
+                port: row.get(3)?,
+                baud_rate: row.get(4)?,
+                started: row.get(5)?,
+                ended: row.get(6)?,
+                app_version: row.get(7)?,
+                session: SessionInfo {
+                    operator: row.get(8)?,
+                    unit_serial: row.get(9)?,
+                    firmware: row.get(10)?,
+                    test_run: row.get(11)?,
+                    notes: row.get(12)?,
+                },
+                frames: row.get(13)?,
+                malformed: row.get(14)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| e.to_string())
+}
+
+/// Returns the frames matching `query`, in recording order. Blocking.
+pub fn query_frames(database: &Path, query: &FrameQuery) -> Result<Vec<FrameRow>, String> {
+    let connection = open(database).map_err(|e| format!("Could not open {}: {}", database.display(), e))?;
+
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    let mut filter = |condition: &'static str, value: Option<rusqlite::types::Value>| {
+        if let Some(value) = value {
+            conditions.push(condition);
+            values.push(value);
+        }
+    };
+    let parse_time = |time: &Option<String>| -> Result<Option<i64>, String> {
+        time.as_deref()
+            .map(|time| unix_us_of(time).ok_or_else(|| format!("{} is not an RFC 3339 time.", time)))
+            .transpose()
+    };
+    filter("session_id = ?", query.session_id.map(Into::into));
+    filter("command_id = ?", query.command_id.map(Into::into));
+    filter("hardware_id = ?", query.hardware_id.map(Into::into));
+    filter("direction = ?", query.direction.clone().map(Into::into));
+    filter("t_us >= ?", query.from_us.map(|t_us| (t_us as i64).into()));
+    filter("t_us <= ?", query.to_us.map(|t_us| (t_us as i64).into()));
+    filter("unix_us >= ?", parse_time(&query.from)?.map(Into::into));
+    filter("unix_us <= ?", parse_time(&query.to)?.map(Into::into));
+
+    let mut sql = "SELECT session_id, t_us, unix_us, byte_offset, direction, command_id, hardware_id, value
+                   FROM frames"
+        .to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" ORDER BY session_id, rowid LIMIT ? OFFSET ?");
+    values.push(query.limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT).into());
+    values.push(query.offset.unwrap_or(0).into());
+
+    let mut statement = connection.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(rusqlite::params_from_iter(values), |row| {
+            let command_id: u8 = row.get(5)?;
+            let unix_us: Option<i64> = row.get(2)?;
+            Ok(FrameRow {
+                session_id: row.get(0)?,
+                t_us: row.get(1)?,
+                time: unix_us.and_then(time_of),
+                byte_offset: row.get(3)?,
+                direction: row.get(4)?,
+                command: protocol::command_name(command_id),
+                command_id,
+                hardware_id: row.get(6)?,
+                value: row.get(7)?,
+                unit: convert::unit(command_id),
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(|e| e.to_string())
+}
@@ -0,0 +1,685 @@
+// src/diff.rs
+
+// Compares a reference recording with a candidate, typically a test run before
+// and after a firmware change. Frames are aligned either by their order (a
+// shortest edit script over the frame sequence) or by time (per channel, i.e.
+// direction, command and hardware ID). The report lists missing, inserted and
+// changed frames, and matched frames whose timing deviates beyond a tolerance.
+//
+// Also available without the UI, for regression checks:
+//
+//   app diff <reference> <candidate> [--align sequence|time] [--tolerance-ms <ms>]
+//       [--window-ms <ms>] [--timing start|previous] [--ignore <id,...>] [--output <path>]
+//
+// The exit code is 0 when the recordings match, 1 when they differ and 2 on errors.
+
+// Importing necessary crates and modules.
+use std::collections::BTreeMap; // Frames grouped by channel.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::convert::{self, Decoded};
+use crate::protocol::Frame;
+use crate::recording::Direction;
+
+// Differences listed in a report; the rest are only counted.
+const MAX_REPORTED_DIFFERENCES: usize = 1000;
+// Largest edit script searched when aligning by sequence.
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// How the frames of the two recordings are paired.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Alignment {
+    /// By frame order; timing is only compared, not used for pairing.
+    #[default]
+    Sequence,
+    /// By time within each channel; frames further apart than `window_ms` are not paired.
+    Time,
+}
+
+/// What the time of a matched frame is measured from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Timing {
+    /// The first compared frame of each recording.
+    #[default]
+    Start,
+    /// The previous matched frame, so an early delay does not shift everything after it.
+    Previous,
+}
+
+/// Options of a comparison.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiffOptions {
+    pub alignment: Alignment,
+    /// Timing deviation of a matched frame that is still accepted, in milliseconds.
+    pub tolerance_ms: f64,
+    /// Largest distance between two frames paired by time, in milliseconds.
+    pub window_ms: f64,
+    pub timing: Timing,
+    /// Commands left out of the comparison, e.g. periodic status frames.
+    pub ignore_command_ids: Vec<u8>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            alignment: Alignment::Sequence,
+            tolerance_ms: 5.0,
+            window_ms: 1000.0,
+            timing: Timing::Start,
+            ignore_command_ids: Vec::new(),
+        }
+    }
+}
+
+/// A frame of one of the compared recordings.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffFrame {
+    /// Position among the compared frames of the recording, from 0.
+    pub index: u64,
+    /// Line number (from 1) in a structured recording.
+    pub line: Option<u64>,
+    pub offset: u64,
+    pub t_us: Option<u64>,
+    pub direction: Option<Direction>,
+    pub command: &'static str,
+    pub command_id: u8,
+    pub hardware_id: u8,
+    pub value: u32,
+}
+
+/// Kind of a difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DifferenceKind {
+    /// In the reference only.
+    Missing,
+    /// In the candidate only.
+    Inserted,
+    /// Same channel, different value.
+    Changed,
+    /// Same frame, timing beyond the tolerance.
+    Timing,
+}
+
+/// One difference between the recordings.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Difference {
+    pub kind: DifferenceKind,
+    pub reference: Option<DiffFrame>,
+    pub candidate: Option<DiffFrame>,
+    /// Candidate time minus reference time, for paired frames of timed recordings.
+    pub deviation_ms: Option<f64>,
+}
+
+/// Result of a comparison, serialized as the machine-readable report.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffReport {
+    pub reference: String,
+    pub candidate: String,
+    pub options: DiffOptions,
+    pub reference_frames: u64,
+    pub candidate_frames: u64,
+    /// Frames paired without any difference.
+    pub equal: u64,
+    pub missing: u64,
+    pub inserted: u64,
+    pub changed: u64,
+    pub timing_deviations: u64,
+    /// Largest absolute deviation of a paired frame, when both recordings are timed.
+    pub max_deviation_ms: Option<f64>,
+    /// False when either recording is raw and timing could not be compared.
+    pub timed: bool,
+    /// True when nothing but equal frames were found.
+    pub identical: bool,
+    /// Only the first `MAX_REPORTED_DIFFERENCES` differences are listed.
+    pub differences: Vec<Difference>,
+}
+
+// Frames paired by the alignment: reference, candidate, or both.
+type Pair = (Option<usize>, Option<usize>);
+
+// Channel of a frame; only frames of the same channel can be paired.
+fn channel(frame: &DiffFrame) -> (Option<&'static str>, u8, u8) {
+    (frame.direction.map(|direction| direction.as_str()), frame.command_id, frame.hardware_id)
+}
+
+// Whether two frames are the same, timing aside.
+fn same(a: &DiffFrame, b: &DiffFrame) -> bool {
+    channel(a) == channel(b) && a.value == b.value
+}
+
+// Decodes the compared frames of a recording; also returns whether it is structured.
+fn load(path: &Path, options: &DiffOptions) -> Result<(Vec<DiffFrame>, bool), String> {
+    let mut frames = Vec::new();
+    let structured = convert::decode(path, |item| {
+        if let Decoded::Frame {
+            offset,
+            line,
+            t_us,
+            direction,
+            frame,
+        } = item
+        {
+            if !options.ignore_command_ids.contains(&frame.command_id) {
+                frames.push(diff_frame(frames.len() as u64, line, offset, t_us, direction, &frame));
+            }
+        }
+        Ok(())
+    })
+    .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    Ok((frames, structured))
+}
+
+// Frame of the report.
+fn diff_frame(
+    index: u64,
+    line: Option<u64>,
+    offset: u64,
+    t_us: Option<u64>,
+    direction: Option<Direction>,
+    frame: &Frame,
+) -> DiffFrame {
+    DiffFrame {
+        index,
+        line,
+        offset,
+        t_us,
+        direction,
+        command: frame.command_name(),
+        command_id: frame.command_id,
+        hardware_id: frame.hardware_id,
+        value: frame.value,
+    }
+}
+
+// Shortest edit script between `a` and `b` (Myers), or `None` beyond `max_edits`.
+fn edit_script(a: &[DiffFrame], b: &[DiffFrame], max_edits: usize) -> Option<Vec<Pair>> {
+    // The common prefix and suffix need no search.
+    let prefix = a.iter().zip(b).take_while(|(a, b)| same(a, b)).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| same(a, b))
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let (n, m) = (middle_a.len() as isize, middle_b.len() as isize);
+    let limit = max_edits.min(middle_a.len() + middle_b.len()) as isize;
+
+    // v[k + offset] is the furthest x reached on diagonal k; `trace[d]` keeps
+    // diagonals -d-1..=d+1 as they were before step d, for the backtracking.
+    let offset = limit + 1;
+    let mut v = vec![0isize; 2 * limit as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = None;
+    'search: for d in 0..=limit {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let index = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && same(&middle_a[x as usize], &middle_b[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let edits = found?;
+
+    let mut pairs = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (0..=edits).rev() {
+        let previous = &trace[d as usize];
+        let at = |k: isize| previous[(k + d + 1) as usize];
+        let k = x - y;
+        let previous_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let previous_x = at(previous_k);
+        let previous_y = previous_x - previous_k;
+        while x > previous_x && y > previous_y {
+            x -= 1;
+            y -= 1;
+            pairs.push((Some(prefix + x as usize), Some(prefix + y as usize)));
+        }
+        if d > 0 {
+            if x == previous_x {
+                pairs.push((None, Some(prefix + previous_y as usize)));
+            } else {
+                pairs.push((Some(prefix + previous_x as usize), None));
+            }
+        }
+        x = previous_x;
+        y = previous_y;
+    }
+    pairs.reverse();
+
+    let mut script: Vec<Pair> = (0..prefix).map(|i| (Some(i), Some(i))).collect();
+    script.extend(pair_changes(pairs, a, b));
+    script.extend((0..suffix).map(|i| (Some(a.len() - suffix + i), Some(b.len() - suffix + i))));
+    Some(script)
+}
+
+// Pairs the removals and additions of each run of edits that share a channel,
+// in order, so a changed value is reported as one change.
+fn pair_changes(script: Vec<Pair>, a: &[DiffFrame], b: &[DiffFrame]) -> Vec<Pair> {
+    let mut result = Vec::with_capacity(script.len());
+    let mut run: Vec<Pair> = Vec::new();
+    let flush = |run: &mut Vec<Pair>, result: &mut Vec<Pair>| {
+        let removed: Vec<usize> = run.iter().filter_map(|pair| pair.0).collect();
+        let added: Vec<usize> = run.iter().filter_map(|pair| pair.1).collect();
+        let mut used = vec![false; added.len()];
+        let mut next = 0;
+        for &i in &removed {
+            match (next..added.len()).find(|&j| channel(&a[i]) == channel(&b[added[j]])) {
+                Some(j) => {
+                    used[j] = true;
+                    next = j + 1;
+                    result.push((Some(i), Some(added[j])));
+                }
+                None => result.push((Some(i), None)),
+            }
+        }
+        result.extend(added.iter().zip(&used).filter(|(_, used)| !**used).map(|(&j, _)| (None, Some(j))));
+        run.clear();
+    };
+    for pair in script {
+        match pair {
+            (Some(_), Some(_)) => {
+                flush(&mut run, &mut result);
+                result.push(pair);
+            }
+            _ => run.push(pair),
+        }
+    }
+    flush(&mut run, &mut result);
+    result
+}
+
+// Pairs the frames of each channel that are closest in time, within `window_us`.
+fn align_by_time(a: &[DiffFrame], b: &[DiffFrame], window_us: u64) -> Vec<Pair> {
+    let relative = |frames: &[DiffFrame]| {
+        let first = frames.first().and_then(|frame| frame.t_us).unwrap_or(0);
+        move |frame: &DiffFrame| frame.t_us.unwrap_or(0).saturating_sub(first)
+    };
+    let (time_a, time_b) = (relative(a), relative(b));
+
+    let mut channels: BTreeMap<_, (Vec<usize>, Vec<usize>)> = BTreeMap::new();
+    for (i, frame) in a.iter().enumerate() {
+        channels.entry(channel(frame)).or_default().0.push(i);
+    }
+    for (j, frame) in b.iter().enumerate() {
+        channels.entry(channel(frame)).or_default().1.push(j);
+    }
+
+    let mut pairs = Vec::new();
+    for (of_a, of_b) in channels.values() {
+        let (mut i, mut j) = (0, 0);
+        while i < of_a.len() || j < of_b.len() {
+            match (of_a.get(i), of_b.get(j)) {
+                (Some(&x), Some(&y)) if time_a(&a[x]).abs_diff(time_b(&b[y])) <= window_us => {
+                    pairs.push((Some(x), Some(y)));
+                    i += 1;
+                    j += 1;
+                }
+                (Some(&x), Some(&y)) if time_a(&a[x]) < time_b(&b[y]) => {
+                    pairs.push((Some(x), None));
+                    i += 1;
+                }
+                (Some(&x), None) => {
+                    pairs.push((Some(x), None));
+                    i += 1;
+                }
+                (_, Some(&y)) => {
+                    pairs.push((None, Some(y)));
+                    j += 1;
+                }
+                (None, None) => break,
+            }
+        }
+    }
+    // Back into the order of the session.
+    pairs.sort_by_key(|&(x, y)| match (x, y) {
+        (Some(x), _) => (time_a(&a[x]), 0),
+        (None, Some(y)) => (time_b(&b[y]), 1),
+        (None, None) => (0, 2),
+    });
+    pairs
+}
+
+/// Compares two recordings and writes the report to `output` when given. Blocking.
+pub fn compare(
+    reference: &Path,
+    candidate: &Path,
+    options: DiffOptions,
+    output: Option<&Path>,
+) -> Result<DiffReport, String> {
+    let (a, a_structured) = load(reference, &options)?;
+    let (b, b_structured) = load(candidate, &options)?;
+    let timed = a_structured && b_structured;
+
+    let pairs = match options.alignment {
+        Alignment::Sequence => edit_script(&a, &b, MAX_EDIT_DISTANCE).ok_or_else(|| {
+            format!(
+                "The recordings differ in more than {} frames; compare them by time instead.",
+                MAX_EDIT_DISTANCE
+            )
+        })?,
+        Alignment::Time if timed => align_by_time(&a, &b, (options.window_ms * 1000.0) as u64),
+        Alignment::Time => return Err("Raw recordings carry no timestamps; compare them by sequence.".to_string()),
+    };
+
+    let mut report = DiffReport {
+        reference: reference.to_string_lossy().to_string(),
+        candidate: candidate.to_string_lossy().to_string(),
+        options,
+        reference_frames: a.len() as u64,
+        candidate_frames: b.len() as u64,
+        equal: 0,
+        missing: 0,
+        inserted: 0,
+        changed: 0,
+        timing_deviations: 0,
+        max_deviation_ms: None,
+        timed,
+        identical: false,
+        differences: Vec::new(),
+    };
+    let start = (
+        a.first().and_then(|frame| frame.t_us).unwrap_or(0),
+        b.first().and_then(|frame| frame.t_us).unwrap_or(0),
+    );
+    let mut previous = start;
+
+    for pair in pairs {
+        let (kind, deviation_ms) = match pair {
+            (Some(_), None) => (Some(DifferenceKind::Missing), None),
+            (None, Some(_)) => (Some(DifferenceKind::Inserted), None),
+            (Some(i), Some(j)) => {
+                let deviation_ms = match (a[i].t_us, b[j].t_us) {
+                    (Some(t_a), Some(t_b)) if timed => {
+                        let (from_a, from_b) = match report.options.timing {
+                            Timing::Start => start,
+                            Timing::Previous => previous,
+                        };
+                        previous = (t_a, t_b);
+                        Some((t_b as f64 - from_b as f64 - (t_a as f64 - from_a as f64)) / 1000.0)
+                    }
+                    _ => None,
+                };
+                if let Some(deviation) = deviation_ms.map(f64::abs) {
+                    report.max_deviation_ms = Some(report.max_deviation_ms.map_or(deviation, |max| max.max(deviation)));
+                }
+                let kind = if a[i].value != b[j].value {
+                    Some(DifferenceKind::Changed)
+                } else if deviation_ms.is_some_and(|deviation| deviation.abs() > report.options.tolerance_ms) {
+                    Some(DifferenceKind::Timing)
+                } else {
+                    None
+                };
+                (kind, deviation_ms)
+            }
+            (None, None) => continue,
+        };
+
+        let kind = match kind {
+            Some(kind) => kind,
+            None => {
+                report.equal += 1;
+                continue;
+            }
+        };
+        match kind {
+            DifferenceKind::Missing => report.missing += 1,
+            DifferenceKind::Inserted => report.inserted += 1,
+            DifferenceKind::Changed => report.changed += 1,
+            DifferenceKind::Timing => report.timing_deviations += 1,
+        }
+        if report.differences.len() < MAX_REPORTED_DIFFERENCES {
+            report.differences.push(Difference {
+                kind,
+                reference: pair.0.map(|i| a[i].clone()),
+                candidate: pair.1.map(|j| b[j].clone()),
+                deviation_ms,
+            });
+        }
+    }
+    report.identical =
+        report.missing == 0 && report.inserted == 0 && report.changed == 0 && report.timing_deviations == 0;
+
+    if let Some(output) = output {
+        let json = serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?;
+        std::fs::write(output, json).map_err(|e| format!("Could not write {}: {}", output.display(), e))?;
+    }
+    Ok(report)
+}
+
+// Applies one option of the command line; `None` when its value is invalid.
+fn apply_option(options: &mut DiffOptions, output: &mut Option<PathBuf>, name: &str, value: &str) -> Option<()> {
+    match name {
+        "--align" => {
+            options.alignment = match value {
+                "sequence" => Alignment::Sequence,
+                "time" => Alignment::Time,
+                _ => return None,
+            }
+        }
+        "--timing" => {
+            options.timing = match value {
+                "start" => Timing::Start,
+                "previous" => Timing::Previous,
+                _ => return None,
+            }
+        }
+        "--tolerance-ms" => options.tolerance_ms = value.parse().ok()?,
+        "--window-ms" => options.window_ms = value.parse().ok()?,
+        "--ignore" => {
+            options.ignore_command_ids = value
+                .split(',')
+                .map(|id| id.trim().parse().ok())
+                .collect::<Option<Vec<u8>>>()?
+        }
+        _ => *output = Some(PathBuf::from(value)),
+    }
+    Some(())
+}
+
+/// Runs `compare` from the command line and returns the process exit code.
+///
+/// The report goes to `--output`, or to stdout as JSON.
+pub fn run_cli(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: app diff <reference> <candidate> [--align sequence|time] [--tolerance-ms <ms>] \
+                         [--window-ms <ms>] [--timing start|previous] [--ignore <id,...>] [--output <path>]";
+    let mut paths = Vec::new();
+    let mut options = DiffOptions::default();
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--align" | "--tolerance-ms" | "--window-ms" | "--timing" | "--ignore" | "--output" => {
+                match args.next() {
+                    Some(value) => value.as_str(),
+                    None => {
+                        eprintln!("Missing value for {}.", arg);
+                        return 2;
+                    }
+                }
+            }
+            _ => {
+                paths.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        if apply_option(&mut options, &mut output, arg, value).is_none() {
+            eprintln!("Invalid value {} for {}.", value, arg);
+            return 2;
+        }
+    }
+    let (reference, candidate) = match paths.as_slice() {
+        [reference, candidate] => (reference, candidate),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    };
+
+    match compare(reference, candidate, options, output.as_deref()) {
+        Ok(report) => {
+            if output.is_none() {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            }
+            eprintln!(
+                "{} equal, {} missing, {} inserted, {} changed, {} timing deviation(s).",
+                report.equal, report.missing, report.inserted, report.changed, report.timing_deviations
+            );
+            if report.identical {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Received frames of `(command_id, hardware_id, value)`.
+    fn frames(values: &[(u8, u8, u32)]) -> Vec<DiffFrame> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, &(command_id, hardware_id, value))| {
+                diff_frame(index as u64, None, 0, None, Some(Direction::Rx), &Frame::new(command_id, hardware_id, value))
+            })
+            .collect()
+    }
+
+    // Every frame of both sides appears once and in order, and pairs share a channel.
+    fn assert_complete(script: &[Pair], a: &[DiffFrame], b: &[DiffFrame]) {
+        let left: Vec<usize> = script.iter().filter_map(|pair| pair.0).collect();
+        let right: Vec<usize> = script.iter().filter_map(|pair| pair.1).collect();
+        assert_eq!(left, (0..a.len()).collect::<Vec<_>>());
+        assert_eq!(right, (0..b.len()).collect::<Vec<_>>());
+        for pair in script {
+            if let (Some(i), Some(j)) = *pair {
+                assert_eq!(channel(&a[i]), channel(&b[j]));
+            }
+        }
+    }
+
+    #[test]
+    fn identical_inputs_pair_every_frame() {
+        let a = frames(&[(7, 1, 1), (2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(7, 1, 1), (2, 1, 50), (20, 3, 1)]);
+        let script = edit_script(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        assert_eq!(script, vec![(Some(0), Some(0)), (Some(1), Some(1)), (Some(2), Some(2))]);
+    }
+
+    #[test]
+    fn empty_inputs() {
+        let empty = frames(&[]);
+        let b = frames(&[(7, 1, 1), (2, 1, 50)]);
+        assert_eq!(edit_script(&empty, &empty, MAX_EDIT_DISTANCE), Some(vec![]));
+        assert_eq!(
+            edit_script(&empty, &b, MAX_EDIT_DISTANCE),
+            Some(vec![(None, Some(0)), (None, Some(1))])
+        );
+        assert_eq!(
+            edit_script(&b, &empty, MAX_EDIT_DISTANCE),
+            Some(vec![(Some(0), None), (Some(1), None)])
+        );
+    }
+
+    #[test]
+    fn pure_insertion() {
+        let a = frames(&[(7, 1, 1), (2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(7, 1, 1), (8, 2, 0), (2, 1, 50), (20, 3, 1)]);
+        let script = edit_script(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        assert_eq!(
+            script,
+            vec![(Some(0), Some(0)), (None, Some(1)), (Some(1), Some(2)), (Some(2), Some(3))]
+        );
+        assert_complete(&script, &a, &b);
+    }
+
+    #[test]
+    fn pure_deletion() {
+        let a = frames(&[(7, 1, 1), (8, 2, 0), (2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(7, 1, 1), (2, 1, 50), (20, 3, 1)]);
+        let script = edit_script(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        assert_eq!(
+            script,
+            vec![(Some(0), Some(0)), (Some(1), None), (Some(2), Some(1)), (Some(3), Some(2))]
+        );
+        assert_complete(&script, &a, &b);
+    }
+
+    #[test]
+    fn changed_value_is_paired() {
+        let a = frames(&[(7, 1, 1), (2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(7, 1, 1), (2, 1, 60), (20, 3, 1)]);
+        let script = edit_script(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        assert_eq!(script, vec![(Some(0), Some(0)), (Some(1), Some(1)), (Some(2), Some(2))]);
+        assert_ne!(a[1].value, b[1].value);
+    }
+
+    #[test]
+    fn changes_pair_only_within_a_channel() {
+        let a = frames(&[(2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(2, 1, 60), (20, 4, 1)]);
+        let script = edit_script(&a, &b, MAX_EDIT_DISTANCE).unwrap();
+        assert_eq!(script, vec![(Some(0), Some(0)), (Some(1), None), (None, Some(1))]);
+        assert_complete(&script, &a, &b);
+    }
+
+    #[test]
+    fn pair_changes_keeps_unmatched_edits() {
+        let a = frames(&[(2, 1, 50), (20, 3, 1)]);
+        let b = frames(&[(20, 3, 0), (2, 1, 60)]);
+        let script = vec![(Some(0), None), (Some(1), None), (None, Some(0)), (None, Some(1))];
+        // The first removal takes the second addition, so the first addition is left over.
+        assert_eq!(
+            pair_changes(script, &a, &b),
+            vec![(Some(0), Some(1)), (Some(1), None), (None, Some(0))]
+        );
+    }
+
+    #[test]
+    fn stops_beyond_the_edit_limit() {
+        let a = frames(&[(7, 1, 1), (2, 1, 50), (2, 1, 51), (20, 3, 1)]);
+        let b = frames(&[(7, 1, 1), (2, 1, 60), (2, 1, 61), (20, 3, 1)]);
+        // Two changed values take four edits.
+        assert_eq!(edit_script(&a, &b, 3), None);
+        assert_complete(&edit_script(&a, &b, 4).unwrap(), &a, &b);
+    }
+
+    #[test]
+    fn stops_beyond_max_edit_distance() {
+        let half = MAX_EDIT_DISTANCE / 2;
+        let a = frames(&vec![(2, 1, 0); half]);
+        let b = frames(&vec![(2, 2, 0); half]);
+        assert!(edit_script(&a, &b, MAX_EDIT_DISTANCE).is_some());
+        let a = frames(&vec![(2, 1, 0); half + 1]);
+        let b = frames(&vec![(2, 2, 0); half + 1]);
+        assert_eq!(edit_script(&a, &b, MAX_EDIT_DISTANCE), None);
+    }
+}
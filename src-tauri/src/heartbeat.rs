@@ -0,0 +1,260 @@
+// src/heartbeat.rs
+
+// Importing necessary crates and modules.
+use std::sync::{Arc, Mutex}; // Shared, thread-safe link state.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio_util::sync::CancellationToken; // Token used to stop the heartbeat task.
+
+use crate::protocol::Frame;
+use crate::safe_state;
+use crate::serial_wrapper::backend_log;
+use crate::writer::WriterHandle;
+use crate::AppData;
+
+/// Attempts to reopen the port after the link was lost.
+pub const RECONNECT_ATTEMPTS: u32 = 5;
+/// Wait before each attempt to reopen the port.
+pub const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// What to do once the link is considered lost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LossAction {
+    /// Only report the loss.
+    Nothing,
+    /// Close the port and open it again.
+    Reconnect,
+    /// Send the configured safe-state sequence.
+    SafeStop,
+}
+
+/// Configuration of the optional link heartbeat.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartbeatConfig {
+    pub enabled: bool,
+    /// Time between two pings, in milliseconds.
+    pub interval_ms: u64,
+    /// Time the firmware has to answer a ping, in milliseconds.
+    pub timeout_ms: u64,
+    /// Command ID of the no-op frame; any received frame with this ID counts as the answer.
+    pub command_id: u8,
+    pub hardware_id: u8,
+    /// Consecutive missed heartbeats after which the link is reported as lost.
+    pub lost_after: u32,
+    pub on_loss: LossAction,
+}
+
+impl Default for HeartbeatConfig {
+    fn default() -> Self {
+        HeartbeatConfig {
+            enabled: false,
+            interval_ms: 1000,
+            timeout_ms: 500,
+            command_id: 0,
+            hardware_id: 0,
+            lost_after: 3,
+            on_loss: LossAction::Nothing,
+        }
+    }
+}
+
+/// Quality of the serial link as reported in the `linkQuality` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkQuality {
+    Good,
+    Degraded,
+    Lost,
+}
+
+/// Payload of the `linkQuality` event and of `get_link_quality`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkQualityReport {
+    pub quality: LinkQuality,
+    pub last_rtt_ms: Option<f64>,
+    pub average_rtt_ms: Option<f64>,
+    pub missed_heartbeats: u64,
+    pub consecutive_missed: u32,
+    pub decode_errors: u64,
+}
+
+// Mutable part of the link monitor.
+struct LinkState {
+    command_id: u8,
+    pending_since: Option<Instant>,
+    last_rtt: Option<Duration>,
+    average_rtt: Option<Duration>,
+    missed_total: u64,
+    missed_in_row: u32,
+    decode_errors: u64,
+    decode_errors_seen: u64,
+    quality: LinkQuality,
+}
+
+/// Link statistics shared between the reader and the heartbeat task.
+pub struct LinkMonitor {
+    state: Mutex<LinkState>,
+}
+
+impl LinkMonitor {
+    pub fn new(config: &HeartbeatConfig) -> Arc<Self> {
+        Arc::new(LinkMonitor {
+            state: Mutex::new(LinkState {
+                command_id: config.command_id,
+                pending_since: None,
+                last_rtt: None,
+                average_rtt: None,
+                missed_total: 0,
+                missed_in_row: 0,
+                decode_errors: 0,
+                decode_errors_seen: 0,
+                quality: LinkQuality::Good,
+            }),
+        })
+    }
+
+    /// Starts counting missed heartbeats over after the port was reopened; totals are kept.
+    pub fn on_reconnect(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.pending_since = None;
+        state.missed_in_row = 0;
+        state.quality = LinkQuality::Good;
+    }
+
+    /// Called by the reader for every decoded frame.
+    pub fn on_frame(&self, frame: &Frame) {
+        let mut state = self.state.lock().unwrap();
+        if frame.command_id != state.command_id {
+            return;
+        }
+        if let Some(sent_at) = state.pending_since.take() {
+            let rtt = sent_at.elapsed();
+            state.last_rtt = Some(rtt);
+            // Exponential moving average, weighting the newest sample by 1/8.
+            state.average_rtt = Some(match state.average_rtt {
+                Some(average) => (average * 7 + rtt) / 8,
+                None => rtt,
+            });
+            state.missed_in_row = 0;
+        }
+    }
+
+    /// Called by the reader for bytes that could not be decoded.
+    pub fn on_decode_error(&self) {
+        self.state.lock().unwrap().decode_errors += 1;
+    }
+
+    /// Returns the current statistics.
+    pub fn report(&self) -> LinkQualityReport {
+        let state = self.state.lock().unwrap();
+        LinkQualityReport {
+            quality: state.quality,
+            last_rtt_ms: state.last_rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            average_rtt_ms: state.average_rtt.map(|rtt| rtt.as_secs_f64() * 1000.0),
+            missed_heartbeats: state.missed_total,
+            consecutive_missed: state.missed_in_row,
+            decode_errors: state.decode_errors,
+        }
+    }
+
+    // Accounts for an unanswered ping, updates the quality and tells whether a new ping is due.
+    fn tick(&self, timeout: Duration, lost_after: u32) -> (LinkQuality, bool) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(sent_at) = state.pending_since {
+            if now.duration_since(sent_at) >= timeout {
+                state.pending_since = None;
+                state.missed_total += 1;
+                state.missed_in_row += 1;
+            }
+        }
+        // Only one ping is in flight at a time, so a slow answer is never counted twice.
+        let send_ping = state.pending_since.is_none();
+        if send_ping {
+            state.pending_since = Some(now);
+        }
+
+        let new_decode_errors = state.decode_errors > state.decode_errors_seen;
+        state.decode_errors_seen = state.decode_errors;
+
+        state.quality = if state.missed_in_row >= lost_after.max(1) {
+            LinkQuality::Lost
+        } else if state.missed_in_row > 0 || new_decode_errors {
+            LinkQuality::Degraded
+        } else {
+            LinkQuality::Good
+        };
+        (state.quality, send_ping)
+    }
+}
+
+/// Function to start the heartbeat task of a connection.
+pub fn start_heartbeat_task(
+    app: tauri::AppHandle,
+    config: HeartbeatConfig,
+    monitor: Arc<LinkMonitor>,
+    writer: WriterHandle,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        backend_log(&app, "Heartbeat task started.", "INFO");
+        let ping = Frame::new(config.command_id, config.hardware_id, 0).encode().to_vec();
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let mut interval = tokio::time::interval(Duration::from_millis(config.interval_ms.max(1)));
+        let mut previous = LinkQuality::Good;
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = interval.tick() => {}
+            }
+
+            let (quality, send_ping) = monitor.tick(timeout, config.lost_after);
+            if let Err(e) = app.emit_all("linkQuality", monitor.report()) {
+                println!("Failed to emit link quality to the frontend: {:?}", e);
+            }
+
+            if quality != previous {
+                let level = match quality {
+                    LinkQuality::Good => "SUCCESS",
+                    LinkQuality::Degraded => "WARNING",
+                    LinkQuality::Lost => "ERROR",
+                };
+                backend_log(&app, &format!("Link quality changed to {:?}.", quality), level);
+            }
+
+            if quality == LinkQuality::Lost && previous != LinkQuality::Lost {
+                match config.on_loss {
+                    LossAction::Nothing => {}
+                    LossAction::SafeStop => {
+                        backend_log(&app, "Link lost: sending safe state.", "WARNING");
+                        let config = app.state::<AppData>().0.lock().unwrap().safe_state_config.clone();
+                        safe_state::send_safe_state(&app, &writer, &config).await;
+                    }
+                    LossAction::Reconnect => {
+                        backend_log(&app, "Link lost: reconnecting.", "WARNING");
+                        let app = app.clone();
+                        // The reconnect replaces this task, so run it detached from it.
+                        tauri::async_runtime::spawn(async move {
+                            crate::reconnect_port(&app).await;
+                        });
+                        break;
+                    }
+                }
+            }
+            previous = quality;
+
+            // A ping rejected by a full queue simply shows up as a missed heartbeat.
+            if send_ping {
+                let _ = writer.enqueue_quiet(ping.clone());
+            }
+        }
+
+        backend_log(&app, "Heartbeat task terminated.", "INFO");
+    })
+}
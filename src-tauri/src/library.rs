@@ -0,0 +1,303 @@
+// src/library.rs
+
+// Read and file operations on the recordings of the configured folder. The
+// frontend only ever passes file names; they are resolved inside the folder
+// here, so it needs no filesystem access of its own. Files still being written
+// carry the `.partial` extension and are never listed or touched.
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local, SecondsFormat}; // Crate for date and time handling.
+use std::io::{BufRead, Read}; // Sync I/O traits.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::compression::{self, Compression};
+use crate::convert;
+use crate::protocol::{DecodeEvent, FrameDecoder};
+use crate::recording::{self, Marker};
+use crate::retention::{self, RECORDING_PREFIX};
+use crate::sidecar::{self, Sidecar};
+use crate::split;
+
+/// A recording of the library, as returned by `list_recordings`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSummary {
+    pub name: String,
+    /// Size of the file on disk.
+    pub size: u64,
+    /// RFC 3339 timestamps.
+    pub modified: String,
+    pub compression: Compression,
+    /// "structured" for text recordings, "raw" for legacy binary dumps.
+    pub kind: &'static str,
+    pub frames: u64,
+    pub started: Option<String>,
+    pub ended: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub sidecar: Option<Sidecar>,
+}
+
+/// A recording opened for replay or export.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingDetails {
+    pub summary: RecordingSummary,
+    /// Full path, for `read_recording_lines` and `convert_recording`.
+    pub path: String,
+    pub port: Option<String>,
+    pub baud_rate: Option<u32>,
+    pub markers: Vec<Marker>,
+}
+
+// What a scan of a file without a sidecar finds.
+#[derive(Default)]
+struct Scan {
+    frames: u64,
+    started: Option<DateTime<Local>>,
+    last_t_us: Option<u64>,
+    port: Option<String>,
+    baud_rate: Option<u32>,
+}
+
+// Reads the header and counts the frames of a structured recording.
+fn scan_structured(path: &Path) -> std::io::Result<Scan> {
+    let reader = compression::open_recording(path)?;
+    let mut scan = Scan::default();
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if let Some(value) = line.strip_prefix("#started\t") {
+            scan.started = DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|time| time.with_timezone(&Local));
+        } else if let Some(value) = line.strip_prefix("#port\t") {
+            scan.port = Some(value.to_string());
+        } else if let Some(value) = line.strip_prefix("#baud\t") {
+            scan.baud_rate = value.parse().ok();
+        } else if !line.starts_with('#') {
+            let mut fields = line.split('\t');
+            if let Some(t_us) = fields.next().and_then(|t_us| t_us.parse::<u64>().ok()) {
+                scan.last_t_us = Some(t_us);
+            }
+            if fields.count() == 6 {
+                scan.frames += 1;
+            }
+        }
+    }
+    Ok(scan)
+}
+
+// Counts the frames of a legacy binary recording.
+fn scan_raw(path: &Path) -> std::io::Result<Scan> {
+    let mut file = std::fs::File::open(path)?;
+    let mut decoder = FrameDecoder::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut scan = Scan::default();
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        scan.frames += decoder
+            .feed(&buffer[..read])
+            .iter()
+            .filter(|event| matches!(event, DecodeEvent::Frame(..)))
+            .count() as u64;
+    }
+    Ok(scan)
+}
+
+// Reads the sidecar of a recording, if it has a valid one.
+fn read_sidecar(path: &Path) -> Option<Sidecar> {
+    std::fs::read(sidecar::sidecar_path(path))
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+}
+
+// Milliseconds between two RFC 3339 timestamps.
+fn duration_ms(started: &str, ended: &str) -> Option<u64> {
+    let started = DateTime::parse_from_rfc3339(started).ok()?;
+    let ended = DateTime::parse_from_rfc3339(ended).ok()?;
+    u64::try_from((ended - started).num_milliseconds()).ok()
+}
+
+// Builds the summary of one recording, and the scan when one was needed.
+fn summarize(path: &Path, size: u64, modified: std::time::SystemTime) -> (RecordingSummary, Option<Scan>) {
+    let structured = convert::is_structured(path).unwrap_or(false);
+    let mut summary = RecordingSummary {
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        size,
+        modified: DateTime::<Local>::from(modified).to_rfc3339_opts(SecondsFormat::Secs, false),
+        compression: compression::detect(path).unwrap_or(Compression::None),
+        kind: if structured { "structured" } else { "raw" },
+        frames: 0,
+        started: None,
+        ended: None,
+        duration_ms: None,
+        sidecar: read_sidecar(path),
+    };
+
+    // The sidecar of a complete file already holds the counts.
+    if let Some(meta) = summary.sidecar.as_ref().filter(|meta| meta.ended.is_some()) {
+        summary.frames = meta.frames;
+        summary.started = Some(meta.started.clone());
+        summary.ended = meta.ended.clone();
+        summary.duration_ms = meta.ended.as_deref().and_then(|ended| duration_ms(&meta.started, ended));
+        return (summary, None);
+    }
+
+    let scan = if structured { scan_structured(path) } else { scan_raw(path) };
+    let scan = match scan {
+        Ok(scan) => scan,
+        Err(_) => return (summary, None),
+    };
+    summary.frames = scan.frames;
+    if let Some(started) = scan.started {
+        summary.started = Some(started.to_rfc3339_opts(SecondsFormat::Micros, false));
+        if let Some(t_us) = scan.last_t_us {
+            summary.ended = Some(recording::wall_time(started, t_us));
+            summary.duration_ms = Some(t_us / 1000);
+        }
+    }
+    (summary, Some(scan))
+}
+
+/// Lists the recordings in `folder`, newest first. Blocking.
+pub fn list(folder: &Path) -> Vec<RecordingSummary> {
+    let mut summaries: Vec<RecordingSummary> = retention::list_recordings(folder)
+        .into_iter()
+        .map(|file| summarize(&file.path, file.size, file.modified).0)
+        .collect();
+    summaries.reverse();
+    summaries
+}
+
+/// Resolves the name of a complete recording inside `folder`.
+pub fn resolve(folder: &Path, name: &str) -> Result<PathBuf, String> {
+    let valid = !name.contains(['/', '\\']) && retention::is_recording_name(name);
+    let path = folder.join(name);
+    if !valid || !path.is_file() {
+        return Err(format!("{} is not a recording of {}.", name, folder.display()));
+    }
+    Ok(path)
+}
+
+// Extensions of a recording name, e.g. ".txt.gz".
+fn extensions(name: &str) -> &str {
+    name.find(".txt").map_or("", |index| &name[index..])
+}
+
+/// Renames a recording, its sidecar and its channel folder, returning the new name.
+///
+/// The prefix and extensions are kept, so the file stays part of the library.
+pub fn rename(folder: &Path, name: &str, new_name: &str) -> Result<String, String> {
+    let path = resolve(folder, name)?;
+    let new_name = new_name.trim();
+    if new_name.is_empty() || new_name.contains(['/', '\\']) || new_name.starts_with('.') {
+        return Err(format!("{} is not a valid file name.", new_name));
+    }
+
+    // Extensions typed by the user are replaced by those of the file.
+    let stem = &new_name[..new_name.len() - extensions(new_name).len()];
+    let mut target = format!("{}{}", stem, extensions(name));
+    if !target.starts_with(RECORDING_PREFIX) {
+        target = format!("{}_{}", RECORDING_PREFIX, target);
+    }
+    let target_path = folder.join(&target);
+    if target_path.exists()
+        || sidecar::sidecar_path(&target_path).exists()
+        || split::channel_folder(&target_path).exists()
+    {
+        return Err(format!("{} already exists.", target));
+    }
+
+    std::fs::rename(&path, &target_path).map_err(|e| format!("Could not rename {}: {}", name, e))?;
+    let old_sidecar = sidecar::sidecar_path(&path);
+    if let Some(mut meta) = read_sidecar(&path) {
+        meta.file = target.clone();
+        sidecar::save_blocking(&meta, &target_path)
+            .and_then(|_| std::fs::remove_file(&old_sidecar))
+            .map_err(|e| format!("Renamed {}, but its sidecar could not be updated: {}", name, e))?;
+    } else if old_sidecar.exists() {
+        std::fs::rename(&old_sidecar, sidecar::sidecar_path(&target_path))
+            .map_err(|e| format!("Renamed {}, but not its sidecar: {}", name, e))?;
+    }
+    split::move_channel_folder(&path, &target_path)
+        .map_err(|e| format!("Renamed {}, but not its channel streams: {}", name, e))?;
+    Ok(target)
+}
+
+/// Deletes a recording, its sidecar and its channel folder.
+pub fn delete(folder: &Path, name: &str) -> Result<(), String> {
+    let path = resolve(folder, name)?;
+    std::fs::remove_file(&path).map_err(|e| format!("Could not delete {}: {}", name, e))?;
+    let sidecar = sidecar::sidecar_path(&path);
+    if sidecar.exists() {
+        std::fs::remove_file(&sidecar)
+            .map_err(|e| format!("Deleted {}, but not its sidecar: {}", name, e))?;
+    }
+    split::remove_channel_folder(&path)
+        .map_err(|e| format!("Deleted {}, but not its channel streams: {}", name, e))?;
+    Ok(())
+}
+
+/// Moves a recording, its sidecar and its channel folder into `archive`.
+pub fn archive(folder: &Path, name: &str, archive: &Path) -> Result<(), String> {
+    let path = resolve(folder, name)?;
+    if archive == folder {
+        return Err("The archive folder is the recording folder.".to_string());
+    }
+    let archived = archive.join(name);
+    if archived.exists() || split::channel_folder(&archived).exists() {
+        return Err(format!("{} already exists in {}.", name, archive.display()));
+    }
+    retention::move_file(&path, archive).map_err(|e| format!("Could not archive {}: {}", name, e))?;
+    let sidecar = sidecar::sidecar_path(&path);
+    if sidecar.exists() {
+        retention::move_file(&sidecar, archive)
+            .map_err(|e| format!("Archived {}, but not its sidecar: {}", name, e))?;
+    }
+    split::move_channel_folder(&path, &archived)
+        .map_err(|e| format!("Archived {}, but not its channel streams: {}", name, e))?;
+    Ok(())
+}
+
+/// Opens a recording for replay or export: summary, line settings and markers.
+pub fn open(folder: &Path, name: &str) -> Result<RecordingDetails, String> {
+    details(&resolve(folder, name)?)
+}
+
+/// Details of a recording anywhere on disk. Blocking.
+pub fn details(path: &Path) -> Result<RecordingDetails, String> {
+    let name = path.display();
+    let metadata = std::fs::metadata(path).map_err(|e| format!("Could not open {}: {}", name, e))?;
+    let (summary, scan) = summarize(
+        path,
+        metadata.len(),
+        metadata.modified().unwrap_or(std::time::SystemTime::UNIX_EPOCH),
+    );
+
+    let (port, baud_rate) = match (&summary.sidecar, scan) {
+        (Some(meta), _) => (Some(meta.port.clone()), Some(meta.serial.baud_rate)),
+        (None, Some(scan)) => (scan.port, scan.baud_rate),
+        (None, None) => (None, None),
+    };
+    let markers = if summary.kind == "structured" {
+        recording::read_markers(path).map_err(|e| format!("Could not read {}: {}", name, e))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(RecordingDetails {
+        summary,
+        path: path.to_string_lossy().to_string(),
+        port,
+        baud_rate,
+        markers,
+    })
+}
@@ -3,22 +3,58 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 // Importing the `serial_wrapper` module which contains serial port handling functions.
+mod barriers;
+mod compression;
+mod convert;
+mod database;
+mod diff;
+mod heartbeat;
+mod library;
+mod metrics;
+mod pipeline;
+mod port_lock;
+mod protocol;
+mod recorder;
+mod recording;
+mod recovery;
+mod retention;
+mod safe_state;
+mod scheduler;
+mod search;
 mod serial_wrapper;
+mod sidecar;
+mod split;
+mod stats;
+mod trigger;
+mod vcd;
+mod writer;
 use crate::serial_wrapper::backend_log; // if both files are in the same crate
+use crate::barriers::{BarrierMonitor, BarrierStatsConfig};
+use crate::compression::CompressionConfig;
+use crate::database::DatabaseConfig;
+use crate::heartbeat::{HeartbeatConfig, LinkMonitor, LinkQualityReport};
+use crate::metrics::MetricsConfig;
+use crate::recorder::{DurabilityConfig, RecordingOptions, RotationConfig};
+use crate::pipeline::SinkRegistry;
+use crate::recording::RecordingHeader;
+use crate::retention::RetentionConfig;
+use crate::safe_state::SafeStateConfig;
+use crate::serial_wrapper::{PortTasks, SerialConnection};
+use crate::sidecar::SessionInfo;
+use crate::split::SplitConfig;
+use crate::stats::{SerialStatsConfig, StatsMonitor};
+use crate::trigger::TriggerConfig;
+use crate::writer::{QueueStatus, WriterConfig, WriterHandle};
 
 // Importing necessary crates and modules.
-use serialport::SerialPort; // Trait for serial port operations.
 use std::path::PathBuf; // Struct for handling filesystem paths.
-use std::sync::Mutex; // Mutex for thread-safe data access.
-use std::sync::{
-    atomic::{AtomicBool, Ordering}, // AtomicBool for thread-safe boolean flags.
-    Arc, // Arc for shared ownership across threads.
-};
+use std::sync::{Arc, Mutex}; // Arc and Mutex for thread-safe data access.
 use tauri::{Manager, State}; // Tauri utilities for managing application state.
 use rfd::FileDialog; // File dialog for selecting folders.
-use std::fs::File; // Struct for file operations.
-use std::time::SystemTime; // Struct for handling system time.
-use chrono::{DateTime, Local}; // Crate for date and time handling.
+use tokio_util::sync::CancellationToken; // Token used to stop the serial tasks.
+use std::time::Instant; // Struct for monotonic timestamps.
+use tokio_serial::SerialStream; // Async serial stream on the tokio reactor.
+use chrono::Local; // Crate for date and time handling.
 
 // Struct representing serial port configuration items.
 #[derive(Debug)]
@@ -29,11 +65,24 @@ pub struct PortItems {
 
 // Struct representing the application's data state.
 pub struct Data {
-    port: Option<Box<dyn SerialPort>>, // Optional serial port.
+    connection: Option<SerialConnection>, // Tasks of the open serial port, if any.
     folder_path: Option<PathBuf>, // Optional path to the recording folder.
     port_items: PortItems, // Serial port configuration.
-    is_thread_open: Arc<AtomicBool>, // Flag indicating if the serial thread is running.
     is_recording: bool, // Flag indicating if recording is active.
+    writer_config: WriterConfig, // Outgoing queue configuration.
+    heartbeat_config: HeartbeatConfig, // Link heartbeat configuration.
+    safe_state_config: SafeStateConfig, // Frames sent before the port closes.
+    rotation_config: RotationConfig, // When recording files are rotated.
+    retention_config: RetentionConfig, // Size, age and free-space limits of the recording folder.
+    compression_config: CompressionConfig, // Compression of new recording files.
+    session_info: SessionInfo, // Operator and unit details written into the sidecars.
+    durability_config: DurabilityConfig, // Buffering and sync interval of recording files.
+    database_config: DatabaseConfig, // SQLite database of the session exports.
+    barrier_stats_config: BarrierStatsConfig, // Rolling window of the light barrier statistics.
+    serial_stats_config: SerialStatsConfig, // Interval and read timeout of the link statistics.
+    trigger_config: TriggerConfig, // Pre- and post-trigger windows and rules of the trigger-based recording.
+    split_config: SplitConfig, // Per-channel streams written alongside recordings.
+    metrics_config: MetricsConfig, // File, format and interval of the time-series export.
 }
 
 // Wrapper struct for thread-safe access to `Data` using a Mutex.
@@ -70,224 +119,744 @@ fn set_port_items(
 }
 
 // Command to handle serial port connection.
+//
+// Runs on the Tauri async runtime so the serial stream can be registered with
+// the tokio reactor. The `Data` lock is never held across an `.await`.
 #[tauri::command]
-fn handle_serial_connect(app: tauri::AppHandle) -> Result<bool, String> {
-    let app_clone = app.clone();
-    let state = app_clone.state::<AppData>();
-    let mut state_guard = state.0.lock().unwrap();
+async fn handle_serial_connect(app: tauri::AppHandle) -> Result<bool, String> {
+    let (is_connected, port_path, baud_rate) = {
+        let state = app.state::<AppData>();
+        let state_guard = state.0.lock().unwrap();
+        if state_guard.is_recording {
+            // Emit a log before returning the error.
+            backend_log(&app, "Cannot connect: recording is active. Stop recording first.", "ERROR");
+            return Err("Please stop recording before disconnecting.".to_string());
+        }
+        (
+            state_guard.connection.is_some(),
+            state_guard.port_items.port_path.clone(),
+            state_guard.port_items.baud_rate,
+        )
+    };
 
-    if state_guard.is_recording {
-        // Emit a log before returning the error.
-        backend_log(&app, "Cannot connect: recording is active. Stop recording first.", "ERROR");
-        return Err("Please stop recording before disconnecting.".to_string());
+    if is_connected {
+        // If already connected, disconnect.
+        disconnect_port(&app).await;
+        Ok(false)
+    } else {
+        // If not connected, try to connect.
+        connect_port(&app, port_path, baud_rate).await.map(|_| true)
     }
+}
 
-    match &state_guard.port {
-        // If already connected, disconnect.
-        Some(_) => {
-            // Instead of println!("Disconnecting...");
-            backend_log(&app, "Disconnecting...", "INFO");
+// Opens `port_path` at `baud_rate` and starts the reader, writer and heartbeat tasks.
+pub async fn connect_port(app: &tauri::AppHandle, port_path: String, baud_rate: u32) -> Result<(), String> {
+    let state = app.state::<AppData>();
+    if state.0.lock().unwrap().connection.is_some() {
+        return Ok(());
+    }
+    backend_log(app, "Attempting to connect to serial port...", "INFO");
 
-            state_guard.is_thread_open.store(false, Ordering::Relaxed);
-            while state_guard.is_thread_open.load(Ordering::Relaxed) {}
-            state_guard.port = None;
-            // Another log
-            backend_log(&app, "Serial port successfully disconnected.", "SUCCESS");
+    // Listing the ports, looking for other holders and opening the port take a
+    // while; the state stays unlocked meanwhile.
+    let available_ports = serial_wrapper::list_ports(app.clone());
+    if !available_ports.contains(&port_path) {
+        let msg = format!("The specified port '{}' is not available.", port_path);
+        backend_log(app, &msg, "ERROR");
+        return Err(msg);
+    }
 
-            Ok(false)
-        }
-        // If not connected, try to connect.
-        None => {
-            backend_log(&app, "Attempting to connect to serial port...", "INFO");
+    let port_result = serial_wrapper::init_port(app.clone(), port_path.clone(), baud_rate);
 
-            let available_ports = serial_wrapper::list_ports(app_clone.clone());
-            if !available_ports.contains(&state_guard.port_items.port_path) {
-                let msg = format!(
-                    "The specified port '{}' is not available.",
-                    state_guard.port_items.port_path
-                );
-                backend_log(&app, &msg, "ERROR");
-                return Err(msg);
+    match port_result {
+        Ok(port) => {
+            let mut state_guard = state.0.lock().unwrap();
+            // Another connect may have won the race; this port closes again.
+            if state_guard.connection.is_some() {
+                return Ok(());
             }
+            let cancel = CancellationToken::new();
+            let link = LinkMonitor::new(&state_guard.heartbeat_config);
+            let sinks = Arc::new(SinkRegistry::new(app.clone()));
+            let stats = StatsMonitor::new();
+            let (writer, port) = start_port_tasks(app, port, &state_guard, cancel.child_token(), &link, &stats, &sinks);
 
-            let port_result = serial_wrapper::init_port(
-                app_clone.clone(),
-                state_guard.port_items.port_path.to_string(),
-                state_guard.port_items.baud_rate,
+            // Start light barrier statistics task
+            let barriers = BarrierMonitor::new(&state_guard.barrier_stats_config);
+            let barrier_task = barriers::start_barrier_task(
+                app.clone(),
+                state_guard.barrier_stats_config,
+                barriers.clone(),
+                sinks.register(barriers::BARRIER_SINK),
+                cancel.child_token(),
             );
 
-            match port_result {
-                Ok(port) => {
-                    let port_clone = port.try_clone().expect("Couldn't clone port");
-                    state_guard.port = Some(port);
+            // Start link statistics task
+            let stats_task = stats::start_stats_task(
+                app.clone(),
+                state_guard.serial_stats_config,
+                stats.clone(),
+                cancel.child_token(),
+            );
 
-                    // Start reading thread
-                    let is_thread_open_ref = state_guard.is_thread_open.clone();
-                    serial_wrapper::start_clone_thread(app_clone.clone(), port_clone, is_thread_open_ref);
+            state_guard.connection = Some(SerialConnection {
+                port_path,
+                baud_rate,
+                cancel,
+                port: Some(port),
+                writer,
+                sinks,
+                link,
+                barriers,
+                barrier_task,
+                stats,
+                stats_task,
+                recording: None,
+                channels: None,
+                trigger: None,
+                database: None,
+                metrics: None,
+            });
 
-                    backend_log(&app, "Serial port connected successfuly.", "SUCCESS");
-                    Ok(true)
-                }
-                Err(e) => {
-                    let msg = format!("Error opening port: {}", e);
-                    backend_log(&app, &msg, "ERROR");
-                    Err(msg)
-                }
+            backend_log(app, "Serial port connected successfuly.", "SUCCESS");
+            Ok(())
+        }
+        Err(msg) => {
+            backend_log(app, &msg, "ERROR");
+            Err(msg)
+        }
+    }
+}
+
+// Starts the tasks using the port itself: writer, reader and heartbeat.
+fn start_port_tasks(
+    app: &tauri::AppHandle,
+    port: SerialStream,
+    state_guard: &Data,
+    cancel: CancellationToken,
+    link: &Arc<LinkMonitor>,
+    stats: &Arc<StatsMonitor>,
+    sinks: &Arc<SinkRegistry>,
+) -> (WriterHandle, PortTasks) {
+    let (reader, writer_port) = tokio::io::split(port);
+    let heartbeat_config = state_guard.heartbeat_config;
+
+    // Start writer task
+    let (writer, writer_task) = writer::start_writer_task(
+        app.clone(),
+        writer_port,
+        state_guard.writer_config,
+        cancel.clone(),
+        stats.clone(),
+        sinks.clone(),
+    );
+
+    // Start reading task
+    let reader_task = serial_wrapper::start_read_task(
+        app.clone(),
+        reader,
+        cancel.clone(),
+        link.clone(),
+        stats.clone(),
+        sinks.clone(),
+    );
+
+    // Start heartbeat task
+    let heartbeat_task = if heartbeat_config.enabled {
+        Some(heartbeat::start_heartbeat_task(
+            app.clone(),
+            heartbeat_config,
+            link.clone(),
+            writer.clone(),
+            cancel.child_token(),
+        ))
+    } else {
+        None
+    };
+
+    let port = PortTasks {
+        cancel,
+        reader_task,
+        writer_task,
+        heartbeat_task,
+    };
+    (writer, port)
+}
+
+// Reopens the port of the connection after the link was lost.
+//
+// Only the port tasks are replaced: a running recording, the channel streams,
+// the trigger and the exports keep their sinks and see no traffic until the
+// port is back. If it does not come back, the connection closes as on a
+// disconnect, which also ends the recording.
+pub async fn reconnect_port(app: &tauri::AppHandle) {
+    let state = app.state::<AppData>();
+    let (port, port_path, baud_rate) = {
+        let mut state_guard = state.0.lock().unwrap();
+        match state_guard.connection.as_mut() {
+            Some(connection) => (connection.port.take(), connection.port_path.clone(), connection.baud_rate),
+            None => return,
+        }
+    };
+    // Another reconnect is already under way.
+    let port = match port {
+        Some(port) => port,
+        None => return,
+    };
+    port.close().await;
+    let _ = with_recording(&state, |recording| recording.add_marker("Link lost: reconnecting"));
+
+    for attempt in 1..=heartbeat::RECONNECT_ATTEMPTS {
+        // Give the device time to come back, e.g. after a USB reset.
+        tokio::time::sleep(heartbeat::RECONNECT_DELAY).await;
+        let stream = match serial_wrapper::init_port(app.clone(), port_path.clone(), baud_rate) {
+            Ok(stream) => stream,
+            Err(e) => {
+                backend_log(
+                    app,
+                    &format!("Reconnect attempt {} of {} failed: {}", attempt, heartbeat::RECONNECT_ATTEMPTS, e),
+                    "WARNING",
+                );
+                continue;
             }
+        };
+
+        let mut state_guard = state.0.lock().unwrap();
+        // Disconnected, or connected anew, in the meantime; this port closes again.
+        let (cancel, link, stats, sinks) = match state_guard.connection.as_ref() {
+            Some(connection) if connection.port.is_none() => (
+                connection.cancel.child_token(),
+                connection.link.clone(),
+                connection.stats.clone(),
+                connection.sinks.clone(),
+            ),
+            _ => return,
+        };
+        link.on_reconnect();
+        let (writer, port) = start_port_tasks(app, stream, &state_guard, cancel, &link, &stats, &sinks);
+        let connection = state_guard.connection.as_mut().unwrap();
+        connection.writer = writer;
+        connection.port = Some(port);
+        if let Some(recording) = &connection.recording {
+            let _ = recording.add_marker("Link restored");
         }
+        std::mem::drop(state_guard);
+        backend_log(app, "Serial port reopened; recordings and exports continue.", "SUCCESS");
+        return;
     }
+
+    backend_log(
+        app,
+        &format!(
+            "Could not reopen {}: the port is closed and a running recording was stopped.",
+            port_path
+        ),
+        "ERROR",
+    );
+    disconnect_port(app).await;
 }
 
-#[tauri::command]
-fn handle_serial_disconnect(app: tauri::AppHandle) -> bool {
-    let app_clone = app.clone();
-    let state = app_clone.state::<AppData>();
-    let mut state_guard = state.0.lock().unwrap();
+// Closes the serial port, if any. Returns whether a port was open.
+//
+// The safe-state sequence is written first, and a running recording is
+// flushed and closed before the port is released.
+pub async fn disconnect_port(app: &tauri::AppHandle) -> bool {
+    let (connection, safe_state_config) = {
+        let state = app.state::<AppData>();
+        let mut state_guard = state.0.lock().unwrap();
+        // A disconnect also ends a running recording.
+        state_guard.is_recording = false;
+        (state_guard.connection.take(), state_guard.safe_state_config.clone())
+    };
 
     // Check if a serial port is currently connected.
-    if let Some(_) = &state_guard.port {
+    if let Some(connection) = connection {
         // Instead of println!("Disconnecting serial port...");
-        backend_log(&app, "Disconnecting serial port...", "INFO");
+        backend_log(app, "Disconnecting serial port...", "INFO");
 
-        // Signal the thread to stop by setting the flag to false.
-        state_guard.is_thread_open.store(false, Ordering::Relaxed);
+        // Leave motors and LEDs off before the port goes away.
+        safe_state::send_safe_state(app, &connection.writer, &safe_state_config).await;
 
-        // Wait for the thread to acknowledge the stop signal.
-        while state_guard.is_thread_open.load(Ordering::Relaxed) {}
-
-        // Remove the serial port from the state.
-        state_guard.port = None;
+        // Cancel the reader and writer tasks and wait until they released the port.
+        connection.close().await;
 
         // Another log
-        backend_log(&app, "Serial port disconnected.", "SUCCESS");
+        backend_log(app, "Serial port disconnected.", "SUCCESS");
         true
     } else {
         // Instead of println!("No serial port to disconnect.");
-        backend_log(&app, "No serial port to disconnect.", "WARNING");
+        backend_log(app, "No serial port to disconnect.", "WARNING");
         false
     }
 }
 
-// Command to handle starting or stopping recording of serial data.
 #[tauri::command]
-fn handle_start_record(app: tauri::AppHandle) -> bool {
-    let app_clone = app.clone(); // Clone the app handle.
-    let state = app_clone.state::<AppData>(); // Retrieve the application state.
-    let mut state_guard = state.0.lock().unwrap(); // Acquire the lock on the state.
-    println!("start handle record"); // Log the action.
-
-    // Check if recording is not currently active.
-    if !state_guard.is_recording {
-        // Check if a serial port is connected.
-        match &state_guard.port {
-            Some(port) => {
-                // Check if a folder path has been set for recording.
-                match &state_guard.folder_path {
-                    Some(path) => {
-                        // Clone the serial port for recording.
-                        let port_clone = port.try_clone().unwrap();
-                        let is_thread_open_ref = state_guard.is_thread_open.clone(); // Clone the thread open flag.
-
-                        // Get the current system time.
-                        let system_time = SystemTime::now();
-                        // Convert system time to a DateTime object in the local timezone.
-                        let datetime: DateTime<Local> = system_time.into();
-                        // Format the date and time as a string for the filename.
-                        let formatted_date_time = datetime.format("%Y-%m-%d_%H.%M.%S").to_string();
-                        // Create the filename with the formatted date and time.
-                        let file_name = format!("DCubedISM{}.txt", formatted_date_time);
-                        // Combine the folder path and filename to get the full file path.
-                        let file_path = path.join(file_name);
-
-                        let path_clone = path.clone(); // Clone the path for thread usage.
-                        // Attempt to create the file for recording.
-                        let file = File::create(&file_path);
-                        match file {
-                            Ok(file) => {
-                                // Signal the serial thread to stop.
-                                state_guard.is_thread_open.store(false, Ordering::Relaxed);
-                                // Wait for the thread to acknowledge the stop signal.
-                                while !state_guard.is_thread_open.load(Ordering::Relaxed) {}
-                                // Set the recording flag to true.
-                                state_guard.is_recording = true;
-                                // Start the recording thread with the cloned port and file.
-                                serial_wrapper::start_record_on_port(
-                                    app.clone(),
-                                    port_clone,
-                                    is_thread_open_ref,
-                                    Some(file),
-                                    path_clone
-                                );
-                                println!("finish start clone"); // Log the action.
-                                return true; // Indicate SUCESSful start of recording.
-                            }
-                            Err(e) => {
-                                // If file creation fails, reset the recording flag.
-                                state_guard.is_recording = false;
-                                // Format the error message.
-                                let error_description =
-                                    format!("{}{}", "An error occurred creating file: ", e);
-                                // Display the error message to the user.
-                                rfd::MessageDialog::new()
-                                    .set_level(rfd::MessageLevel::Error) // Set the message level to error.
-                                    .set_title("File Error") // Set the dialog title.
-                                    .set_description(error_description.as_str()) // Set the dialog description.
-                                    .set_buttons(rfd::MessageButtons::Ok) // Use Ok button.
-                                    .show();
-                                return false; // Indicate failure to start recording.
-                            }
-                        }
-                    }
-                    None => {
-                        // If no folder path is set, reset the recording flag.
-                        state_guard.is_recording = false;
-                        // Display an error message to the user.
-                        rfd::MessageDialog::new()
-                            .set_level(rfd::MessageLevel::Error) // Set the message level to error.
-                            .set_title("File Error") // Set the dialog title.
-                            .set_description("File path not set.") // Set the dialog description.
-                            .set_buttons(rfd::MessageButtons::Ok) // Use Ok button.
-                            .show();
-                        return false; // Indicate failure to start recording.
+async fn handle_serial_disconnect(app: tauri::AppHandle) -> bool {
+    disconnect_port(&app).await
+}
+
+// Shows an error dialog for a failed recording start.
+fn show_record_error(title: &str, description: &str) {
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error) // Set the message level to error.
+        .set_title(title) // Set the dialog title.
+        .set_description(description) // Set the dialog description.
+        .set_buttons(rfd::MessageButtons::Ok) // Use Ok button.
+        .show();
+}
+
+// Header of a session starting now: the port it was opened with, or else the configured one.
+fn session_header(state_guard: &Data) -> RecordingHeader {
+    let (port, baud_rate) = match &state_guard.connection {
+        Some(connection) => (connection.port_path.clone(), connection.baud_rate),
+        None => (state_guard.port_items.port_path.clone(), state_guard.port_items.baud_rate),
+    };
+    RecordingHeader {
+        started: Local::now(),
+        started_at: Instant::now(),
+        port,
+        baud_rate,
+    }
+}
+
+// Builds the options of a recording session starting now, with the current policies.
+fn recording_options(state_guard: &Data, app_version: String) -> RecordingOptions {
+    RecordingOptions {
+        folder: state_guard.folder_path.clone().unwrap_or_default(),
+        header: session_header(state_guard),
+        rotation: state_guard.rotation_config,
+        retention: state_guard.retention_config.clone(),
+        compression: state_guard.compression_config,
+        durability: state_guard.durability_config,
+        session: state_guard.session_info.clone(),
+        app_version,
+    }
+}
+
+// Starts a recording on the open port, into `folder` or the configured folder.
+//
+// The recorder is a sink on the connection's pipeline, so the reader keeps
+// running and the port stays open while recordings start and stop. Errors
+// carry the title and text of the dialog shown for a manual start.
+pub async fn start_recording(
+    app: &tauri::AppHandle,
+    folder: Option<PathBuf>,
+) -> Result<(), (&'static str, String)> {
+    let state = app.state::<AppData>(); // Retrieve the application state.
+
+    // Collect what is needed to start a recording.
+    let (options, split_config) = {
+        let state_guard = state.0.lock().unwrap(); // Acquire the lock on the state.
+        if state_guard.is_recording {
+            return Err(("Record Error", "A recording is already running.".to_string()));
+        }
+        if state_guard.connection.is_none() {
+            // If no serial port is connected, there is nothing to record.
+            return Err(("Port Error", "Connect to port first.".to_string()));
+        }
+        let mut options = recording_options(&state_guard, app.package_info().version.to_string());
+        match (folder, &state_guard.folder_path) {
+            (Some(folder), _) => options.folder = folder,
+            (None, Some(_)) => {}
+            // If no folder path is set, there is nothing to record into.
+            (None, None) => return Err(("File Error", "File path not set.".to_string())),
+        }
+        (options, state_guard.split_config)
+    };
+
+    // Attempt to create the first file of the session, header included.
+    let file = recorder::open_session_file(&options)
+        .await
+        .map_err(|e| ("File Error", format!("{}{}", "An error occurred creating file: ", e)))?;
+
+    let error = {
+        let mut state_guard = state.0.lock().unwrap();
+        let is_recording = state_guard.is_recording;
+        match state_guard.connection.as_mut() {
+            // Another start may have won the race while the file was created.
+            Some(connection) if !is_recording && connection.recording.is_none() => {
+                // The channel streams are optional; the recording runs without them.
+                if split_config.enabled {
+                    match split::start_live_split(
+                        app.clone(),
+                        file.path(),
+                        &options.header,
+                        split_config.format,
+                        &connection.sinks,
+                    ) {
+                        Ok(channels) => connection.channels = Some(channels),
+                        Err(e) => backend_log(app, &format!("Recording without channel streams: {}", e), "ERROR"),
                     }
                 }
+
+                // Register the recorder on the pipeline and start writing.
+                let events = connection.sinks.register(recorder::RECORDER_SINK);
+                connection.recording = Some(recorder::start_record_task(
+                    app.clone(),
+                    file,
+                    options,
+                    events,
+                    connection.cancel.child_token(),
+                ));
+                // Set the recording flag to true.
+                state_guard.is_recording = true;
+                return Ok(());
             }
-            None => {
-                // If no serial port is connected, reset the recording flag.
-                state_guard.is_recording = false;
-                // Display an error message to the user.
-                rfd::MessageDialog::new()
-                    .set_level(rfd::MessageLevel::Error) // Set the message level to error.
-                    .set_title("Port Error") // Set the dialog title.
-                    .set_description("Connect to port first.") // Set the dialog description.
-                    .set_buttons(rfd::MessageButtons::Ok) // Use Ok button.
-                    .show();
-                return false; // Indicate failure to start recording.
+            Some(_) => ("Record Error", "A recording is already running.".to_string()),
+            // The port was lost in the meantime.
+            None => ("Port Error", "The port was disconnected.".to_string()),
+        }
+    };
+
+    // Remove the file created for nothing, so it is not left for recovery.
+    file.discard().await;
+    Err(error)
+}
+
+// Stops the running recording. Returns whether one was running.
+pub async fn stop_recording(app: &tauri::AppHandle) -> bool {
+    let (recording, channels) = {
+        let state = app.state::<AppData>();
+        let mut state_guard = state.0.lock().unwrap();
+        let was_recording = std::mem::replace(&mut state_guard.is_recording, false);
+        match state_guard.connection.as_mut() {
+            Some(connection) if was_recording => {
+                connection.sinks.unregister(recorder::RECORDER_SINK);
+                let channels = connection.channels.take().map(|channels| (channels, connection.sinks.clone()));
+                (connection.recording.take(), channels)
+            }
+            _ => (None, None),
+        }
+    };
+
+    // The channel streams get their manifest once the pending events are written.
+    if let Some((channels, sinks)) = channels {
+        channels.stop(&sinks).await;
+    }
+    // The recorder writes what it already received and closes its file.
+    match recording {
+        Some(recording) => {
+            recording.stop().await;
+            backend_log(app, "Recording stopped.", "INFO");
+            true
+        }
+        None => false,
+    }
+}
+
+// Command to handle starting or stopping recording of serial data.
+#[tauri::command]
+async fn handle_start_record(app: tauri::AppHandle) -> bool {
+    let is_recording = app.state::<AppData>().0.lock().unwrap().is_recording;
+    if is_recording {
+        stop_recording(&app).await;
+        return false; // Indicate that recording is not running.
+    }
+    match start_recording(&app, None).await {
+        Ok(()) => true, // Indicate SUCESSful start of recording.
+        Err((title, description)) => {
+            // Display the error message to the user.
+            show_record_error(title, &description);
+            false // Indicate failure to start recording.
+        }
+    }
+}
+
+// Command to configure the trigger-based recording; applied when it is next armed.
+#[tauri::command]
+fn set_trigger_config(app: tauri::AppHandle, state: State<AppData>, config: TriggerConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Trigger config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.trigger_config = config;
+}
+
+// Command to retrieve the trigger-based recording configuration.
+#[tauri::command]
+fn get_trigger_config(state: State<AppData>) -> TriggerConfig {
+    state.0.lock().unwrap().trigger_config.clone()
+}
+
+// Command to configure the per-channel streams; applied when a recording next starts.
+#[tauri::command]
+fn set_split_config(app: tauri::AppHandle, state: State<AppData>, config: SplitConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Split config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.split_config = config;
+}
+
+// Command to retrieve the per-channel stream configuration.
+#[tauri::command]
+fn get_split_config(state: State<AppData>) -> SplitConfig {
+    state.0.lock().unwrap().split_config
+}
+
+// Command to arm the trigger-based recording on the open port.
+#[tauri::command]
+fn arm_recording_trigger(app: tauri::AppHandle, state: State<AppData>) -> Result<(), String> {
+    let mut state_guard = state.0.lock().unwrap();
+    if state_guard.folder_path.is_none() {
+        return Err("File path not set.".to_string());
+    }
+    let config = state_guard.trigger_config.clone();
+    let template = recording_options(&state_guard, app.package_info().version.to_string());
+    let connection = state_guard
+        .connection
+        .as_mut()
+        .ok_or_else(|| "Connect to port first.".to_string())?;
+    if connection.trigger.is_some() {
+        return Err("The trigger is already armed.".to_string());
+    }
+    let (pre, post, rules) = (config.pre_trigger_secs, config.post_trigger_secs, config.rules.len());
+    connection.trigger = Some(trigger::start_trigger_task(
+        app.clone(),
+        config,
+        template,
+        &connection.sinks,
+        connection.cancel.child_token(),
+    ));
+    std::mem::drop(state_guard);
+    backend_log(
+        &app,
+        &format!("Trigger armed: {} s before, {} s after, {} rule(s).", pre, post, rules),
+        "SUCCESS",
+    );
+    Ok(())
+}
+
+// Command to disarm the trigger; a triggered recording still running is closed.
+#[tauri::command]
+async fn disarm_recording_trigger(app: tauri::AppHandle, state: State<'_, AppData>) -> Result<(), String> {
+    let (task, sinks) = {
+        let mut state_guard = state.0.lock().unwrap();
+        let connection = state_guard
+            .connection
+            .as_mut()
+            .ok_or_else(|| "The trigger is not armed.".to_string())?;
+        let task = connection
+            .trigger
+            .take()
+            .ok_or_else(|| "The trigger is not armed.".to_string())?;
+        (task, connection.sinks.clone())
+    };
+    task.stop(&sinks).await;
+    backend_log(&app, "Trigger disarmed.", "INFO");
+    Ok(())
+}
+
+// Command to fire the armed trigger by hand, e.g. when the operator notices a fault.
+#[tauri::command]
+fn fire_recording_trigger(state: State<AppData>, reason: Option<String>) -> Result<(), String> {
+    let state_guard = state.0.lock().unwrap();
+    match state_guard.connection.as_ref().and_then(|connection| connection.trigger.as_ref()) {
+        Some(trigger) => trigger.fire(reason.unwrap_or_else(|| "manual".to_string())),
+        None => Err("The trigger is not armed.".to_string()),
+    }
+}
+
+// Command to list the recording schedules, with their latest runs.
+#[tauri::command]
+fn list_schedules(scheduler: State<scheduler::Scheduler>) -> Vec<scheduler::Schedule> {
+    scheduler.list()
+}
+
+// Command to add a one-off or daily recording schedule; returns it with its ID.
+#[tauri::command]
+fn add_schedule(
+    app: tauri::AppHandle,
+    scheduler: State<scheduler::Scheduler>,
+    schedule: scheduler::Schedule,
+) -> Result<scheduler::Schedule, String> {
+    let schedule = scheduler.add(&app, schedule)?;
+    backend_log(
+        &app,
+        &format!("Schedule '{}' added: {:?}", schedule.name, schedule.window),
+        "SUCCESS",
+    );
+    Ok(schedule)
+}
+
+// Command to change a recording schedule, e.g. to enable or disable it.
+#[tauri::command]
+fn update_schedule(
+    app: tauri::AppHandle,
+    scheduler: State<scheduler::Scheduler>,
+    schedule: scheduler::Schedule,
+) -> Result<scheduler::Schedule, String> {
+    let schedule = scheduler.update(&app, schedule)?;
+    backend_log(
+        &app,
+        &format!("Schedule '{}' updated: {:?}", schedule.name, schedule.window),
+        "SUCCESS",
+    );
+    Ok(schedule)
+}
+
+// Command to remove a recording schedule; a window being recorded ends right away.
+#[tauri::command]
+fn remove_schedule(app: tauri::AppHandle, scheduler: State<scheduler::Scheduler>, id: u64) -> Result<(), String> {
+    scheduler.remove(&app, id)?;
+    backend_log(&app, &format!("Schedule {} removed.", id), "INFO");
+    Ok(())
+}
+
+// Runs `action` on the running recording, if there is one.
+fn with_recording<T>(
+    state: &State<AppData>,
+    action: impl FnOnce(&recorder::RecordingTask) -> Result<T, String>,
+) -> Result<T, String> {
+    let state_guard = state.0.lock().unwrap();
+    match state_guard.connection.as_ref().and_then(|connection| connection.recording.as_ref()) {
+        Some(recording) => action(recording),
+        None => Err("No recording is running.".to_string()),
+    }
+}
+
+// Payload of the `recordingPaused` event.
+#[derive(Clone, serde::Serialize)]
+struct PausedPayload {
+    paused: bool,
+}
+
+// Command to pause the running recording, e.g. while a part is swapped.
+#[tauri::command]
+fn pause_recording(app: tauri::AppHandle, state: State<AppData>) -> Result<(), String> {
+    with_recording(&state, |recording| recording.pause())?;
+    backend_log(&app, "Recording paused.", "INFO");
+    app.emit_all("recordingPaused", PausedPayload { paused: true }).map_err(|e| e.to_string())
+}
+
+// Command to resume a paused recording.
+#[tauri::command]
+fn resume_recording(app: tauri::AppHandle, state: State<AppData>) -> Result<(), String> {
+    with_recording(&state, |recording| recording.resume())?;
+    backend_log(&app, "Recording resumed.", "INFO");
+    app.emit_all("recordingPaused", PausedPayload { paused: false }).map_err(|e| e.to_string())
+}
+
+// Command to write a timestamped note such as "door opened" into the recording.
+#[tauri::command]
+fn add_recording_marker(app: tauri::AppHandle, state: State<AppData>, text: String) -> Result<(), String> {
+    {
+        let state_guard = state.0.lock().unwrap();
+        let connection = state_guard.connection.as_ref();
+        // Markers go to the recording and to the live database export, whichever runs.
+        let recorded = match connection.and_then(|connection| connection.recording.as_ref()) {
+            Some(recording) => recording.add_marker(&text).map(|_| true)?,
+            None => false,
+        };
+        let exported = match connection.and_then(|connection| connection.database.as_ref()) {
+            Some(database) => {
+                database.add_marker(&text);
+                true
             }
+            None => false,
+        };
+        if !recorded && !exported {
+            return Err("No recording is running.".to_string());
         }
     }
+    backend_log(&app, &format!("Marker added: {}", text), "SUCCESS");
+    Ok(())
+}
 
-    // If already recording, proceed to stop recording.
-    state_guard.is_recording = false; // Reset the recording flag.
-    state_guard.port = None; // Remove the serial port from the state.
-    state_guard.is_thread_open.store(false, Ordering::Relaxed); // Signal the thread to stop.
-    // Wait for the thread to acknowledge the stop signal.
-    while !state_guard.is_thread_open.load(Ordering::Relaxed) {}
-    std::mem::drop(state_guard); // Drop the lock on the state.
-    handle_serial_connect(app.clone()); // Reconnect the serial port.
-    return false; // Indicate that recording has been stopped.
+// Command to list markers: those of the running session, or those of a recording file.
+#[tauri::command]
+async fn list_recording_markers(
+    state: State<'_, AppData>,
+    path: Option<String>,
+) -> Result<Vec<recording::Marker>, String> {
+    match path {
+        None => with_recording(&state, |recording| Ok(recording.markers())),
+        Some(path) => tauri::async_runtime::spawn_blocking(move || {
+            recording::read_markers(std::path::Path::new(&path))
+                .map_err(|e| format!("Could not read {}: {}", path, e))
+        })
+        .await
+        .map_err(|e| e.to_string())?,
+    }
 }
 
 // Command to set the folder path for recordings.
 #[tauri::command]
-fn set_folder_path(state: State<AppData>){
-    let mut state_guard = state.0.lock().unwrap(); // Acquire the lock on the state.
-    // Open a folder picker dialog starting at the root directory.
+fn set_folder_path(app: tauri::AppHandle, state: State<AppData>){
+    // Open a folder picker dialog starting at the root directory, before taking the lock.
     let dir = FileDialog::new().set_directory("/").pick_folder();
+    let mut state_guard = state.0.lock().unwrap(); // Acquire the lock on the state.
+    // Recover recordings a crash left behind in the new folder.
+    if let (Some(folder), None) = (dir.clone(), recovery_blocked(&state_guard)) {
+        tauri::async_runtime::spawn(async move {
+            recovery::scan_and_report(&app, folder).await;
+        });
+    }
     // Store the selected directory in the state.
     state_guard.folder_path = dir;
 }
 
+// Why partial files may not be recovered now: something may still be writing them.
+fn recovery_blocked(state: &Data) -> Option<&'static str> {
+    if state.is_recording {
+        return Some("Stop recording before recovering partial files.");
+    }
+    // An armed trigger may open a partial file at any moment.
+    if state
+        .connection
+        .as_ref()
+        .is_some_and(|connection| connection.trigger.is_some())
+    {
+        return Some("Disarm the trigger before recovering partial files.");
+    }
+    None
+}
+
+// Command to recover or flag partial recordings left in the recording folder by a crash.
+#[tauri::command]
+async fn recover_recordings(
+    app: tauri::AppHandle,
+    state: State<'_, AppData>,
+) -> Result<Vec<recovery::RecoveredFile>, String> {
+    let folder = {
+        let state_guard = state.0.lock().unwrap();
+        if let Some(reason) = recovery_blocked(&state_guard) {
+            return Err(reason.to_string());
+        }
+        state_guard.folder_path.clone()
+    };
+    let folder = folder.ok_or_else(|| "File path not set.".to_string())?;
+    Ok(recovery::scan_and_report(&app, folder).await)
+}
+
+// Command to configure buffering and the sync interval of recording files.
+#[tauri::command]
+fn set_durability_config(app: tauri::AppHandle, state: State<AppData>, config: DurabilityConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Durability config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.durability_config = config;
+}
+
+// Command to retrieve buffering and the sync interval of recording files.
+#[tauri::command]
+fn get_durability_config(state: State<AppData>) -> DurabilityConfig {
+    state.0.lock().unwrap().durability_config
+}
+
+// Command to list the other processes holding a serial port open.
+#[tauri::command]
+fn get_port_holders(port: String) -> Vec<port_lock::PortHolder> {
+    port_lock::find_port_holders(&port)
+}
+
 // Command to retrieve a list of available serial ports.
 #[tauri::command]
 fn get_ports(app: tauri::AppHandle) -> Vec<String> {
@@ -324,81 +893,33 @@ fn emit_error(input: String, app: tauri::AppHandle) {
 }
 
 
-// Command to send a serial command to the connected device.
+// Command to queue a serial command for the writer task.
+//
+// The `Data` lock is only held long enough to clone the writer handle, so a
+// slow write never blocks the other commands. Returns the id reported in the
+// `commandStatus` events.
 #[tauri::command]
-fn send_serial(app: tauri::AppHandle, state: State<AppData>, input: Vec<u8>) {
-    let mut state_guard = state.0.lock().unwrap();
+fn send_serial(app: tauri::AppHandle, state: State<AppData>, input: Vec<u8>) -> Result<u64, String> {
+    let writer = state
+        .0
+        .lock()
+        .unwrap()
+        .connection
+        .as_ref()
+        .map(|connection| connection.writer.clone());
 
-    match &mut state_guard.port {
-        Some(port) => {
+    match writer {
+        Some(writer) => {
             backend_log(
                 &app,
                 &format!("Preparing to send message: {:?}", input),
                 "INFO",
             );
 
-            match port.write(&input) {
-                Ok(bytes_written) => {
-                    // Log básico de bytes enviados
-                    backend_log(
-                        &app,
-                        &format!("Sent {} bytes.", bytes_written),
-                        "SUCCESS",
-                    );
-
-                    // Log detalhado do conteúdo da mensagem
-                    let hex_content = input
-                        .iter()
-                        .map(|b| format!("{:02X}", b))
-                        .collect::<Vec<_>>()
-                        .join(" ");
-                    backend_log(
-                        &app,
-                        &format!("Message content (hex): {}", hex_content),
-                        "SUCCESS",
-                    );
-
-                    // Decodificar os valores
-                    let command_id = input.get(0).copied().unwrap_or_default();
-                    let hardware_id = input.get(1).copied().unwrap_or_default();
-                    let value = (input.get(2).copied().unwrap_or_default() as u32)
-                        | ((input.get(3).copied().unwrap_or_default() as u32) << 8)
-                        | ((input.get(4).copied().unwrap_or_default() as u32) << 16)
-                        | ((input.get(5).copied().unwrap_or_default() as u32) << 24);
-
-                    backend_log(
-                        &app,
-                        &format!(
-                            "Parsed Sent Message -> COMMAND_ID: {}, HARDWARE_ID: {}, VALUE: {}",
-                            command_id, hardware_id, value
-                        ),
-                        "INFO"
-                    );
-
-                    // Log adicional para debugging
-                    backend_log(
-                        &app,
-                        &format!(
-                            "Message details -> Decimal: {:?}, Hex: {}",
-                            input, hex_content
-                        ),
-                        "DEBUG"
-                    );
-                }
-                Err(e) => {
-                    backend_log(
-                        &app,
-                        &format!("Failed to send message: {:?}", e),
-                        "ERROR",
-                    );
-                    rfd::MessageDialog::new()
-                        .set_level(rfd::MessageLevel::Error)
-                        .set_title("Write Error")
-                        .set_description(&format!("An error occurred writing to port: {}", e))
-                        .set_buttons(rfd::MessageButtons::Ok)
-                        .show();
-                }
-            }
+            writer.enqueue(&app, input).map_err(|e| {
+                backend_log(&app, &format!("Failed to queue message: {}", e), "ERROR");
+                e
+            })
         }
         None => {
             backend_log(
@@ -413,10 +934,788 @@ fn send_serial(app: tauri::AppHandle, state: State<AppData>, input: Vec<u8>) {
                 .set_description("Connect to port first.")
                 .set_buttons(rfd::MessageButtons::Ok)
                 .show();
+            Err("Connect to port first.".to_string())
         }
     }
 }
 
+// Command to configure the outgoing queue. Applies from the next connection.
+#[tauri::command]
+fn set_writer_config(app: tauri::AppHandle, state: State<AppData>, config: WriterConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    state_guard.writer_config = config;
+    backend_log(
+        &app,
+        &format!("Writer config updated: {:?}", config),
+        "SUCCESS",
+    );
+}
+
+// Command to retrieve the depth of the outgoing queue.
+#[tauri::command]
+fn get_write_queue_status(state: State<AppData>) -> Option<QueueStatus> {
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .connection
+        .as_ref()
+        .map(|connection| connection.writer.status())
+}
+
+
+// Command to configure the link heartbeat. Applies from the next connection.
+#[tauri::command]
+fn set_heartbeat_config(app: tauri::AppHandle, state: State<AppData>, config: HeartbeatConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    state_guard.heartbeat_config = config;
+    backend_log(
+        &app,
+        &format!("Heartbeat config updated: {:?}", config),
+        "SUCCESS",
+    );
+}
+
+// Command to retrieve the current link quality statistics.
+#[tauri::command]
+fn get_link_quality(state: State<AppData>) -> Option<LinkQualityReport> {
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .connection
+        .as_ref()
+        .map(|connection| connection.link.report())
+}
+
+// Command to configure the light barrier statistics; applied on the next connect.
+#[tauri::command]
+fn set_barrier_stats_config(app: tauri::AppHandle, state: State<AppData>, config: BarrierStatsConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Light barrier statistics config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.barrier_stats_config = config;
+}
+
+// Command to retrieve the light barrier statistics configuration.
+#[tauri::command]
+fn get_barrier_stats_config(state: State<AppData>) -> BarrierStatsConfig {
+    state.0.lock().unwrap().barrier_stats_config
+}
+
+// Command to retrieve the current light barrier statistics of the open port.
+#[tauri::command]
+fn get_light_barrier_stats(state: State<AppData>) -> Option<barriers::BarrierReport> {
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .connection
+        .as_ref()
+        .map(|connection| connection.barriers.report())
+}
+
+// Command to compute the light barrier statistics of a recording, optionally per rolling window.
+#[tauri::command]
+async fn analyze_light_barriers(
+    path: String,
+    window_secs: Option<u64>,
+) -> Result<barriers::BarrierAnalysis, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        barriers::analyze(
+            std::path::Path::new(&path),
+            window_secs.filter(|&secs| secs > 0).map(std::time::Duration::from_secs),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to configure the link statistics; applied on the next connect.
+#[tauri::command]
+fn set_serial_stats_config(app: tauri::AppHandle, state: State<AppData>, config: SerialStatsConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Serial statistics config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.serial_stats_config = config;
+}
+
+// Command to retrieve the link statistics configuration.
+#[tauri::command]
+fn get_serial_stats_config(state: State<AppData>) -> SerialStatsConfig {
+    state.0.lock().unwrap().serial_stats_config
+}
+
+// Command to retrieve the traffic statistics of the open port.
+#[tauri::command]
+fn get_serial_stats(state: State<AppData>) -> Option<stats::SerialStatsReport> {
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .connection
+        .as_ref()
+        .map(|connection| connection.stats.report())
+}
+
+// Command to start counting the traffic statistics from zero.
+#[tauri::command]
+fn reset_serial_stats(app: tauri::AppHandle, state: State<AppData>) -> Result<(), String> {
+    let state_guard = state.0.lock().unwrap();
+    let connection = state_guard
+        .connection
+        .as_ref()
+        .ok_or_else(|| "Connect to port first.".to_string())?;
+    connection.stats.reset();
+    backend_log(&app, "Serial statistics reset.", "SUCCESS");
+    Ok(())
+}
+
+// Command to configure the safe-state sequence.
+#[tauri::command]
+fn set_safe_state_config(app: tauri::AppHandle, state: State<AppData>, config: SafeStateConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Safe-state config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.safe_state_config = config;
+}
+
+// Command to retrieve the safe-state sequence configuration.
+#[tauri::command]
+fn get_safe_state_config(state: State<AppData>) -> SafeStateConfig {
+    state.0.lock().unwrap().safe_state_config.clone()
+}
+
+// Command to configure the rotation policy of recording files.
+#[tauri::command]
+fn set_rotation_config(app: tauri::AppHandle, state: State<AppData>, config: RotationConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Rotation config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.rotation_config = config;
+}
+
+// Command to retrieve the rotation policy of recording files.
+#[tauri::command]
+fn get_rotation_config(state: State<AppData>) -> RotationConfig {
+    state.0.lock().unwrap().rotation_config
+}
+
+// Command to configure the retention rules of the recording folder.
+#[tauri::command]
+fn set_retention_config(
+    app: tauri::AppHandle,
+    state: State<AppData>,
+    config: RetentionConfig,
+) -> Result<(), String> {
+    config.validate()?;
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Retention config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.retention_config = config;
+    Ok(())
+}
+
+// Command to retrieve the retention rules of the recording folder.
+#[tauri::command]
+fn get_retention_config(state: State<AppData>) -> RetentionConfig {
+    state.0.lock().unwrap().retention_config.clone()
+}
+
+// Command to configure the compression of new recording files.
+#[tauri::command]
+fn set_compression_config(app: tauri::AppHandle, state: State<AppData>, config: CompressionConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Compression config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.compression_config = config;
+}
+
+// Command to retrieve the compression of new recording files.
+#[tauri::command]
+fn get_compression_config(state: State<AppData>) -> CompressionConfig {
+    state.0.lock().unwrap().compression_config
+}
+
+// Command to set the operator and unit details recorded in the sidecars.
+#[tauri::command]
+fn set_session_info(app: tauri::AppHandle, state: State<AppData>, info: SessionInfo) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Session info updated: {:?}", info),
+        "SUCCESS",
+    );
+    state_guard.session_info = info;
+}
+
+// Command to retrieve the operator and unit details recorded in the sidecars.
+#[tauri::command]
+fn get_session_info(state: State<AppData>) -> SessionInfo {
+    state.0.lock().unwrap().session_info.clone()
+}
+
+// Command to read lines of a recording, compressed or not, for replay and export in the UI.
+#[tauri::command]
+async fn read_recording_lines(path: String, offset: usize, limit: usize) -> Result<Vec<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        use std::io::BufRead;
+        let reader = compression::open_recording(std::path::Path::new(&path))
+            .map_err(|e| format!("Could not open {}: {}", path, e))?;
+        reader
+            .lines()
+            .skip(offset)
+            .take(limit)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Could not read {}: {}", path, e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// Command to convert a recording, raw or structured, into decoded CSV or NDJSON.
+#[tauri::command]
+async fn convert_recording(
+    app: tauri::AppHandle,
+    path: String,
+    format: convert::OutputFormat,
+    output: Option<String>,
+) -> Result<convert::ConversionReport, String> {
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        convert::convert(std::path::Path::new(&path), format, output.map(PathBuf::from))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match &report {
+        Ok(report) => {
+            let level = if report.malformed_count > 0 { "WARNING" } else { "SUCCESS" };
+            backend_log(
+                &app,
+                &format!(
+                    "Converted {} to {}: {} frames, {} malformed region(s).",
+                    report.input, report.output, report.frames, report.malformed_count
+                ),
+                level,
+            );
+        }
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    report
+}
+
+// Command to export a recording as a Value Change Dump for GTKWave or PulseView.
+#[tauri::command]
+async fn export_recording_vcd(
+    app: tauri::AppHandle,
+    path: String,
+    output: Option<String>,
+    direction: Option<recording::Direction>,
+) -> Result<vcd::VcdReport, String> {
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        vcd::export(std::path::Path::new(&path), output.map(PathBuf::from), direction)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match &report {
+        Ok(report) => backend_log(
+            &app,
+            &format!(
+                "Exported {} to {}: {} signal(s), {} value changes.",
+                report.input,
+                report.output,
+                report.signals.len(),
+                report.changes
+            ),
+            "SUCCESS",
+        ),
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    report
+}
+
+// Command to split a recording into one decoded stream per command and hardware ID, with a manifest.
+#[tauri::command]
+async fn split_recording(
+    app: tauri::AppHandle,
+    path: String,
+    format: convert::OutputFormat,
+    output: Option<String>,
+) -> Result<split::SplitManifest, String> {
+    let manifest = tauri::async_runtime::spawn_blocking(move || {
+        split::split(std::path::Path::new(&path), format, output.map(PathBuf::from))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match &manifest {
+        Ok(manifest) => backend_log(
+            &app,
+            &format!(
+                "Split {} into {}: {} channel(s), {} frames.",
+                manifest.session,
+                manifest.folder,
+                manifest.channels.len(),
+                manifest.frames
+            ),
+            "SUCCESS",
+        ),
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    manifest
+}
+
+// Command to compare a reference recording with a candidate; the report is also written to `output` when given.
+#[tauri::command]
+async fn compare_recordings(
+    app: tauri::AppHandle,
+    reference: String,
+    candidate: String,
+    options: Option<diff::DiffOptions>,
+    output: Option<String>,
+) -> Result<diff::DiffReport, String> {
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        diff::compare(
+            std::path::Path::new(&reference),
+            std::path::Path::new(&candidate),
+            options.unwrap_or_default(),
+            output.as_deref().map(std::path::Path::new),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match &report {
+        Ok(report) => {
+            let level = if report.identical { "SUCCESS" } else { "WARNING" };
+            backend_log(
+                &app,
+                &format!(
+                    "Compared {} with {}: {} equal, {} missing, {} inserted, {} changed, {} timing deviation(s).",
+                    report.reference,
+                    report.candidate,
+                    report.equal,
+                    report.missing,
+                    report.inserted,
+                    report.changed,
+                    report.timing_deviations
+                ),
+                level,
+            );
+        }
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    report
+}
+
+// Returns the configured recording folder.
+fn recording_folder(state: &State<AppData>) -> Result<PathBuf, String> {
+    state.0.lock().unwrap().folder_path.clone().ok_or_else(|| "File path not set.".to_string())
+}
+
+// Command to list the recordings of the recording folder, newest first.
+#[tauri::command]
+async fn list_recordings(state: State<'_, AppData>) -> Result<Vec<library::RecordingSummary>, String> {
+    let folder = recording_folder(&state)?;
+    tauri::async_runtime::spawn_blocking(move || library::list(&folder))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Command to open a recording of the folder for replay or export.
+#[tauri::command]
+async fn open_recording(state: State<'_, AppData>, name: String) -> Result<library::RecordingDetails, String> {
+    let folder = recording_folder(&state)?;
+    tauri::async_runtime::spawn_blocking(move || library::open(&folder, &name))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to rename a recording of the folder; returns the new file name.
+#[tauri::command]
+async fn rename_recording(
+    app: tauri::AppHandle,
+    state: State<'_, AppData>,
+    name: String,
+    new_name: String,
+) -> Result<String, String> {
+    let folder = recording_folder(&state)?;
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        library::rename(&folder, &name, &new_name).map(|renamed| (name, renamed))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match result {
+        Ok((name, renamed)) => {
+            backend_log(&app, &format!("Recording {} renamed to {}.", name, renamed), "SUCCESS");
+            Ok(renamed)
+        }
+        Err(e) => {
+            backend_log(&app, &e, "ERROR");
+            Err(e)
+        }
+    }
+}
+
+// Command to delete a recording of the folder together with its sidecar.
+#[tauri::command]
+async fn delete_recording(app: tauri::AppHandle, state: State<'_, AppData>, name: String) -> Result<(), String> {
+    let folder = recording_folder(&state)?;
+    let result = {
+        let name = name.clone();
+        tauri::async_runtime::spawn_blocking(move || library::delete(&folder, &name))
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    match &result {
+        Ok(()) => backend_log(&app, &format!("Recording {} deleted.", name), "SUCCESS"),
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    result
+}
+
+// Command to move a recording to the archive folder, by default the one of the retention rules.
+#[tauri::command]
+async fn archive_recording(
+    app: tauri::AppHandle,
+    state: State<'_, AppData>,
+    name: String,
+    archive_folder: Option<String>,
+) -> Result<(), String> {
+    let folder = recording_folder(&state)?;
+    let archive = archive_folder
+        .map(PathBuf::from)
+        .or_else(|| state.0.lock().unwrap().retention_config.archive_folder.clone())
+        .ok_or_else(|| "Archive folder not set.".to_string())?;
+    let result = {
+        let (name, archive) = (name.clone(), archive.clone());
+        tauri::async_runtime::spawn_blocking(move || library::archive(&folder, &name, &archive))
+            .await
+            .map_err(|e| e.to_string())?
+    };
+    match &result {
+        Ok(()) => backend_log(
+            &app,
+            &format!("Recording {} archived to {}.", name, archive.display()),
+            "SUCCESS",
+        ),
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    result
+}
+
+// Command to configure the SQLite database of the session exports.
+#[tauri::command]
+fn set_database_config(app: tauri::AppHandle, state: State<AppData>, config: DatabaseConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Database config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.database_config = config;
+}
+
+// Command to retrieve the SQLite database configuration.
+#[tauri::command]
+fn get_database_config(state: State<AppData>) -> DatabaseConfig {
+    state.0.lock().unwrap().database_config.clone()
+}
+
+// Returns the configured database file.
+fn database_path(state: &State<AppData>) -> Result<PathBuf, String> {
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .database_config
+        .resolve(state_guard.folder_path.as_deref())
+        .ok_or_else(|| "Database path not set.".to_string())
+}
+
+// Command to start exporting the decoded traffic of the open port into the database.
+#[tauri::command]
+async fn start_database_export(app: tauri::AppHandle, state: State<'_, AppData>) -> Result<i64, String> {
+    let path = database_path(&state)?;
+    let (header, session) = {
+        let state_guard = state.0.lock().unwrap();
+        match &state_guard.connection {
+            None => return Err("Connect to port first.".to_string()),
+            Some(connection) if connection.database.is_some() => {
+                return Err("The database export is already running.".to_string())
+            }
+            Some(_) => {}
+        }
+        let header = session_header(&state_guard);
+        (header, state_guard.session_info.clone())
+    };
+
+    // Opening the database may wait for its lock; never hold the state meanwhile.
+    let prepared = {
+        let (path, header) = (path.clone(), header.clone());
+        let app_version = app.package_info().version.to_string();
+        tauri::async_runtime::spawn_blocking(move || {
+            database::prepare_export(&path, &header, &app_version, &session)
+        })
+        .await
+        .map_err(|e| e.to_string())??
+    };
+
+    let error = {
+        let mut state_guard = state.0.lock().unwrap();
+        match state_guard.connection.as_mut() {
+            // Another start may have won the race while the database was opened.
+            Some(connection) if connection.database.is_none() => {
+                let task = database::start_export(app.clone(), prepared, header, &connection.sinks);
+                let session_id = task.session_id;
+                connection.database = Some(task);
+                std::mem::drop(state_guard);
+                backend_log(
+                    &app,
+                    &format!("Exporting session {} into {}.", session_id, path.display()),
+                    "SUCCESS",
+                );
+                return Ok(session_id);
+            }
+            Some(_) => "The database export is already running.".to_string(),
+            // The port was lost in the meantime.
+            None => "The port was disconnected.".to_string(),
+        }
+    };
+    let _ = tauri::async_runtime::spawn_blocking(move || prepared.abandon()).await;
+    Err(error)
+}
+
+// Command to stop the live database export.
+#[tauri::command]
+async fn stop_database_export(app: tauri::AppHandle, state: State<'_, AppData>) -> Result<(), String> {
+    let (task, sinks) = {
+        let mut state_guard = state.0.lock().unwrap();
+        let connection = state_guard
+            .connection
+            .as_mut()
+            .ok_or_else(|| "No database export is running.".to_string())?;
+        let task = connection
+            .database
+            .take()
+            .ok_or_else(|| "No database export is running.".to_string())?;
+        (task, connection.sinks.clone())
+    };
+    let session_id = task.session_id;
+    task.stop(&sinks).await;
+    backend_log(&app, &format!("Database export of session {} stopped.", session_id), "INFO");
+    Ok(())
+}
+
+// Command to configure the time-series export.
+#[tauri::command]
+fn set_metrics_config(app: tauri::AppHandle, state: State<AppData>, config: MetricsConfig) {
+    let mut state_guard = state.0.lock().unwrap();
+    backend_log(
+        &app,
+        &format!("Metrics config updated: {:?}", config),
+        "SUCCESS",
+    );
+    state_guard.metrics_config = config;
+}
+
+// Command to retrieve the time-series export configuration.
+#[tauri::command]
+fn get_metrics_config(state: State<AppData>) -> MetricsConfig {
+    state.0.lock().unwrap().metrics_config.clone()
+}
+
+// Command to start exporting the device values and link statistics of the open port; returns the session ID.
+#[tauri::command]
+fn start_metrics_export(app: tauri::AppHandle, state: State<AppData>) -> Result<String, String> {
+    let mut state_guard = state.0.lock().unwrap();
+    let config = state_guard.metrics_config.clone();
+    let path = config
+        .resolve(state_guard.folder_path.as_deref())
+        .ok_or_else(|| "Metrics path not set.".to_string())?;
+    let header = session_header(&state_guard);
+    let connection = state_guard
+        .connection
+        .as_mut()
+        .ok_or_else(|| "Connect to port first.".to_string())?;
+    if connection.metrics.is_some() {
+        return Err("The metrics export is already running.".to_string());
+    }
+    let task = metrics::start_export(
+        app.clone(),
+        path.clone(),
+        &config,
+        header,
+        &connection.sinks,
+        connection.stats.clone(),
+    )?;
+    let session_id = task.session_id.clone();
+    connection.metrics = Some(task);
+    std::mem::drop(state_guard);
+    backend_log(
+        &app,
+        &format!("Exporting metrics of session {} into {}.", session_id, path.display()),
+        "SUCCESS",
+    );
+    Ok(session_id)
+}
+
+// Command to stop the live metrics export.
+#[tauri::command]
+async fn stop_metrics_export(app: tauri::AppHandle, state: State<'_, AppData>) -> Result<(), String> {
+    let (task, sinks) = {
+        let mut state_guard = state.0.lock().unwrap();
+        let connection = state_guard
+            .connection
+            .as_mut()
+            .ok_or_else(|| "No metrics export is running.".to_string())?;
+        let task = connection
+            .metrics
+            .take()
+            .ok_or_else(|| "No metrics export is running.".to_string())?;
+        (task, connection.sinks.clone())
+    };
+    let (session_id, path) = (task.session_id.clone(), task.path.clone());
+    task.stop(&sinks).await;
+    backend_log(
+        &app,
+        &format!("Metrics export of session {} into {} stopped.", session_id, path.display()),
+        "INFO",
+    );
+    Ok(())
+}
+
+// Command to export the device values and link statistics of a recording as InfluxDB line protocol or Prometheus text.
+#[tauri::command]
+async fn export_recording_metrics(
+    app: tauri::AppHandle,
+    state: State<'_, AppData>,
+    path: String,
+    format: metrics::MetricsFormat,
+    output: Option<String>,
+    session_id: Option<String>,
+) -> Result<metrics::MetricsReport, String> {
+    let interval_secs = state.0.lock().unwrap().metrics_config.interval_secs;
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        metrics::export(
+            std::path::Path::new(&path),
+            output.map(PathBuf::from),
+            format,
+            interval_secs,
+            session_id,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match &report {
+        Ok(report) => backend_log(
+            &app,
+            &format!(
+                "Exported metrics of {} to {}: {} points in {} series.",
+                report.input, report.output, report.points, report.series
+            ),
+            "SUCCESS",
+        ),
+        Err(e) => backend_log(&app, e, "ERROR"),
+    }
+    report
+}
+
+// Command to import a recording, raw or structured, into the database as a new session.
+#[tauri::command]
+async fn import_recording_to_database(
+    app: tauri::AppHandle,
+    state: State<'_, AppData>,
+    path: String,
+) -> Result<database::ImportReport, String> {
+    let database = database_path(&state)?;
+    let report = tauri::async_runtime::spawn_blocking(move || {
+        database::import(&database, std::path::Path::new(&path)).map(|report| (path, report))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match report {
+        Ok((path, report)) => {
+            backend_log(
+                &app,
+                &format!("Imported {} as session {}: {} frames.", path, report.session_id, report.frames),
+                "SUCCESS",
+            );
+            Ok(report)
+        }
+        Err(e) => {
+            backend_log(&app, &e, "ERROR");
+            Err(e)
+        }
+    }
+}
+
+// Command to list the sessions stored in the database.
+#[tauri::command]
+async fn list_database_sessions(state: State<'_, AppData>) -> Result<Vec<database::SessionRow>, String> {
+    let database = database_path(&state)?;
+    tauri::async_runtime::spawn_blocking(move || database::list_sessions(&database))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to query decoded frames by session, command, hardware ID and time range.
+#[tauri::command]
+async fn query_database(
+    state: State<'_, AppData>,
+    query: database::FrameQuery,
+) -> Result<Vec<database::FrameRow>, String> {
+    let database = database_path(&state)?;
+    tauri::async_runtime::spawn_blocking(move || database::query_frames(&database, &query))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// Command to search a recording for frames by command, hardware ID, value and time.
+// Returns the search ID; hits arrive through `recordingSearchResults` and `recordingSearchDone`.
+#[tauri::command]
+fn search_recording(
+    app: tauri::AppHandle,
+    searches: State<search::Searches>,
+    path: String,
+    query: search::SearchQuery,
+) -> u64 {
+    let search_id = search::start(app.clone(), &searches, PathBuf::from(&path), query);
+    backend_log(&app, &format!("Search {} started in {}.", search_id, path), "INFO");
+    search_id
+}
+
+// Command to cancel a running search.
+#[tauri::command]
+fn cancel_recording_search(searches: State<search::Searches>, search_id: u64) -> bool {
+    searches.cancel(search_id)
+}
+
+// Command to apply the retention rules to the recording folder right away.
+#[tauri::command]
+async fn apply_retention(app: tauri::AppHandle, state: State<'_, AppData>) -> Result<(), String> {
+    let (folder, config) = {
+        let state_guard = state.0.lock().unwrap();
+        if state_guard.is_recording {
+            // The recorder applies the rules itself and knows which file it is writing.
+            return Err("Retention is applied automatically while recording.".to_string());
+        }
+        (state_guard.folder_path.clone(), state_guard.retention_config.clone())
+    };
+    let folder = folder.ok_or_else(|| "File path not set.".to_string())?;
+    retention::enforce_and_report(&app, &folder, &config, Vec::new(), false).await;
+    Ok(())
+}
 
 // A simple greeting command for testing purposes.
 #[tauri::command]
@@ -443,19 +1742,62 @@ async fn make_window(handle: tauri::AppHandle) {
 
 // The main function where the Tauri application is initialized and run.
 fn main() {
+    // Headless tools, run as `app <tool> ...`: convert, diff, vcd, split and metrics.
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("convert") => std::process::exit(convert::run_cli(&args[2..])),
+        Some("diff") => std::process::exit(diff::run_cli(&args[2..])),
+        Some("vcd") => std::process::exit(vcd::run_cli(&args[2..])),
+        Some("split") => std::process::exit(split::run_cli(&args[2..])),
+        Some("metrics") => std::process::exit(metrics::run_cli(&args[2..])),
+        _ => {}
+    }
+
     tauri::Builder::default()
         .manage(AppData(
             Mutex::new(Data {
-                port: None,
+                connection: None,
                 folder_path: Some(PathBuf::from("/home")),
                 port_items: PortItems {
                     port_path: String::new(),
                     baud_rate: 0,
                 },
-                is_thread_open: Arc::new(AtomicBool::new(true)),
                 is_recording: false,
+                writer_config: WriterConfig::default(),
+                heartbeat_config: HeartbeatConfig::default(),
+                safe_state_config: SafeStateConfig::default(),
+                rotation_config: RotationConfig::default(),
+                retention_config: RetentionConfig::default(),
+                compression_config: CompressionConfig::default(),
+                session_info: SessionInfo::default(),
+                durability_config: DurabilityConfig::default(),
+                database_config: DatabaseConfig::default(),
+                barrier_stats_config: BarrierStatsConfig::default(),
+                serial_stats_config: SerialStatsConfig::default(),
+                trigger_config: TriggerConfig::default(),
+                split_config: SplitConfig::default(),
+                metrics_config: MetricsConfig::default(),
             }),
         ))
+        .manage(database::DatabaseLog::default())
+        .manage(search::Searches::default())
+        .manage(scheduler::Scheduler::default())
+        .setup(|app| {
+            // Send the safe state even if the backend panics.
+            safe_state::install_panic_hook(app.handle());
+            // Load the recording schedules and start checking them.
+            app.state::<scheduler::Scheduler>().load(&app.handle());
+            scheduler::start_scheduler_task(app.handle());
+            // Recover recordings left behind by a crash or power loss.
+            let handle = app.handle();
+            let folder = handle.state::<AppData>().0.lock().unwrap().folder_path.clone();
+            if let Some(folder) = folder {
+                tauri::async_runtime::spawn(async move {
+                    recovery::scan_and_report(&handle, folder).await;
+                });
+            }
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             set_port_items,
             handle_serial_connect,
@@ -466,8 +1808,80 @@ fn main() {
             send_serial,
             make_window,
             emit_error,
-            handle_serial_disconnect
+            handle_serial_disconnect,
+            set_writer_config,
+            get_write_queue_status,
+            set_heartbeat_config,
+            get_link_quality,
+            get_port_holders,
+            set_safe_state_config,
+            get_safe_state_config,
+            set_rotation_config,
+            get_rotation_config,
+            set_retention_config,
+            get_retention_config,
+            apply_retention,
+            set_compression_config,
+            get_compression_config,
+            read_recording_lines,
+            set_session_info,
+            get_session_info,
+            pause_recording,
+            resume_recording,
+            add_recording_marker,
+            set_trigger_config,
+            get_trigger_config,
+            set_split_config,
+            get_split_config,
+            arm_recording_trigger,
+            disarm_recording_trigger,
+            fire_recording_trigger,
+            list_schedules,
+            add_schedule,
+            update_schedule,
+            remove_schedule,
+            list_recording_markers,
+            recover_recordings,
+            set_durability_config,
+            get_durability_config,
+            convert_recording,
+            compare_recordings,
+            export_recording_vcd,
+            split_recording,
+            list_recordings,
+            open_recording,
+            rename_recording,
+            delete_recording,
+            archive_recording,
+            set_database_config,
+            get_database_config,
+            start_database_export,
+            stop_database_export,
+            set_metrics_config,
+            get_metrics_config,
+            start_metrics_export,
+            stop_metrics_export,
+            export_recording_metrics,
+            import_recording_to_database,
+            list_database_sessions,
+            query_database,
+            search_recording,
+            cancel_recording_search,
+            set_barrier_stats_config,
+            get_barrier_stats_config,
+            get_light_barrier_stats,
+            analyze_light_barriers,
+            set_serial_stats_config,
+            get_serial_stats_config,
+            get_serial_stats,
+            reset_serial_stats
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                // Leave the printer in a safe state and close the recording before the process ends.
+                tauri::async_runtime::block_on(disconnect_port(app));
+            }
+        });
 }
\ No newline at end of file
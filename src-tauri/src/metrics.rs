@@ -0,0 +1,765 @@
+// src/metrics.rs
+
+// Time-series export of decoded device values and link statistics, for bulk
+// import into a time-series database. Device values are one series per
+// component and hardware ID, link statistics one series per session:
+//
+//   dcubedism_led      on, intensity (%)
+//   dcubedism_motor    on, speed (Hz), direction (0 = CW, 1 = CCW)
+//   dcubedism_barrier  active
+//   dcubedism_link     frame, byte and error counters
+//
+// Every point is tagged with the port and the session ID, device values also
+// with the hardware ID and the direction. Two formats are written:
+//
+//   influx      InfluxDB line protocol, one line per frame, nanosecond timestamps
+//   prometheus  Prometheus text format; with millisecond timestamps from a
+//               recording, or while live as a snapshot of the current values,
+//               for the textfile collector of node_exporter; one metric per
+//               field, counters ending in `_total`
+//
+// Recordings are exported offline, the open port live as a sink of the serial
+// pipeline. Also available without the UI:
+//
+//   app metrics <recording> [--format influx|prometheus] [--interval-secs <n>]
+//               [--session <id>] [--output <path>]
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local}; // Crate for date and time handling.
+use std::collections::{BTreeMap, BTreeSet}; // Series by name and labels.
+use std::fs::{File, OpenOptions}; // Structs for writing the export.
+use std::io::{BufWriter, Write}; // Sync I/O traits.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+use std::sync::Arc; // Shared link statistics of the connection.
+use std::time::Duration; // Interval of the link statistics.
+use tokio::sync::mpsc::{self, Receiver}; // Channel feeding the export thread.
+
+use crate::convert::{self, Decoded};
+use crate::pipeline::{SinkEvent, SinkRegistry, SINK_CAPACITY};
+use crate::protocol::{self, Frame};
+use crate::recording::{self, Direction, RecordingHeader};
+use crate::serial_wrapper::backend_log;
+use crate::stats::{SerialStatsReport, StatsMonitor};
+
+/// Name of the pipeline sink feeding a live export.
+pub const METRICS_SINK: &str = "metrics";
+
+/// Format of a time-series export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsFormat {
+    Influx,
+    Prometheus,
+}
+
+impl MetricsFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MetricsFormat::Influx => "lp",
+            MetricsFormat::Prometheus => "prom",
+        }
+    }
+}
+
+/// Configuration of the time-series export.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsConfig {
+    /// File of the live export; `metrics.lp` or `metrics.prom` in the recording folder when unset.
+    pub path: Option<PathBuf>,
+    pub format: MetricsFormat,
+    /// Time between two points of the link statistics, in seconds; also how
+    /// often the live Prometheus snapshot is rewritten.
+    pub interval_secs: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            path: None,
+            format: MetricsFormat::Influx,
+            interval_secs: 10,
+        }
+    }
+}
+
+impl MetricsConfig {
+    /// File of the live export, if one can be determined.
+    pub fn resolve(&self, folder: Option<&Path>) -> Option<PathBuf> {
+        self.path
+            .clone()
+            .or_else(|| folder.map(|folder| folder.join(format!("metrics.{}", self.format.extension()))))
+    }
+}
+
+/// Result of an export, returned by `export_recording_metrics`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsReport {
+    pub input: String,
+    pub output: String,
+    pub format: MetricsFormat,
+    pub session_id: String,
+    pub frames: u64,
+    pub points: u64,
+    /// Distinct series written, one per measurement, tag set and field.
+    pub series: usize,
+}
+
+// Value of a field.
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+// A point: the fields of a measurement with its tags at one time.
+struct Point {
+    measurement: &'static str,
+    // Sorted by key, as InfluxDB prefers.
+    tags: Vec<(&'static str, String)>,
+    fields: Vec<(&'static str, Value)>,
+    unix_us: i64,
+}
+
+// Tags shared by all points of a session.
+struct Session {
+    port: String,
+    session_id: String,
+}
+
+impl Session {
+    fn tags(&self, hardware_id: Option<u8>, direction: Option<Direction>) -> Vec<(&'static str, String)> {
+        let mut tags = Vec::new();
+        if let Some(direction) = direction {
+            tags.push(("direction", direction.as_str().to_string()));
+        }
+        if let Some(hardware_id) = hardware_id {
+            tags.push(("hardware_id", hardware_id.to_string()));
+        }
+        tags.push(("port", self.port.clone()));
+        tags.push(("session_id", self.session_id.clone()));
+        tags
+    }
+
+    // Point of a frame, if the command carries a device state.
+    fn device_point(&self, frame: &Frame, direction: Option<Direction>, unix_us: i64) -> Option<Point> {
+        let state = (frame.value != 0) as i64;
+        let (measurement, field, value) = match frame.command_id {
+            protocol::LED_ON_OFF => ("dcubedism_led", "on", state),
+            protocol::LED_INTENSITY => ("dcubedism_led", "intensity", frame.value as i64),
+            protocol::MOTOR_ON_OFF => ("dcubedism_motor", "on", state),
+            protocol::MOTOR_SPEED => ("dcubedism_motor", "speed", frame.value as i64),
+            protocol::MOTOR_DIRECTION => ("dcubedism_motor", "direction", state),
+            protocol::LIGHT_BARRIER_TOGGLE => ("dcubedism_barrier", "active", state),
+            _ => return None,
+        };
+        Some(Point {
+            measurement,
+            tags: self.tags(Some(frame.hardware_id), direction),
+            fields: vec![(field, Value::Int(value))],
+            unix_us,
+        })
+    }
+
+    // Point of the link statistics of a live connection.
+    fn link_point(&self, report: &SerialStatsReport, unix_us: i64) -> Point {
+        let count = |value: u64| Value::Int(value as i64);
+        Point {
+            measurement: "dcubedism_link",
+            tags: self.tags(None, None),
+            fields: vec![
+                ("bytes_in", count(report.bytes_in)),
+                ("bytes_out", count(report.bytes_out)),
+                ("frames_in", count(report.frames_in)),
+                ("frames_out", count(report.frames_out)),
+                ("discarded_bytes", count(report.discarded_bytes)),
+                ("desync_events", count(report.desync_events)),
+                ("write_errors", count(report.write_errors)),
+                ("read_timeouts", count(report.read_timeouts)),
+                ("bytes_in_per_sec", Value::Float(report.bytes_in_per_sec)),
+                ("bytes_out_per_sec", Value::Float(report.bytes_out_per_sec)),
+            ],
+            unix_us,
+        }
+    }
+}
+
+// Link counters of a recording; it holds no write errors or read timeouts.
+#[derive(Default)]
+struct LinkCounters {
+    frames_in: u64,
+    frames_out: u64,
+    discarded_bytes: u64,
+    desync_events: u64,
+}
+
+impl LinkCounters {
+    fn point(&self, session: &Session, unix_us: i64) -> Point {
+        let count = |value: u64| Value::Int(value as i64);
+        Point {
+            measurement: "dcubedism_link",
+            tags: session.tags(None, None),
+            fields: vec![
+                ("frames_in", count(self.frames_in)),
+                ("frames_out", count(self.frames_out)),
+                ("discarded_bytes", count(self.discarded_bytes)),
+                ("desync_events", count(self.desync_events)),
+            ],
+            unix_us,
+        }
+    }
+}
+
+// Fields counting since the start of the session; all others are gauges.
+fn is_counter(field: &str) -> bool {
+    matches!(
+        field,
+        "bytes_in"
+            | "bytes_out"
+            | "frames_in"
+            | "frames_out"
+            | "discarded_bytes"
+            | "desync_events"
+            | "write_errors"
+            | "read_timeouts"
+    )
+}
+
+// Escapes a tag value of the line protocol.
+fn escape_tag(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+// Escapes a label value of the Prometheus text format.
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: Value, influx: bool) -> String {
+    match value {
+        Value::Int(value) if influx => format!("{}i", value),
+        Value::Int(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+    }
+}
+
+// Line of a point in the line protocol; empty tag values are not allowed there.
+fn influx_line(point: &Point) -> String {
+    let mut line = point.measurement.to_string();
+    for (key, value) in point.tags.iter().filter(|(_, value)| !value.is_empty()) {
+        line.push_str(&format!(",{}={}", key, escape_tag(value)));
+    }
+    let fields = point
+        .fields
+        .iter()
+        .map(|(field, value)| format!("{}={}", field, format_value(*value, true)))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{} {} {}\n", line, fields, point.unix_us * 1000)
+}
+
+// Name of the Prometheus metric of a field; counters end in `_total`.
+fn metric_name(measurement: &str, field: &str) -> String {
+    if is_counter(field) {
+        format!("{}_{}_total", measurement, field)
+    } else {
+        format!("{}_{}", measurement, field)
+    }
+}
+
+// Labels of a point in the Prometheus text format.
+fn labels(point: &Point) -> String {
+    point
+        .tags
+        .iter()
+        .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Latest values of the live Prometheus snapshot, grouped by metric as the text format requires.
+#[derive(Default)]
+struct Families {
+    // Type and latest value of every metric, by labels.
+    metrics: BTreeMap<String, (&'static str, BTreeMap<String, String>)>,
+}
+
+impl Families {
+    fn add(&mut self, point: &Point) {
+        let labels = labels(point);
+        for (field, value) in &point.fields {
+            let kind = if is_counter(field) { "counter" } else { "gauge" };
+            self.metrics
+                .entry(metric_name(point.measurement, field))
+                .or_insert_with(|| (kind, BTreeMap::new()))
+                .1
+                .insert(labels.clone(), format_value(*value, false));
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        for (name, (kind, series)) in &self.metrics {
+            writeln!(writer, "# TYPE {} {}", name, kind)?;
+            for (labels, value) in series {
+                writeln!(writer, "{}{{{}}} {}", name, labels, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// One metric of the offline Prometheus export.
+struct HistoryFamily {
+    kind: &'static str,
+    temporary: PathBuf,
+    file: BufWriter<File>,
+    // Latest sample of every series, in milliseconds, not written yet.
+    latest: BTreeMap<String, (i64, String)>,
+}
+
+// Samples of the offline Prometheus export, with their timestamps.
+//
+// The text format wants the samples of a metric together. Every metric is
+// streamed into a temporary file next to the output and the files are joined
+// at the end, so only the latest sample of every series is kept in memory.
+struct History {
+    path: PathBuf,
+    metrics: BTreeMap<String, HistoryFamily>,
+}
+
+impl History {
+    fn add(&mut self, point: &Point) -> std::io::Result<()> {
+        let labels = labels(point);
+        let unix_ms = point.unix_us.div_euclid(1000);
+        for (field, value) in &point.fields {
+            let name = metric_name(point.measurement, field);
+            if !self.metrics.contains_key(&name) {
+                let mut temporary = self.path.clone().into_os_string();
+                temporary.push(format!(".{}.tmp", name));
+                let temporary = PathBuf::from(temporary);
+                let file = BufWriter::new(File::create(&temporary)?);
+                let kind = if is_counter(field) { "counter" } else { "gauge" };
+                let family = HistoryFamily {
+                    kind,
+                    temporary,
+                    file,
+                    latest: BTreeMap::new(),
+                };
+                self.metrics.insert(name.clone(), family);
+            }
+            let family = self.metrics.get_mut(&name).unwrap();
+            let value = format_value(*value, false);
+            // Timestamps of a series must increase; a later sample in the same millisecond wins.
+            match family.latest.get_mut(&labels) {
+                Some(latest) if latest.0 >= unix_ms => latest.1 = value,
+                Some(latest) => {
+                    let (previous_ms, previous) = std::mem::replace(latest, (unix_ms, value));
+                    writeln!(family.file, "{}{{{}}} {} {}", name, labels, previous, previous_ms)?;
+                }
+                None => {
+                    family.latest.insert(labels.clone(), (unix_ms, value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the pending samples and joins the metrics into `writer`.
+    fn write(&mut self, writer: &mut impl Write) -> std::io::Result<()> {
+        for (name, family) in self.metrics.iter_mut() {
+            for (labels, (unix_ms, value)) in std::mem::take(&mut family.latest) {
+                writeln!(family.file, "{}{{{}}} {} {}", name, labels, value, unix_ms)?;
+            }
+            family.file.flush()?;
+            writeln!(writer, "# TYPE {} {}", name, family.kind)?;
+            std::io::copy(&mut File::open(&family.temporary)?, writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        for family in self.metrics.values() {
+            let _ = std::fs::remove_file(&family.temporary);
+        }
+    }
+}
+
+// Where the points go.
+enum Output {
+    Influx(BufWriter<File>),
+    // Snapshot of the latest values of a live export.
+    Prometheus(Families),
+    // Every sample of an offline export.
+    PrometheusHistory(History),
+}
+
+// Writes points in the chosen format.
+struct MetricsWriter {
+    path: PathBuf,
+    output: Output,
+    points: u64,
+    series: BTreeSet<String>,
+}
+
+impl MetricsWriter {
+    // A live export appends to line protocol files and keeps a snapshot of Prometheus ones.
+    fn create(path: &Path, format: MetricsFormat, live: bool) -> std::io::Result<Self> {
+        let output = match format {
+            MetricsFormat::Influx => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(live)
+                    .truncate(!live)
+                    .open(path)?;
+                Output::Influx(BufWriter::new(file))
+            }
+            MetricsFormat::Prometheus => {
+                // Fail now rather than when the first snapshot is written.
+                OpenOptions::new().create(true).append(true).open(path)?;
+                if live {
+                    Output::Prometheus(Families::default())
+                } else {
+                    Output::PrometheusHistory(History {
+                        path: path.to_path_buf(),
+                        metrics: BTreeMap::new(),
+                    })
+                }
+            }
+        };
+        Ok(MetricsWriter {
+            path: path.to_path_buf(),
+            output,
+            points: 0,
+            series: BTreeSet::new(),
+        })
+    }
+
+    fn write(&mut self, point: &Point) -> std::io::Result<()> {
+        let line = influx_line(point);
+        let key = line.split_once(' ').map_or(line.as_str(), |(key, _)| key);
+        for (field, _) in &point.fields {
+            self.series.insert(format!("{} {}", key, field));
+        }
+        self.points += 1;
+        match &mut self.output {
+            Output::Influx(writer) => writer.write_all(line.as_bytes()),
+            Output::Prometheus(families) => {
+                families.add(point);
+                Ok(())
+            }
+            Output::PrometheusHistory(history) => history.add(point),
+        }
+    }
+
+    // Makes the points written so far visible: flushes the line protocol, or
+    // replaces the Prometheus file at once, so a collector never reads half of it.
+    // The offline Prometheus export is complete after its only flush, at the end.
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.output {
+            Output::Influx(writer) => writer.flush(),
+            Output::Prometheus(families) => replace_file(&self.path, |writer| families.write(writer)),
+            Output::PrometheusHistory(history) => replace_file(&self.path, |writer| history.write(writer)),
+        }
+    }
+}
+
+// Writes `path` through a temporary file that replaces it once complete.
+fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut temporary = path.to_path_buf().into_os_string();
+    temporary.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&temporary)?);
+    write(&mut writer)?;
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&temporary, path)
+}
+
+// Session ID of a recording: its file name without extensions.
+fn recording_session_id(recording: &Path) -> String {
+    convert::output_with_extension(recording, "id")
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Session ID of a live export started at `started`, named like the recordings.
+pub fn live_session_id(started: DateTime<Local>) -> String {
+    format!("DCubedISM{}", started.format("%Y-%m-%d_%H.%M.%S"))
+}
+
+/// Exports a structured recording into `output`, or next to it as `.lp` or `.prom`. Blocking.
+///
+/// Link counters are written every `interval_secs` of the recording, 0 for
+/// once at its end. The session ID defaults to the recording name.
+pub fn export(
+    input: &Path,
+    output: Option<PathBuf>,
+    format: MetricsFormat,
+    interval_secs: u64,
+    session_id: Option<String>,
+) -> Result<MetricsReport, String> {
+    let output = output.unwrap_or_else(|| convert::output_with_extension(input, format.extension()));
+    if output == input {
+        return Err("The output would overwrite the recording.".to_string());
+    }
+    let read_error = |e: std::io::Error| format!("Could not read {}: {}", input.display(), e);
+    if !convert::is_structured(input).map_err(read_error)? {
+        return Err("Raw recordings carry no timestamps; convert them first.".to_string());
+    }
+    let started = recording::read_started(input)
+        .map_err(read_error)?
+        .ok_or_else(|| format!("{} has no start time.", input.display()))?;
+    let session = Session {
+        port: recording::read_header_field(input, "port").map_err(read_error)?.unwrap_or_default(),
+        session_id: session_id.unwrap_or_else(|| recording_session_id(input)),
+    };
+
+    let mut writer = MetricsWriter::create(&output, format, false)
+        .map_err(|e| format!("Could not write {}: {}", output.display(), e))?;
+    let started_us = started.timestamp_micros();
+    let interval_us = interval_secs.saturating_mul(1_000_000);
+    let mut link = LinkCounters::default();
+    let (mut next_link_us, mut end_us) = (interval_us, 0);
+    let mut frames = 0;
+
+    convert::decode(input, |item| {
+        let t_us = match &item {
+            Decoded::Frame { t_us, .. } => *t_us,
+            Decoded::Malformed(region) => region.t_us,
+        };
+        let t_us = match t_us {
+            Some(t_us) => t_us,
+            None => return Ok(()),
+        };
+        // Counters as they stood at every interval boundary passed.
+        while interval_us > 0 && t_us >= next_link_us {
+            writer.write(&link.point(&session, started_us + next_link_us as i64))?;
+            next_link_us += interval_us;
+        }
+        end_us = end_us.max(t_us);
+        match item {
+            Decoded::Frame { direction, frame, .. } => {
+                frames += 1;
+                match direction {
+                    Some(Direction::Rx) => link.frames_in += 1,
+                    Some(Direction::Tx) => link.frames_out += 1,
+                    None => {}
+                }
+                match session.device_point(&frame, direction, started_us + t_us as i64) {
+                    Some(point) => writer.write(&point),
+                    None => Ok(()),
+                }
+            }
+            Decoded::Malformed(region) => {
+                link.discarded_bytes += region.length as u64;
+                link.desync_events += 1;
+                Ok(())
+            }
+        }
+    })
+    // The counters at the end of the recording.
+    .and_then(|_| writer.write(&link.point(&session, started_us + end_us as i64)))
+    .and_then(|_| writer.flush())
+    .map_err(|e| format!("Export of {} failed: {}", input.display(), e))?;
+
+    Ok(MetricsReport {
+        input: input.to_string_lossy().to_string(),
+        output: output.to_string_lossy().to_string(),
+        format,
+        session_id: session.session_id,
+        frames,
+        points: writer.points,
+        series: writer.series.len(),
+    })
+}
+
+// Messages of a live export to its thread.
+enum MetricsMessage {
+    Event(SinkEvent),
+    Stats(SerialStatsReport),
+}
+
+/// Handle on a live export.
+pub struct MetricsTask {
+    pub session_id: String,
+    pub path: PathBuf,
+    forward: tauri::async_runtime::JoinHandle<()>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl MetricsTask {
+    /// Stops the export once every event received so far is written.
+    pub async fn stop(self, sinks: &SinkRegistry) {
+        sinks.unregister(METRICS_SINK);
+        let _ = self.forward.await;
+        let thread = self.thread;
+        let _ = tauri::async_runtime::spawn_blocking(move || thread.join()).await;
+    }
+}
+
+/// Starts a live export of the pipeline events and of the link statistics of `stats`.
+pub fn start_export(
+    app: tauri::AppHandle,
+    path: PathBuf,
+    config: &MetricsConfig,
+    header: RecordingHeader,
+    sinks: &SinkRegistry,
+    stats: Arc<StatsMonitor>,
+) -> Result<MetricsTask, String> {
+    let writer = MetricsWriter::create(&path, config.format, true)
+        .map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+    let session = Session {
+        port: header.port.clone(),
+        session_id: live_session_id(header.started),
+    };
+    let session_id = session.session_id.clone();
+
+    // A slow export thread holds the forwarding back, so the sink drops and counts the excess.
+    let (sender, receiver) = mpsc::channel(SINK_CAPACITY);
+    let mut events = sinks.register(METRICS_SINK);
+    let interval = Duration::from_secs(config.interval_secs.max(1));
+    let forward = tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                event = events.recv() => {
+                    // Ends once the sink is unregistered and its pending events are read.
+                    let event = match event {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    if sender.send(MetricsMessage::Event(event)).await.is_err() {
+                        return;
+                    }
+                }
+                _ = ticker.tick() => {
+                    let _ = sender.send(MetricsMessage::Stats(stats.report())).await;
+                }
+            }
+        }
+        // The counters at the end of the export.
+        let _ = sender.send(MetricsMessage::Stats(stats.report())).await;
+    });
+    let thread = std::thread::spawn(move || run_export(app, writer, session, header, receiver));
+
+    Ok(MetricsTask {
+        session_id,
+        path,
+        forward,
+        thread,
+    })
+}
+
+// Body of the export thread: writes the messages in batches.
+fn run_export(
+    app: tauri::AppHandle,
+    mut writer: MetricsWriter,
+    session: Session,
+    header: RecordingHeader,
+    mut receiver: Receiver<MetricsMessage>,
+) {
+    let started_us = header.started.timestamp_micros();
+    let prometheus = matches!(writer.output, Output::Prometheus(_));
+    let mut failed = false;
+
+    while let Some(message) = receiver.blocking_recv() {
+        let mut batch = vec![message];
+        while let Ok(message) = receiver.try_recv() {
+            batch.push(message);
+        }
+        let ticked = batch.iter().any(|message| matches!(message, MetricsMessage::Stats(_)));
+
+        let result = batch
+            .iter()
+            .try_for_each(|message| match message {
+                MetricsMessage::Event(event) => {
+                    let unix_us = started_us + recording::elapsed_us(header.started_at, event.at) as i64;
+                    match event
+                        .frame
+                        .as_ref()
+                        .and_then(|frame| session.device_point(frame, Some(event.direction), unix_us))
+                    {
+                        Some(point) => writer.write(&point),
+                        None => Ok(()),
+                    }
+                }
+                MetricsMessage::Stats(report) => writer.write(&session.link_point(report, Local::now().timestamp_micros())),
+            })
+            // The Prometheus snapshot is only rewritten with the link statistics.
+            .and_then(|_| if ticked || !prometheus { writer.flush() } else { Ok(()) });
+
+        // Report the first failure only; the export keeps trying with the next batch.
+        if let Err(e) = result {
+            if !failed {
+                failed = true;
+                backend_log(&app, &format!("Metrics export failed: {}", e), "ERROR");
+            }
+        }
+    }
+}
+
+/// Runs `export` from the command line and returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut format = MetricsFormat::Influx;
+    let mut interval_secs = MetricsConfig::default().interval_secs;
+    let mut session_id = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("influx") => format = MetricsFormat::Influx,
+                Some("prometheus") => format = MetricsFormat::Prometheus,
+                other => {
+                    eprintln!("Unknown format {:?}; use influx or prometheus.", other.unwrap_or_default());
+                    return 2;
+                }
+            },
+            "--interval-secs" => match args.next().and_then(|value| value.parse().ok()) {
+                Some(value) => interval_secs = value,
+                None => {
+                    eprintln!("--interval-secs expects a number of seconds.");
+                    return 2;
+                }
+            },
+            "--session" => session_id = args.next().cloned(),
+            "--output" => output = args.next().map(PathBuf::from),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument {}.", arg);
+                return 2;
+            }
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!(
+                "Usage: app metrics <recording> [--format influx|prometheus] [--interval-secs <n>] [--session <id>] [--output <path>]"
+            );
+            return 2;
+        }
+    };
+
+    match export(&input, output, format, interval_secs, session_id) {
+        Ok(report) => {
+            println!(
+                "{} points in {} series of session {} written to {}.",
+                report.points, report.series, report.session_id, report.output
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// src/pipeline.rs
+
+// The serial reader decodes everything it receives once and publishes the
+// result, together with the frames the writer task sends, to every registered
+// sink (recorder, statistics, bridges). Sinks come and go while the port stays
+// open; the reader never has to be restarted for them.
+//
+// Every sink has a bounded channel. A sink that falls SINK_CAPACITY events
+// behind loses the newer events until it catches up; the reader never waits
+// for a sink. The events dropped for a sink are counted and logged.
+
+// Importing necessary crates and modules.
+use std::sync::Mutex; // Mutex for the sink list.
+use std::time::Instant; // Struct for monotonic timestamps.
+use tokio::sync::mpsc::error::TrySendError; // Outcome of a send to a full or closed sink.
+use tokio::sync::mpsc::{self, Receiver, Sender}; // Bounded channels feeding the sinks.
+
+use crate::protocol::{DecodeEvent, Frame};
+use crate::recording::Direction;
+use crate::serial_wrapper::backend_log;
+
+/// Events a sink may fall behind by before newer events are dropped for it.
+pub const SINK_CAPACITY: usize = 16 * 1024;
+
+/// Bytes seen on the port, decoded when they form a frame.
+#[derive(Debug, Clone)]
+pub struct SinkEvent {
+    /// Monotonic instant at which the bytes were read or written.
+    pub at: Instant,
+    pub direction: Direction,
+    pub raw: Vec<u8>,
+    /// `None` for bytes discarded while resynchronizing.
+    pub frame: Option<Frame>,
+}
+
+impl SinkEvent {
+    /// Event for received bytes, as produced by the frame decoder.
+    pub fn received(at: Instant, event: &DecodeEvent) -> Self {
+        match event {
+            DecodeEvent::Frame(frame, raw) => SinkEvent {
+                at,
+                direction: Direction::Rx,
+                raw: raw.to_vec(),
+                frame: Some(*frame),
+            },
+            DecodeEvent::Discarded(raw) => SinkEvent {
+                at,
+                direction: Direction::Rx,
+                raw: raw.clone(),
+                frame: None,
+            },
+        }
+    }
+
+    /// Event for bytes written by the writer task.
+    pub fn sent(at: Instant, bytes: &[u8]) -> Self {
+        SinkEvent {
+            at,
+            direction: Direction::Tx,
+            raw: bytes.to_vec(),
+            frame: Frame::parse(bytes),
+        }
+    }
+}
+
+// A registered sink and the events dropped since it last kept up.
+struct Sink {
+    name: String,
+    sender: Sender<SinkEvent>,
+    dropped: u64,
+}
+
+/// Sinks registered on a connection, by name.
+pub struct SinkRegistry {
+    app: tauri::AppHandle,
+    sinks: Mutex<Vec<Sink>>,
+}
+
+impl SinkRegistry {
+    /// Creates an empty registry; overflows are logged through `app`.
+    pub fn new(app: tauri::AppHandle) -> Self {
+        SinkRegistry {
+            app,
+            sinks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Registers a sink and returns the receiving end of its channel.
+    ///
+    /// A sink registered under an existing name replaces the previous one.
+    pub fn register(&self, name: &str) -> Receiver<SinkEvent> {
+        let (sender, receiver) = mpsc::channel(SINK_CAPACITY);
+        let replaced = {
+            let mut sinks = self.sinks.lock().unwrap();
+            let replaced = take_sink(&mut sinks, name);
+            sinks.push(Sink {
+                name: name.to_string(),
+                sender,
+                dropped: 0,
+            });
+            replaced
+        };
+        if let Some(sink) = replaced {
+            self.report_dropped(&sink);
+        }
+        receiver
+    }
+
+    /// Removes a sink; its channel closes once the pending events are read.
+    pub fn unregister(&self, name: &str) {
+        let removed = take_sink(&mut self.sinks.lock().unwrap(), name);
+        if let Some(sink) = removed {
+            self.report_dropped(&sink);
+        }
+    }
+
+    /// Sends an event to every sink, dropping sinks whose receiver is gone.
+    ///
+    /// A full sink loses the event; the first loss is logged right away and
+    /// the total once the sink keeps up again.
+    pub fn publish(&self, event: SinkEvent) {
+        let mut lagging = Vec::new();
+        let mut recovered = Vec::new();
+        {
+            let mut sinks = self.sinks.lock().unwrap();
+            if sinks.is_empty() {
+                return;
+            }
+            sinks.retain_mut(|sink| match sink.sender.try_send(event.clone()) {
+                Ok(()) => {
+                    if sink.dropped > 0 {
+                        recovered.push((sink.name.clone(), sink.dropped));
+                        sink.dropped = 0;
+                    }
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    if sink.dropped == 0 {
+                        lagging.push(sink.name.clone());
+                    }
+                    sink.dropped += 1;
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+        }
+
+        for name in lagging {
+            backend_log(
+                &self.app,
+                &format!("The {} sink is {} events behind; newer events are dropped for it.", name, SINK_CAPACITY),
+                "WARNING",
+            );
+        }
+        for (name, dropped) in recovered {
+            backend_log(
+                &self.app,
+                &format!("The {} sink caught up after losing {} event(s).", name, dropped),
+                "WARNING",
+            );
+        }
+    }
+
+    // Logs the events a sink lost before it was removed.
+    fn report_dropped(&self, sink: &Sink) {
+        if sink.dropped > 0 {
+            backend_log(
+                &self.app,
+                &format!("The {} sink lost {} event(s) before it stopped.", sink.name, sink.dropped),
+                "WARNING",
+            );
+        }
+    }
+}
+
+// Removes the sink registered as `name`, if any.
+fn take_sink(sinks: &mut Vec<Sink>, name: &str) -> Option<Sink> {
+    let index = sinks.iter().position(|sink| sink.name == name)?;
+    Some(sinks.remove(index))
+}
@@ -0,0 +1,147 @@
+// src/port_lock.rs
+
+// Importing necessary crates and modules.
+use tokio_serial::SerialStream; // Async serial stream.
+
+/// A process that currently has the serial device open.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortHolder {
+    pub pid: u32,
+    pub name: String,
+}
+
+impl std::fmt::Display for PortHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "'{}' (PID {})", self.name, self.pid)
+    }
+}
+
+/// Lists the other processes holding `port_path` open.
+///
+/// Walks `/proc/<pid>/fd` on Linux; other platforms have no cheap equivalent
+/// and always return an empty list.
+#[cfg(target_os = "linux")]
+pub fn find_port_holders(port_path: &str) -> Vec<PortHolder> {
+    let target = match std::fs::canonicalize(port_path) {
+        Ok(target) => target,
+        Err(_) => return Vec::new(),
+    };
+    let own_pid = std::process::id();
+    let mut holders = Vec::new();
+
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return holders,
+    };
+    for entry in entries.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+            Some(pid) if pid != own_pid => pid,
+            _ => continue,
+        };
+        // File descriptors of processes owned by other users are not readable; skip them.
+        let fds = match std::fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let holds_port = fds
+            .flatten()
+            .any(|fd| std::fs::read_link(fd.path()).map(|link| link == target).unwrap_or(false));
+        if holds_port {
+            let name = std::fs::read_to_string(entry.path().join("comm"))
+                .map(|comm| comm.trim().to_string())
+                .unwrap_or_else(|_| "unknown".to_string());
+            holders.push(PortHolder { pid, name });
+        }
+    }
+    holders
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn find_port_holders(_port_path: &str) -> Vec<PortHolder> {
+    Vec::new()
+}
+
+/// Claims exclusive access to an open port.
+///
+/// On Unix this sets TIOCEXCL, so later `open` calls on the device fail, and
+/// takes a non-blocking `flock`, which other well-behaved programs respect.
+/// Windows already opens COM ports exclusively.
+#[cfg(unix)]
+pub fn lock_exclusive(stream: &mut SerialStream) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+
+    stream
+        .set_exclusive(true)
+        .map_err(|e| format!("Could not set exclusive access (TIOCEXCL): {}", e))?;
+
+    // SAFETY: the descriptor belongs to `stream`, which outlives this call.
+    let result = unsafe { libc::flock(stream.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if result != 0 {
+        let error = std::io::Error::last_os_error();
+        return Err(if error.kind() == std::io::ErrorKind::WouldBlock {
+            "The port is locked (flock) by another process.".to_string()
+        } else {
+            format!("Could not lock the port: {}", error)
+        });
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn lock_exclusive(_stream: &mut SerialStream) -> Result<(), String> {
+    Ok(())
+}
+
+/// Builds the message shown when a port is held by other processes.
+pub fn describe_busy(port_path: &str, holders: &[PortHolder]) -> String {
+    let names = holders
+        .iter()
+        .map(|holder| holder.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut msg = format!("Port '{}' is busy: it is already open in {}.", port_path, names);
+
+    if holders.iter().any(|holder| holder.name.contains("ModemManager")) {
+        msg.push_str(
+            " ModemManager probes new serial devices; stop it with \
+             'sudo systemctl stop ModemManager' or add a udev rule with \
+             ENV{ID_MM_DEVICE_IGNORE}=\"1\" for this device.",
+        );
+    } else {
+        msg.push_str(" Close the other application (or the other DCubedISM window) and try again.");
+    }
+    msg
+}
+
+/// Turns an error from opening the port into an explanation with suggested fixes.
+pub fn describe_open_error(port_path: &str, error: &tokio_serial::Error) -> String {
+    let holders = find_port_holders(port_path);
+    if !holders.is_empty() {
+        return describe_busy(port_path, &holders);
+    }
+
+    let description = error.to_string();
+    match error.kind() {
+        tokio_serial::ErrorKind::NoDevice | tokio_serial::ErrorKind::Io(std::io::ErrorKind::NotFound) => format!(
+            "Port '{}' does not exist anymore: {}. Check the cable and refresh the port list.",
+            port_path, description
+        ),
+        tokio_serial::ErrorKind::Io(std::io::ErrorKind::PermissionDenied) if cfg!(windows) => format!(
+            "Access to '{}' was denied: {}. Another application is probably using the port; close it and try again.",
+            port_path, description
+        ),
+        tokio_serial::ErrorKind::Io(std::io::ErrorKind::PermissionDenied) => format!(
+            "Permission denied opening '{}': {}. Your user is probably not in the group owning the device \
+             (usually 'dialout', or 'uucp' on Arch). Run 'sudo usermod -aG dialout $USER', then log out and back in.",
+            port_path, description
+        ),
+        // EBUSY is not mapped to an I/O kind, so fall back to the OS description.
+        _ if description.contains("busy") => format!(
+            "Port '{}' is busy: {}. Another process has it open in exclusive mode; \
+             close the other application and try again.",
+            port_path, description
+        ),
+        _ => format!("Error opening port '{}': {}", port_path, description),
+    }
+}
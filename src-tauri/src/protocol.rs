@@ -0,0 +1,212 @@
+// src/protocol.rs
+
+// Binary frame format shared by the application and the firmware:
+// [COMMAND_ID (1 byte), HARDWARE_ID (1 byte), VALUE (4 bytes, little-endian), '\n' (1 byte)]
+
+/// Length of a complete frame in bytes.
+pub const FRAME_LEN: usize = 7;
+/// Last byte of every frame.
+pub const FRAME_TERMINATOR: u8 = 0x0A;
+
+// Command IDs, mirroring `src/utils/commandIds.ts`.
+pub const MOTOR_DIRECTION: u8 = 1;
+pub const MOTOR_SPEED: u8 = 2;
+pub const MOTOR_ON_OFF: u8 = 3;
+pub const LED_ON_OFF: u8 = 7;
+pub const LED_INTENSITY: u8 = 8;
+pub const RESET: u8 = 9;
+pub const PRODUCTION_MODE: u8 = 10;
+pub const LIGHT_BARRIER_TOGGLE: u8 = 20;
+
+/// A decoded 7-byte frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Frame {
+    pub command_id: u8,
+    pub hardware_id: u8,
+    pub value: u32,
+}
+
+impl Frame {
+    /// Creates a frame from its fields.
+    pub fn new(command_id: u8, hardware_id: u8, value: u32) -> Self {
+        Frame {
+            command_id,
+            hardware_id,
+            value,
+        }
+    }
+
+    /// Decodes a complete frame, or returns `None` if the bytes are not one.
+    pub fn parse(bytes: &[u8]) -> Option<Frame> {
+        if bytes.len() != FRAME_LEN || bytes[FRAME_LEN - 1] != FRAME_TERMINATOR {
+            return None;
+        }
+        Some(Frame {
+            command_id: bytes[0],
+            hardware_id: bytes[1],
+            value: u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]),
+        })
+    }
+
+    /// Encodes the frame into its wire format.
+    pub fn encode(&self) -> [u8; FRAME_LEN] {
+        let value = self.value.to_le_bytes();
+        [
+            self.command_id,
+            self.hardware_id,
+            value[0],
+            value[1],
+            value[2],
+            value[3],
+            FRAME_TERMINATOR,
+        ]
+    }
+
+    /// Human readable name of the command.
+    pub fn command_name(&self) -> &'static str {
+        command_name(self.command_id)
+    }
+}
+
+/// Returns the name of a command ID, as used in logs and exports.
+pub fn command_name(command_id: u8) -> &'static str {
+    match command_id {
+        MOTOR_DIRECTION => "MOTOR_DIRECTION",
+        MOTOR_SPEED => "MOTOR_SPEED",
+        MOTOR_ON_OFF => "MOTOR_ON_OFF",
+        LED_ON_OFF => "LED_ON_OFF",
+        LED_INTENSITY => "LED_INTENSITY",
+        RESET => "RESET",
+        PRODUCTION_MODE => "PRODUCTION_MODE",
+        LIGHT_BARRIER_TOGGLE => "LIGHT_BARRIER_TOGGLE",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Output of the frame decoder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeEvent {
+    /// A complete frame together with its raw bytes.
+    Frame(Frame, [u8; FRAME_LEN]),
+    /// Bytes skipped while resynchronizing on the frame terminator.
+    Discarded(Vec<u8>),
+}
+
+/// Incremental decoder turning a byte stream into frames.
+///
+/// When a 7-byte window does not end with the terminator, the decoder drops
+/// bytes one at a time until it finds a valid frame again; the dropped bytes
+/// are reported as a single `Discarded` event before the next frame.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    discarded: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder::default()
+    }
+
+    /// Feeds received bytes and returns the decoded events in order.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<DecodeEvent> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            self.buffer.push(byte);
+            if self.buffer.len() < FRAME_LEN {
+                continue;
+            }
+
+            match Frame::parse(&self.buffer) {
+                Some(frame) => {
+                    if !self.discarded.is_empty() {
+                        events.push(DecodeEvent::Discarded(std::mem::take(&mut self.discarded)));
+                    }
+                    let mut raw = [0u8; FRAME_LEN];
+                    raw.copy_from_slice(&self.buffer);
+                    events.push(DecodeEvent::Frame(frame, raw));
+                    self.buffer.clear();
+                }
+                None => {
+                    // Out of sync: slide the window by one byte.
+                    self.discarded.push(self.buffer.remove(0));
+                }
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_event(frame: Frame) -> DecodeEvent {
+        DecodeEvent::Frame(frame, frame.encode())
+    }
+
+    #[test]
+    fn encode_and_parse_round_trip() {
+        let frame = Frame::new(MOTOR_SPEED, 3, 0x0102_0304);
+        let bytes = frame.encode();
+        assert_eq!(bytes, [2, 3, 0x04, 0x03, 0x02, 0x01, FRAME_TERMINATOR]);
+        assert_eq!(Frame::parse(&bytes), Some(frame));
+        assert_eq!(Frame::parse(&bytes[..FRAME_LEN - 1]), None);
+    }
+
+    #[test]
+    fn frames_split_across_reads() {
+        let first = Frame::new(LED_ON_OFF, 1, 1);
+        let second = Frame::new(LED_INTENSITY, 1, 80);
+        let bytes: Vec<u8> = [first.encode(), second.encode()].concat();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.feed(&bytes[..4]), vec![]);
+        assert_eq!(decoder.feed(&bytes[4..10]), vec![frame_event(first)]);
+        assert_eq!(decoder.feed(&bytes[10..]), vec![frame_event(second)]);
+    }
+
+    #[test]
+    fn resyncs_after_garbage() {
+        let frame = Frame::new(MOTOR_ON_OFF, 2, 1);
+        let mut bytes = vec![0xFF, 0x00, 0x13];
+        bytes.extend_from_slice(&frame.encode());
+        bytes.extend_from_slice(&frame.encode());
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(&bytes),
+            vec![
+                DecodeEvent::Discarded(vec![0xFF, 0x00, 0x13]),
+                frame_event(frame),
+                frame_event(frame),
+            ]
+        );
+    }
+
+    #[test]
+    fn garbage_across_reads_is_reported_once() {
+        let frame = Frame::new(LIGHT_BARRIER_TOGGLE, 1, 0);
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.feed(&[0xAA, 0xBB]), vec![]);
+        assert_eq!(decoder.feed(&[0xCC, 0xDD, 0xEE, 0x01]), vec![]);
+        let mut events = decoder.feed(&frame.encode()[..3]);
+        events.extend(decoder.feed(&frame.encode()[3..]));
+        assert_eq!(
+            events,
+            vec![DecodeEvent::Discarded(vec![0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0x01]), frame_event(frame)]
+        );
+    }
+
+    #[test]
+    fn terminator_inside_garbage_is_discarded() {
+        // Too short to be a frame, so the window slides past it.
+        let frame = Frame::new(MOTOR_DIRECTION, 4, 1);
+        let mut bytes = vec![0x01, FRAME_TERMINATOR];
+        bytes.extend_from_slice(&frame.encode());
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(
+            decoder.feed(&bytes),
+            vec![DecodeEvent::Discarded(vec![0x01, FRAME_TERMINATOR]), frame_event(frame)]
+        );
+    }
+}
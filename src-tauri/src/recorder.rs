@@ -0,0 +1,702 @@
+// src/recorder.rs
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local, TimeZone, Timelike}; // Crate para lidar com data/hora locais.
+use sha2::{Digest, Sha256}; // Hash of the recorded data.
+use std::path::PathBuf; // Struct for handling filesystem paths.
+use std::sync::atomic::{AtomicBool, Ordering}; // Pause flag shared with the commands.
+use std::sync::{Arc, Mutex}; // Markers shared with the commands.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio::fs::File; // Async struct for file operations.
+use tokio::io::{AsyncWriteExt, BufWriter}; // Async write trait and buffered writer.
+use tokio::sync::mpsc::{self, Receiver, UnboundedSender}; // Channels of the recorder sink and its controls.
+use tokio_util::sync::CancellationToken; // Token used to stop the recorder task.
+
+use crate::compression::{CompressionConfig, Encoder};
+use crate::pipeline::SinkEvent;
+use crate::recording::{self, Marker, Record, RecordingEvent, RecordingHeader};
+use crate::recovery;
+use crate::retention::{self, RetentionConfig};
+use crate::serial_wrapper::backend_log;
+use crate::sidecar::{self, SessionInfo, Sidecar};
+use crate::split;
+
+/// When to close the current recording file and start a new one.
+///
+/// Every limit is optional; the file rotates as soon as any of them is reached.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotationConfig {
+    /// Maximum time a file stays open, in seconds.
+    pub max_duration_secs: Option<u64>,
+    /// Maximum size of a file, in bytes.
+    pub max_size_bytes: Option<u64>,
+    /// Maximum number of decoded frames in a file.
+    pub max_frames: Option<u64>,
+    /// Rotate on wall-clock boundaries every N minutes since midnight (60 = top of the hour).
+    pub boundary_minutes: Option<u32>,
+}
+
+impl Default for RotationConfig {
+    fn default() -> Self {
+        RotationConfig {
+            max_duration_secs: Some(600),
+            max_size_bytes: None,
+            max_frames: None,
+            boundary_minutes: None,
+        }
+    }
+}
+
+/// How recording files are written to disk.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DurabilityConfig {
+    /// Size of the write buffer, in bytes.
+    pub buffer_bytes: usize,
+    /// Maximum time written data stays unsynced, in milliseconds.
+    pub sync_interval_ms: u64,
+}
+
+impl Default for DurabilityConfig {
+    fn default() -> Self {
+        DurabilityConfig {
+            buffer_bytes: 64 * 1024,
+            sync_interval_ms: 1000,
+        }
+    }
+}
+
+/// Everything the record task needs to know about the session.
+#[derive(Clone)]
+pub struct RecordingOptions {
+    pub folder: PathBuf,
+    pub header: RecordingHeader,
+    pub rotation: RotationConfig,
+    pub retention: RetentionConfig,
+    pub compression: CompressionConfig,
+    pub durability: DurabilityConfig,
+    pub session: SessionInfo,
+    pub app_version: String,
+}
+
+// Time to wait before retrying a rotation whose new file could not be created.
+const ROTATION_RETRY: Duration = Duration::from_secs(10);
+
+// Payload of the `recordingRotated` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RotatedPayload {
+    path: String,
+    size_bytes: u64,
+    frames: u64,
+    reason: &'static str,
+}
+
+/// Returns the first wall-clock boundary after `now`, every `minutes` since midnight.
+pub fn next_boundary(now: DateTime<Local>, minutes: u32) -> DateTime<Local> {
+    let minutes = minutes.clamp(1, 24 * 60) as i64;
+    let since_midnight = (now.hour() * 60 + now.minute()) as i64;
+    let next = (since_midnight / minutes + 1) * minutes;
+    let midnight = now.date_naive().and_hms_opt(0, 0, 0).unwrap();
+    Local
+        .from_local_datetime(&(midnight + chrono::Duration::minutes(next)))
+        .earliest()
+        // The boundary falls into a DST gap: fall back to a plain interval.
+        .unwrap_or(now + chrono::Duration::minutes(minutes))
+}
+
+// Name of the first rotation limit reached at `now` by a file with these counters, if any.
+fn limit_reached(
+    rotation: &RotationConfig,
+    size: u64,
+    frames: u64,
+    opened_at: Instant,
+    boundary: Option<Instant>,
+    now: Instant,
+) -> Option<&'static str> {
+    if rotation.max_size_bytes.is_some_and(|max| size >= max) {
+        Some("size")
+    } else if rotation.max_frames.is_some_and(|max| frames >= max) {
+        Some("frames")
+    } else if boundary.is_some_and(|boundary| now >= boundary) {
+        Some("boundary")
+    } else if rotation
+        .max_duration_secs
+        .is_some_and(|secs| now >= opened_at + Duration::from_secs(secs))
+    {
+        Some("duration")
+    } else {
+        None
+    }
+}
+
+/// The file currently being written, with the counters used by the rotation policy.
+///
+/// The data goes to `<path>.partial`, renamed to `path` once the file is closed.
+pub struct CurrentFile {
+    file: BufWriter<File>,
+    encoder: Encoder,
+    path: PathBuf,
+    partial: PathBuf,
+    size: u64, // Bytes on disk, after compression.
+    frames: u64,
+    opened_at: Instant,
+    boundary: Option<Instant>,
+    block_bytes: u64, // Uncompressed bytes since the last complete block.
+    block_started: Option<Instant>,
+    sidecar: Sidecar,
+    hasher: Sha256, // Hash of everything written to disk.
+    last_sync: Instant,
+    unsynced: bool,
+}
+
+impl CurrentFile {
+    // Creates file number `part` of the session, with its header and sidecar.
+    async fn create(options: &RecordingOptions, part: u32) -> std::io::Result<Self> {
+        let path = new_file_path(&options.folder, &options.compression);
+        let sidecar = Sidecar::new(
+            &path,
+            part,
+            &options.header,
+            &options.app_version,
+            &options.session,
+            options.compression.algorithm,
+        );
+        let partial = recovery::partial_path(&path);
+        let file = BufWriter::with_capacity(options.durability.buffer_bytes.max(1), File::create(&partial).await?);
+        let boundary = options.rotation.boundary_minutes.map(|minutes| {
+            let now = Local::now();
+            let wait = (next_boundary(now, minutes) - now).to_std().unwrap_or_default();
+            Instant::now() + wait
+        });
+        let mut current = CurrentFile {
+            file,
+            encoder: Encoder::new(&options.compression)?,
+            path,
+            partial,
+            size: 0,
+            frames: 0,
+            opened_at: Instant::now(),
+            boundary,
+            block_bytes: 0,
+            block_started: None,
+            sidecar,
+            hasher: Sha256::new(),
+            last_sync: Instant::now(),
+            unsynced: false,
+        };
+        current.append(options.header.to_text().as_bytes(), &options.compression).await?;
+        current.sidecar.save(&current.path).await?;
+        Ok(current)
+    }
+
+    /// Final path of the file.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// Removes a file that was created but never recorded into, with its sidecar.
+    pub async fn discard(self) {
+        let CurrentFile { file, path, partial, .. } = self;
+        drop(file);
+        if let Err(e) = tokio::fs::remove_file(&partial).await {
+            println!("Could not remove {}: {}", partial.to_string_lossy(), e);
+        }
+        let _ = tokio::fs::remove_file(sidecar::sidecar_path(&path)).await;
+    }
+
+    // Compresses and writes data, ending the block once it is large enough.
+    async fn append(&mut self, data: &[u8], compression: &CompressionConfig) -> std::io::Result<()> {
+        let output = self.encoder.write(data)?;
+        self.write_output(&output).await?;
+        self.sidecar.data_bytes += data.len() as u64;
+        if self.encoder.is_compressed() {
+            self.block_bytes += data.len() as u64;
+            self.block_started.get_or_insert_with(Instant::now);
+            if self.block_bytes >= compression.block_bytes {
+                self.end_block().await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Ends the current compressed block so it survives a crash.
+    async fn end_block(&mut self) -> std::io::Result<()> {
+        // The block is over even if writing it fails, so its deadline does not fire again at once.
+        self.block_bytes = 0;
+        self.block_started = None;
+        let output = self.encoder.flush()?;
+        self.write_output(&output).await
+    }
+
+    async fn write_output(&mut self, output: &[u8]) -> std::io::Result<()> {
+        if !output.is_empty() {
+            self.file.write_all(output).await?;
+            self.hasher.update(output);
+            self.size += output.len() as u64;
+            self.unsynced = true;
+        }
+        Ok(())
+    }
+
+    // Writes the buffer out and syncs the data to disk.
+    async fn sync(&mut self) -> std::io::Result<()> {
+        // A failed sync is retried after the next interval, not at once.
+        self.last_sync = Instant::now();
+        self.file.flush().await?;
+        self.file.get_ref().sync_data().await?;
+        self.unsynced = false;
+        Ok(())
+    }
+
+    // Instant at which buffered data has to be synced, if any.
+    fn sync_deadline(&self, durability: &DurabilityConfig) -> Option<Instant> {
+        if self.unsynced {
+            Some(self.last_sync + Duration::from_millis(durability.sync_interval_ms))
+        } else {
+            None
+        }
+    }
+
+    // Instant at which the pending block has to be written, if any.
+    fn block_deadline(&self, compression: &CompressionConfig) -> Option<Instant> {
+        self.block_started
+            .map(|started| started + Duration::from_millis(compression.block_ms))
+    }
+
+    // Instant at which a time-based limit is reached, if any.
+    fn deadline(&self, rotation: &RotationConfig) -> Option<Instant> {
+        let by_duration = rotation
+            .max_duration_secs
+            .map(|secs| self.opened_at + Duration::from_secs(secs));
+        match (by_duration, self.boundary) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    // Name of the limit that has been reached, if any.
+    fn rotation_reason(&self, rotation: &RotationConfig) -> Option<&'static str> {
+        limit_reached(rotation, self.size, self.frames, self.opened_at, self.boundary, Instant::now())
+    }
+
+    async fn write_event(
+        &mut self,
+        t_us: u64,
+        event: &RecordingEvent,
+        compression: &CompressionConfig,
+    ) -> std::io::Result<()> {
+        self.append(event.to_line(t_us).as_bytes(), compression).await
+    }
+
+    async fn write_record(&mut self, record: &Record, compression: &CompressionConfig) -> std::io::Result<()> {
+        self.append(record.to_line().as_bytes(), compression).await?;
+        if record.frame.is_some() {
+            self.frames += 1;
+        }
+        Ok(())
+    }
+
+    // Finishes the compressed stream with its trailer, syncs the file to disk,
+    // gives it its final name and completes the sidecar.
+    async fn close(mut self, reason: &str) -> ClosedFile {
+        let encoder = std::mem::replace(&mut self.encoder, Encoder::Plain);
+        match encoder.finish() {
+            Ok(trailer) => {
+                let _ = self.write_output(&trailer).await;
+            }
+            Err(e) => println!("Could not finish {}: {}", self.path.to_string_lossy(), e),
+        }
+        let _ = self.file.flush().await;
+        let _ = self.file.get_ref().sync_all().await;
+        if let Err(e) = tokio::fs::rename(&self.partial, &self.path).await {
+            // The startup scan will pick the file up under its partial name.
+            println!("Could not rename {}: {}", self.partial.to_string_lossy(), e);
+            return ClosedFile {
+                path: self.partial,
+                size: self.size,
+                frames: self.frames,
+            };
+        }
+
+        self.sidecar.ended = Some(sidecar::now_rfc3339());
+        self.sidecar.end_reason = Some(reason.to_string());
+        self.sidecar.bytes = self.size;
+        self.sidecar.frames = self.frames;
+        self.sidecar.sha256 = Some(sidecar::hex_digest(&self.hasher.finalize()));
+        if let Err(e) = self.sidecar.save(&self.path).await {
+            println!("Could not write the sidecar of {}: {}", self.path.to_string_lossy(), e);
+        }
+        ClosedFile {
+            path: self.path,
+            size: self.size,
+            frames: self.frames,
+        }
+    }
+}
+
+// A closed file: where it ended up and its final size, trailer included.
+struct ClosedFile {
+    path: PathBuf,
+    size: u64,
+    frames: u64,
+}
+
+/// Sleeps until `deadline`, or forever when there is none.
+pub async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Builds the path of a new recording file in `folder`.
+///
+/// Files rotated within the same second get a numeric suffix instead of
+/// overwriting each other.
+pub fn new_file_path(folder: &std::path::Path, compression: &CompressionConfig) -> PathBuf {
+    let formatted_date_time = Local::now().format("%Y-%m-%d_%H.%M.%S").to_string();
+    let extension = compression.algorithm.extension();
+    let mut path = folder.join(format!("DCubedISM{}.txt{}", formatted_date_time, extension));
+    let mut suffix = 1;
+    while path.exists() || recovery::partial_path(&path).exists() {
+        path = folder.join(format!("DCubedISM{}_{}.txt{}", formatted_date_time, suffix, extension));
+        suffix += 1;
+    }
+    path
+}
+
+/// Creates the first file of a session; the record task opens the later ones itself.
+pub async fn open_session_file(options: &RecordingOptions) -> std::io::Result<CurrentFile> {
+    CurrentFile::create(options, 1).await
+}
+
+/// Name under which the recorder registers on the connection's sinks.
+pub const RECORDER_SINK: &str = "recorder";
+
+/// A running recording, owned by the connection it records.
+pub struct RecordingTask {
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+    events: UnboundedSender<(Instant, RecordingEvent)>,
+    paused: Arc<AtomicBool>,
+    markers: Arc<Mutex<Vec<Marker>>>,
+}
+
+impl RecordingTask {
+    /// Stops the recording and waits until its file is closed.
+    pub async fn stop(self) {
+        self.cancel.cancel();
+        let _ = self.task.await;
+    }
+
+    /// Stops writing traffic until `resume`; markers are still recorded.
+    pub fn pause(&self) -> Result<(), String> {
+        if self.paused.swap(true, Ordering::SeqCst) {
+            return Err("The recording is already paused.".to_string());
+        }
+        self.send(RecordingEvent::Pause)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        if !self.paused.swap(false, Ordering::SeqCst) {
+            return Err("The recording is not paused.".to_string());
+        }
+        self.send(RecordingEvent::Resume)
+    }
+
+    /// Writes a timestamped marker into the recording.
+    pub fn add_marker(&self, text: &str) -> Result<(), String> {
+        self.send(RecordingEvent::Marker(text.to_string()))
+    }
+
+    /// Markers written so far in this session.
+    pub fn markers(&self) -> Vec<Marker> {
+        self.markers.lock().unwrap().clone()
+    }
+
+    fn send(&self, event: RecordingEvent) -> Result<(), String> {
+        self.events
+            .send((Instant::now(), event))
+            .map_err(|_| "The recording has stopped.".to_string())
+    }
+}
+
+// Function to start a task that records serial data to files in the session folder.
+//
+// The task is a sink of the live pipeline: received and sent bytes arrive on
+// `events` already decoded and in the order they happened. Files rotate
+// according to the rotation policy, checked after every write and on a timer,
+// and the retention rules are applied periodically and before each rotation.
+pub fn start_record_task(
+    app: tauri::AppHandle,
+    mut current: CurrentFile,
+    options: RecordingOptions,
+    mut events: Receiver<SinkEvent>,
+    cancel: CancellationToken,
+) -> RecordingTask {
+    let session_start = options.header.started_at;
+    // A live split writes into the channel folder of the first file until the end.
+    let channels = split::channel_folder(&current.path);
+    let mut retention_timer =
+        tokio::time::interval(Duration::from_secs(options.retention.check_interval_secs.max(1)));
+    let mut low_space_warned = false;
+    let mut retry_rotation_at: Option<Instant> = None;
+    let mut write_failed = false;
+    let mut part = 1;
+    let mut stopping = false;
+    let stop = cancel.clone();
+    let (control, mut controls) = mpsc::unbounded_channel::<(Instant, RecordingEvent)>();
+    let paused = Arc::new(AtomicBool::new(false));
+    let markers = Arc::new(Mutex::new(Vec::new()));
+    let (task_paused, task_markers) = (paused.clone(), markers.clone());
+
+    let task = tauri::async_runtime::spawn(async move {
+        println!("Record task spawned");
+
+        loop {
+            // While a failed rotation waits for its retry, do not wake up before it.
+            let deadline = current
+                .deadline(&options.rotation)
+                .map(|deadline| retry_rotation_at.map_or(deadline, |at| deadline.max(at)));
+            let records = tokio::select! {
+                _ = cancel.cancelled() => {
+                    // Write what the pipeline already published before closing the file.
+                    stopping = true;
+                    let mut pending = Vec::new();
+                    while let Ok(event) = events.try_recv() {
+                        if !task_paused.load(Ordering::SeqCst) {
+                            pending.push(Record::from_event(session_start, &event));
+                        }
+                    }
+                    pending
+                }
+                _ = sleep_until(deadline) => Vec::new(),
+                _ = sleep_until(current.sync_deadline(&options.durability)) => {
+                    if let Err(e) = current.sync().await {
+                        backend_log(&app, &format!("Could not sync the recording: {}", e), "ERROR");
+                    }
+                    Vec::new()
+                }
+                _ = sleep_until(current.block_deadline(&options.compression)) => {
+                    if let Err(e) = current.end_block().await {
+                        backend_log(&app, &format!("Could not write to the recording: {}", e), "ERROR");
+                    }
+                    Vec::new()
+                }
+                _ = retention_timer.tick() => {
+                    low_space_warned = retention::enforce_and_report(
+                        &app,
+                        &options.folder,
+                        &options.retention,
+                        vec![current.partial.clone(), channels.clone()],
+                        low_space_warned,
+                    )
+                    .await;
+                    Vec::new()
+                }
+                Some((at, event)) = controls.recv() => {
+                    let t_us = recording::elapsed_us(session_start, at);
+                    if let RecordingEvent::Marker(text) = &event {
+                        task_markers.lock().unwrap().push(Marker {
+                            t_us,
+                            time: Some(recording::wall_time(options.header.started, t_us)),
+                            text: text.clone(),
+                            file: current.path.file_name().map(|name| name.to_string_lossy().to_string()),
+                        });
+                    }
+                    if let Err(e) = current.write_event(t_us, &event, &options.compression).await {
+                        backend_log(&app, &format!("Could not write to the recording: {}", e), "ERROR");
+                    }
+                    Vec::new()
+                }
+                event = events.recv() => match event {
+                    // Traffic is consumed but not written while paused.
+                    Some(_) if task_paused.load(Ordering::SeqCst) => Vec::new(),
+                    Some(event) => vec![Record::from_event(session_start, &event)],
+                    None => {
+                        stopping = true;
+                        Vec::new()
+                    }
+                },
+            };
+
+            for record in &records {
+                match current.write_record(record, &options.compression).await {
+                    Ok(()) => write_failed = false,
+                    Err(e) => {
+                        // Report the first failure only; retention may free space and let writes resume.
+                        if !write_failed {
+                            backend_log(&app, &format!("Could not write to the recording: {}", e), "ERROR");
+                        }
+                        write_failed = true;
+                    }
+                }
+            }
+
+            if stopping {
+                break;
+            }
+
+            // Rotate the recording file once any limit of the policy is reached.
+            let retry_due = !matches!(retry_rotation_at, Some(at) if Instant::now() < at);
+            if let Some(reason) = current.rotation_reason(&options.rotation).filter(|_| retry_due) {
+                // Make room first, so a full disk does not prevent the new file.
+                low_space_warned = retention::enforce_and_report(
+                    &app,
+                    &options.folder,
+                    &options.retention,
+                    vec![current.partial.clone(), channels.clone()],
+                    low_space_warned,
+                )
+                .await;
+                match CurrentFile::create(&options, part + 1).await {
+                    Ok(next) => {
+                        retry_rotation_at = None;
+                        part += 1;
+                        let finished = std::mem::replace(&mut current, next);
+                        announce_rotation(&app, finished, reason).await;
+                    }
+                    Err(e) => {
+                        // Keep recording into the current file rather than losing the session.
+                        backend_log(
+                            &app,
+                            &format!(
+                                "Could not create the next recording file, still writing to {}: {}",
+                                current.path.to_string_lossy(),
+                                e
+                            ),
+                            "ERROR",
+                        );
+                        retry_rotation_at = Some(Instant::now() + ROTATION_RETRY);
+                    }
+                }
+            }
+        }
+
+        // Flush and close the file cleanly so nothing is lost on disconnect or exit.
+        current.close("stopped").await;
+        println!("Terminating record task.");
+    });
+
+    RecordingTask {
+        cancel: stop,
+        task,
+        events: control,
+        paused,
+        markers,
+    }
+}
+
+// Closes a rotated file and reports it in a `recordingRotated` event.
+async fn announce_rotation(app: &tauri::AppHandle, finished: CurrentFile, reason: &'static str) {
+    let closed = finished.close(reason).await;
+    let payload = RotatedPayload {
+        path: closed.path.to_string_lossy().to_string(),
+        size_bytes: closed.size,
+        frames: closed.frames,
+        reason,
+    };
+    backend_log(
+        app,
+        &format!("Recording rotated ({}): {} ({} bytes)", reason, payload.path, payload.size_bytes),
+        "INFO",
+    );
+    if let Err(e) = app.emit_all("recordingRotated", payload) {
+        println!("Failed to emit recording rotation to the frontend: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rotation(
+        max_duration_secs: Option<u64>,
+        max_size_bytes: Option<u64>,
+        max_frames: Option<u64>,
+    ) -> RotationConfig {
+        RotationConfig {
+            max_duration_secs,
+            max_size_bytes,
+            max_frames,
+            boundary_minutes: None,
+        }
+    }
+
+    fn local(hour: u32, minute: u32, second: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, 15, hour, minute, second).unwrap()
+    }
+
+    #[test]
+    fn no_limits_never_rotate() {
+        let opened_at = Instant::now();
+        let later = opened_at + Duration::from_secs(365 * 24 * 3600);
+        let config = rotation(None, None, None);
+        assert_eq!(limit_reached(&config, u64::MAX, u64::MAX, opened_at, None, later), None);
+    }
+
+    #[test]
+    fn size_and_frames_rotate_once_reached() {
+        let now = Instant::now();
+        let by_size = rotation(None, Some(1000), None);
+        assert_eq!(limit_reached(&by_size, 999, 0, now, None, now), None);
+        assert_eq!(limit_reached(&by_size, 1000, 0, now, None, now), Some("size"));
+        let by_frames = rotation(None, None, Some(50));
+        assert_eq!(limit_reached(&by_frames, 0, 49, now, None, now), None);
+        assert_eq!(limit_reached(&by_frames, 0, 50, now, None, now), Some("frames"));
+    }
+
+    #[test]
+    fn duration_rotates_once_elapsed() {
+        let opened_at = Instant::now();
+        let config = rotation(Some(600), None, None);
+        let before = opened_at + Duration::from_secs(599);
+        let after = opened_at + Duration::from_secs(600);
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, None, before), None);
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, None, after), Some("duration"));
+    }
+
+    #[test]
+    fn boundary_rotates_once_passed() {
+        let opened_at = Instant::now();
+        let boundary = opened_at + Duration::from_secs(30);
+        let config = rotation(None, None, None);
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, Some(boundary), opened_at), None);
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, Some(boundary), boundary), Some("boundary"));
+    }
+
+    #[test]
+    fn first_limit_in_order_is_reported() {
+        let opened_at = Instant::now();
+        let now = opened_at + Duration::from_secs(3600);
+        let config = rotation(Some(60), Some(10), Some(10));
+        assert_eq!(limit_reached(&config, 10, 10, opened_at, Some(opened_at), now), Some("size"));
+        assert_eq!(limit_reached(&config, 0, 10, opened_at, Some(opened_at), now), Some("frames"));
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, Some(opened_at), now), Some("boundary"));
+        assert_eq!(limit_reached(&config, 0, 0, opened_at, None, now), Some("duration"));
+    }
+
+    #[test]
+    fn next_boundary_is_strictly_after_now() {
+        assert_eq!(next_boundary(local(10, 7, 30), 15), local(10, 15, 0));
+        assert_eq!(next_boundary(local(10, 15, 0), 15), local(10, 30, 0));
+        assert_eq!(next_boundary(local(10, 59, 59), 60), local(11, 0, 0));
+    }
+
+    #[test]
+    fn next_boundary_crosses_midnight() {
+        let next = next_boundary(local(23, 50, 0), 60);
+        assert_eq!(next, Local.with_ymd_and_hms(2024, 6, 16, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn next_boundary_clamps_the_interval() {
+        assert_eq!(next_boundary(local(10, 7, 30), 0), local(10, 8, 0));
+        let next = next_boundary(local(10, 7, 30), 10_000);
+        assert_eq!(next, Local.with_ymd_and_hms(2024, 6, 16, 0, 0, 0).unwrap());
+    }
+}
@@ -0,0 +1,250 @@
+// src/recording.rs
+
+// Recording file format (UTF-8 text, one entry per line, fields separated by tabs):
+//
+//   #DCUBEDISM-REC	2
+//   #started	2026-10-18T22:00:00.000000+02:00
+//   #port	/dev/ttyACM0
+//   #baud	115200
+//   #columns	t_us	dir	raw_hex	command	command_id	hardware_id	value
+//   1532	TX	07 01 01 00 00 00 0A	LED_ON_OFF	7	1	1
+//   1877	RX	07 01 01 00 00 00 0A	LED_ON_OFF	7	1	1
+//   2011	RX	FF 13
+//
+// `t_us` counts microseconds on a monotonic clock since `#started`, so it never
+// jumps with wall-clock changes. Bytes that are not part of a frame are written
+// with their raw hex only.
+//
+// Since version 2, in-band events can appear between the data lines:
+//
+//   52311	MARK	door opened
+//   60020	PAUSE
+//   95112	RESUME
+//
+// Marker text is escaped: backslash, tab and newline become `\\`, `\t` and `\n`.
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local}; // Crate for date and time handling.
+use std::io::BufRead; // Line-by-line reading of recordings.
+use std::path::Path; // Struct for handling filesystem paths.
+use std::time::Instant; // Struct for monotonic timestamps.
+
+use crate::compression;
+use crate::pipeline::SinkEvent;
+use crate::protocol::Frame;
+
+/// First token of every structured recording.
+pub const RECORDING_MAGIC: &str = "#DCUBEDISM-REC";
+/// Version of the line format written by this build.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Direction of the recorded bytes, seen from the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Direction {
+    #[serde(rename = "RX")]
+    Rx,
+    #[serde(rename = "TX")]
+    Tx,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Rx => "RX",
+            Direction::Tx => "TX",
+        }
+    }
+}
+
+/// Information written into the header of every file of a session.
+#[derive(Debug, Clone)]
+pub struct RecordingHeader {
+    pub started: DateTime<Local>,
+    pub started_at: Instant, // Monotonic instant matching `started`.
+    pub port: String,
+    pub baud_rate: u32,
+}
+
+impl RecordingHeader {
+    /// Formats the header lines, including the trailing newline.
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\t{}\n#started\t{}\n#port\t{}\n#baud\t{}\n#columns\tt_us\tdir\traw_hex\tcommand\tcommand_id\thardware_id\tvalue\n",
+            RECORDING_MAGIC,
+            FORMAT_VERSION,
+            self.started.to_rfc3339_opts(chrono::SecondsFormat::Micros, false),
+            self.port,
+            self.baud_rate
+        )
+    }
+}
+
+/// One data line of a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub t_us: u64,
+    pub direction: Direction,
+    pub raw: Vec<u8>,
+    pub frame: Option<Frame>,
+}
+
+impl Record {
+    /// Record of a pipeline event, timed relative to the session start.
+    pub fn from_event(session_start: Instant, event: &SinkEvent) -> Self {
+        Record {
+            t_us: elapsed_us(session_start, event.at),
+            direction: event.direction,
+            raw: event.raw.clone(),
+            frame: event.frame,
+        }
+    }
+
+    /// Formats the record as a line, including the trailing newline.
+    pub fn to_line(&self) -> String {
+        let raw_hex = hex(&self.raw);
+        match self.frame {
+            Some(frame) => format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                self.t_us,
+                self.direction.as_str(),
+                raw_hex,
+                frame.command_name(),
+                frame.command_id,
+                frame.hardware_id,
+                frame.value
+            ),
+            None => format!("{}\t{}\t{}\n", self.t_us, self.direction.as_str(), raw_hex),
+        }
+    }
+}
+
+/// In-band event written between the data lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordingEvent {
+    Marker(String),
+    Pause,
+    Resume,
+}
+
+impl RecordingEvent {
+    /// Formats the event as a line, including the trailing newline.
+    pub fn to_line(&self, t_us: u64) -> String {
+        match self {
+            RecordingEvent::Marker(text) => format!("{}\tMARK\t{}\n", t_us, escape_text(text)),
+            RecordingEvent::Pause => format!("{}\tPAUSE\n", t_us),
+            RecordingEvent::Resume => format!("{}\tRESUME\n", t_us),
+        }
+    }
+}
+
+/// A marker, as returned by `list_recording_markers`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Marker {
+    pub t_us: u64,
+    /// Wall-clock time of the marker, RFC 3339.
+    pub time: Option<String>,
+    pub text: String,
+    pub file: Option<String>,
+}
+
+/// Wall-clock time `t_us` after the session start.
+pub fn wall_time(started: DateTime<Local>, t_us: u64) -> String {
+    (started + chrono::Duration::microseconds(t_us as i64))
+        .to_rfc3339_opts(chrono::SecondsFormat::Micros, false)
+}
+
+// Escapes marker text so it stays on one line and one field.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "")
+}
+
+// Reverses `escape_text`.
+fn unescape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Reads the value of a header field, e.g. `port`, from a recording file.
+pub fn read_header_field(path: &Path, name: &str) -> std::io::Result<Option<String>> {
+    let reader = compression::open_recording(path)?;
+    let prefix = format!("#{}\t", name);
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(value) = line.strip_prefix(prefix.as_str()) {
+            return Ok(Some(value.to_string()));
+        }
+        if !line.starts_with('#') {
+            break;
+        }
+    }
+    Ok(None)
+}
+
+/// Reads the `#started` time from the header of a recording file.
+pub fn read_started(path: &Path) -> std::io::Result<Option<DateTime<Local>>> {
+    Ok(read_header_field(path, "started")?.and_then(|value| {
+        DateTime::parse_from_rfc3339(&value)
+            .ok()
+            .map(|time| time.with_timezone(&Local))
+    }))
+}
+
+/// Reads the markers of a recording file, compressed or not.
+pub fn read_markers(path: &Path) -> std::io::Result<Vec<Marker>> {
+    let reader = compression::open_recording(path)?;
+    let file = path.file_name().map(|name| name.to_string_lossy().to_string());
+    let mut started: Option<DateTime<Local>> = None;
+    let mut markers = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(value) = line.strip_prefix("#started\t") {
+            started = DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|time| time.with_timezone(&Local));
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let (t_us, kind, text) = (fields.next(), fields.next(), fields.next());
+        if kind != Some("MARK") {
+            continue;
+        }
+        if let Some(t_us) = t_us.and_then(|t_us| t_us.parse::<u64>().ok()) {
+            markers.push(Marker {
+                t_us,
+                time: started.map(|started| wall_time(started, t_us)),
+                text: unescape_text(text.unwrap_or_default()),
+                file: file.clone(),
+            });
+        }
+    }
+    Ok(markers)
+}
+
+/// Formats bytes as space separated upper-case hex.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Microseconds elapsed between the session start and `at`.
+pub fn elapsed_us(session_start: Instant, at: Instant) -> u64 {
+    at.saturating_duration_since(session_start).as_micros() as u64
+}
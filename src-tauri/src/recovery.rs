@@ -0,0 +1,212 @@
+// src/recovery.rs
+
+// Recordings are written under a `.partial` name and renamed once they are
+// closed cleanly. A `.partial` file found later was left behind by a crash or
+// power loss; the scan below turns it back into a regular recording, or flags
+// it when nothing usable can be read from it.
+
+// Importing necessary crates and modules.
+use sha2::{Digest, Sha256}; // Hash of the recovered file.
+use std::io::{BufRead, Read}; // Sync I/O traits.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+use tauri::Manager; // Tauri Manager for emitting events.
+
+use crate::compression::{self, Compression};
+use crate::recording::RECORDING_MAGIC;
+use crate::retention::RECORDING_PREFIX;
+use crate::serial_wrapper::backend_log;
+use crate::sidecar::{self, Sidecar};
+
+/// Extension of a recording that is still being written.
+pub const PARTIAL_EXTENSION: &str = ".partial";
+
+/// Path under which `path` is written until it is closed.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(PARTIAL_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Outcome for one partial recording, also an entry of `partialRecordings`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveredFile {
+    pub path: String,
+    /// "recovered" or "flagged".
+    pub status: &'static str,
+    pub detail: String,
+}
+
+// What could be salvaged from a partial file.
+struct Salvage {
+    bytes: u64,
+    data_bytes: u64,
+    frames: u64,
+    sha256: String,
+}
+
+// Cuts an uncompressed file after its last complete line.
+fn truncate_to_last_line(path: &Path) -> std::io::Result<()> {
+    let data = std::fs::read(path)?;
+    let keep = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if keep < data.len() {
+        let file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.set_len(keep as u64)?;
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+// Checks the header and counts what a partial file still contains.
+fn salvage(path: &Path) -> Result<Salvage, String> {
+    if compression::detect(path).map_err(|e| e.to_string())? == Compression::None {
+        truncate_to_last_line(path).map_err(|e| e.to_string())?;
+    }
+
+    let mut reader = compression::open_recording(path).map_err(|e| e.to_string())?;
+    let mut first = String::new();
+    reader.read_line(&mut first).map_err(|e| e.to_string())?;
+    if !first.starts_with(RECORDING_MAGIC) {
+        return Err("no recording header could be read".to_string());
+    }
+
+    let mut data_bytes = first.len() as u64;
+    let mut frames = 0;
+    for line in reader.lines() {
+        // A compressed file may end inside a block; keep what decoded cleanly.
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        data_bytes += line.len() as u64 + 1;
+        if !line.starts_with('#') && line.split('\t').count() == 7 {
+            frames += 1;
+        }
+    }
+
+    let mut hasher = Sha256::new();
+    let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let mut buffer = [0u8; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        bytes += read as u64;
+    }
+
+    Ok(Salvage {
+        bytes,
+        data_bytes,
+        frames,
+        sha256: sidecar::hex_digest(&hasher.finalize()),
+    })
+}
+
+// Recovers one partial file into its final name.
+fn recover(partial: &Path) -> RecoveredFile {
+    let final_path = PathBuf::from(
+        partial
+            .to_string_lossy()
+            .strip_suffix(PARTIAL_EXTENSION)
+            .unwrap_or_default()
+            .to_string(),
+    );
+    let name = final_path.to_string_lossy().to_string();
+
+    let salvaged = match salvage(partial) {
+        Ok(salvaged) => salvaged,
+        Err(e) => {
+            return RecoveredFile {
+                path: partial.to_string_lossy().to_string(),
+                status: "flagged",
+                detail: e,
+            }
+        }
+    };
+    if final_path.exists() {
+        return RecoveredFile {
+            path: partial.to_string_lossy().to_string(),
+            status: "flagged",
+            detail: format!("{} already exists", name),
+        };
+    }
+    if let Err(e) = std::fs::rename(partial, &final_path) {
+        return RecoveredFile {
+            path: partial.to_string_lossy().to_string(),
+            status: "flagged",
+            detail: format!("could not rename: {}", e),
+        };
+    }
+
+    // Complete the sidecar written when the file was created.
+    let sidecar_path = sidecar::sidecar_path(&final_path);
+    let detail = match std::fs::read(&sidecar_path)
+        .ok()
+        .and_then(|json| serde_json::from_slice::<Sidecar>(&json).ok())
+    {
+        Some(mut meta) => {
+            meta.end_reason = Some("recovered".to_string());
+            meta.bytes = salvaged.bytes;
+            meta.data_bytes = salvaged.data_bytes;
+            meta.frames = salvaged.frames;
+            meta.sha256 = Some(salvaged.sha256);
+            match sidecar::save_blocking(&meta, &final_path) {
+                Ok(()) => format!("{} frames recovered", salvaged.frames),
+                Err(e) => format!("{} frames recovered, sidecar not updated: {}", salvaged.frames, e),
+            }
+        }
+        None => format!("{} frames recovered, no sidecar found", salvaged.frames),
+    };
+
+    RecoveredFile {
+        path: name,
+        status: "recovered",
+        detail,
+    }
+}
+
+/// Recovers or flags every partial recording in `folder`. Blocking.
+pub fn scan(folder: &Path) -> Vec<RecoveredFile> {
+    let entries = match std::fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut partials: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| name.starts_with(RECORDING_PREFIX) && name.ends_with(PARTIAL_EXTENSION))
+        })
+        .collect();
+    partials.sort();
+    partials.iter().map(|partial| recover(partial)).collect()
+}
+
+/// Scans `folder` for recordings left behind by a crash and reports them.
+///
+/// Must not run while a recording is being written to `folder`.
+pub async fn scan_and_report(app: &tauri::AppHandle, folder: PathBuf) -> Vec<RecoveredFile> {
+    let report = tauri::async_runtime::spawn_blocking(move || scan(&folder))
+        .await
+        .unwrap_or_default();
+
+    for file in &report {
+        let level = if file.status == "recovered" { "WARNING" } else { "ERROR" };
+        backend_log(
+            app,
+            &format!("Interrupted recording {} ({}): {}", file.status, file.path, file.detail),
+            level,
+        );
+    }
+    if !report.is_empty() {
+        if let Err(e) = app.emit_all("partialRecordings", report.clone()) {
+            println!("Failed to emit partial recordings to the frontend: {:?}", e);
+        }
+    }
+    report
+}
@@ -0,0 +1,517 @@
+// src/retention.rs
+
+// Importing necessary crates and modules.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+use std::time::{Duration, SystemTime}; // Structs for file ages.
+use tauri::Manager; // Tauri Manager for emitting events.
+
+use crate::serial_wrapper::backend_log;
+use crate::sidecar;
+use crate::split;
+
+/// Prefix shared by every file the recorder writes.
+pub const RECORDING_PREFIX: &str = "DCubedISM";
+/// Extensions of complete recordings: plain, gzip or zstd compressed text.
+pub const RECORDING_EXTENSIONS: [&str; 3] = [".txt", ".txt.gz", ".txt.zst"];
+
+/// True for the name of a complete recording as the recorder writes it.
+///
+/// Sidecars, partial files and the exports written next to a recording share
+/// its prefix, but not these extensions.
+pub fn is_recording_name(name: &str) -> bool {
+    name.starts_with(RECORDING_PREFIX) && RECORDING_EXTENSIONS.iter().any(|extension| name.ends_with(extension))
+}
+
+// Error of an archive action without a folder to archive to.
+const NO_ARCHIVE_FOLDER: &str = "Archiving needs an archive folder.";
+
+/// What happens to recordings removed by the retention rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RetentionAction {
+    Delete,
+    /// Move the files to `archive_folder`. Archiving to the same disk does not free space.
+    Archive,
+}
+
+/// Retention rules for the recording folder. Every limit is optional.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionConfig {
+    /// Maximum total size of the recordings in the folder, in bytes.
+    pub max_total_bytes: Option<u64>,
+    /// Maximum age of a recording, in hours.
+    pub max_age_hours: Option<u64>,
+    /// Free disk space to keep, in bytes; the oldest recordings go first.
+    pub min_free_bytes: Option<u64>,
+    /// Free disk space under which a `diskSpaceWarning` event fires, in bytes.
+    pub warn_free_bytes: Option<u64>,
+    pub action: RetentionAction,
+    pub archive_folder: Option<PathBuf>,
+    /// Time between two checks while recording, in seconds.
+    pub check_interval_secs: u64,
+}
+
+impl RetentionConfig {
+    /// Checks that the rules can be applied as configured.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.action == RetentionAction::Archive && self.archive_folder.is_none() {
+            return Err(NO_ARCHIVE_FOLDER.to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            max_total_bytes: None,
+            max_age_hours: None,
+            min_free_bytes: None,
+            warn_free_bytes: Some(1024 * 1024 * 1024),
+            action: RetentionAction::Delete,
+            archive_folder: None,
+            check_interval_secs: 60,
+        }
+    }
+}
+
+/// Result of applying the retention rules, also the payload of `retentionApplied`.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionReport {
+    pub removed: Vec<String>,
+    pub archived: Vec<String>,
+    pub freed_bytes: u64,
+    pub total_bytes: u64,
+    pub free_bytes: Option<u64>,
+    /// True when the free-space limit could not be met with the files available.
+    pub low_space: bool,
+    /// Why the rules were not applied, leaving every file in place.
+    pub error: Option<String>,
+}
+
+// Payload of the `diskSpaceWarning` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiskSpacePayload {
+    folder: String,
+    free_bytes: u64,
+    threshold_bytes: u64,
+}
+
+/// A recording found in the folder.
+pub struct RecordingFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Size of its channel folder, 0 when it has none.
+    pub channels_size: u64,
+    pub modified: SystemTime,
+}
+
+/// Lists the complete recordings in `folder`, oldest first.
+pub fn list_recordings(folder: &Path) -> Vec<RecordingFile> {
+    let mut files: Vec<RecordingFile> = match std::fs::read_dir(folder) {
+        Ok(entries) => entries
+            .flatten()
+            // Sidecars follow their recording and are not counted on their own;
+            // partial files are either being written or waiting for recovery.
+            .filter(|entry| is_recording_name(&entry.file_name().to_string_lossy()))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                if !metadata.is_file() {
+                    return None;
+                }
+                Some(RecordingFile {
+                    path: entry.path(),
+                    size: metadata.len(),
+                    channels_size: folder_size(&split::channel_folder(&entry.path())),
+                    modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                })
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort_by_key(|file| file.modified);
+    files
+}
+
+/// Free space on the disk holding `folder`, if it can be determined.
+pub fn free_space(folder: &Path) -> Option<u64> {
+    fs2::available_space(folder).ok()
+}
+
+/// Moves a file into `to_folder`, copying it when the folder is on another disk.
+pub fn move_file(from: &Path, to_folder: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to_folder)?;
+    let to = to_folder.join(from.file_name().unwrap_or_default());
+    if std::fs::rename(from, &to).is_err() {
+        std::fs::copy(from, &to)?;
+        std::fs::remove_file(from)?;
+    }
+    Ok(())
+}
+
+/// Total size of the files in a folder, 0 when it does not exist.
+pub fn folder_size(folder: &Path) -> u64 {
+    match std::fs::read_dir(folder) {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum(),
+        Err(_) => 0,
+    }
+}
+
+/// Moves a folder of files to `to`, copying them when `to` is on another disk.
+pub fn move_folder(from: &Path, to: &Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if std::fs::rename(from, to).is_err() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            move_file(&entry?.path(), to)?;
+        }
+        std::fs::remove_dir(from)?;
+    }
+    Ok(())
+}
+
+/// Applies the retention rules to `folder`, never touching the files in `keep`.
+///
+/// A recording goes together with its sidecar and its channel folder, unless
+/// that folder is in `keep` because a live split is still writing into it.
+///
+/// Blocking; run it on a blocking thread while recording.
+pub fn enforce(folder: &Path, config: &RetentionConfig, keep: &[PathBuf]) -> RetentionReport {
+    let mut report = RetentionReport::default();
+    let mut files: Vec<RecordingFile> = list_recordings(folder)
+        .into_iter()
+        .filter(|file| !keep.contains(&file.path))
+        .collect();
+    let kept_bytes: u64 = keep
+        .iter()
+        .map(|path| match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => folder_size(path),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum();
+    // A kept channel folder is counted once, in kept_bytes.
+    for file in files.iter_mut() {
+        if keep.contains(&split::channel_folder(&file.path)) {
+            file.channels_size = 0;
+        }
+    }
+    let mut total = kept_bytes
+        + files
+            .iter()
+            .map(|file| file.size + file.channels_size)
+            .sum::<u64>();
+    let mut free = free_space(folder);
+
+    // Oldest first: drop expired files, then the oldest until every limit holds.
+    let max_age = config.max_age_hours.map(|hours| Duration::from_secs(hours * 3600));
+    let now = SystemTime::now();
+    while let Some(oldest) = files.first() {
+        let expired = max_age.is_some_and(|max_age| {
+            now.duration_since(oldest.modified).unwrap_or_default() > max_age
+        });
+        let too_big = config.max_total_bytes.is_some_and(|max| total > max);
+        let too_full = matches!((config.min_free_bytes, free), (Some(min), Some(free)) if free < min);
+        if !expired && !too_big && !too_full {
+            break;
+        }
+
+        let oldest = files.remove(0);
+        let name = oldest.path.to_string_lossy().to_string();
+        let sidecar = sidecar::sidecar_path(&oldest.path);
+        let channels = split::channel_folder(&oldest.path);
+        let with_channels = !keep.contains(&channels);
+        let result = match (config.action, &config.archive_folder) {
+            (RetentionAction::Archive, Some(archive)) => move_file(&oldest.path, archive).map(|_| {
+                let _ = move_file(&sidecar, archive);
+                if with_channels {
+                    let archived = archive.join(oldest.path.file_name().unwrap_or_default());
+                    if let Err(e) = split::move_channel_folder(&oldest.path, &archived) {
+                        println!("Retention could not archive the channels of {}: {}", name, e);
+                    }
+                }
+                report.archived.push(name.clone())
+            }),
+            // Never delete files that were meant to be archived.
+            (RetentionAction::Archive, None) => {
+                report.error = Some(NO_ARCHIVE_FOLDER.to_string());
+                break;
+            }
+            (RetentionAction::Delete, _) => std::fs::remove_file(&oldest.path).map(|_| {
+                let _ = std::fs::remove_file(&sidecar);
+                if with_channels {
+                    if let Err(e) = split::remove_channel_folder(&oldest.path) {
+                        println!("Retention could not remove the channels of {}: {}", name, e);
+                    }
+                }
+                report.removed.push(name.clone())
+            }),
+        };
+        match result {
+            Ok(()) => {
+                total -= oldest.size + oldest.channels_size;
+                report.freed_bytes += oldest.size + oldest.channels_size;
+                free = free_space(folder);
+            }
+            Err(e) => println!("Retention could not remove {}: {}", name, e),
+        }
+    }
+
+    report.total_bytes = total;
+    report.free_bytes = free;
+    report.low_space = matches!((config.min_free_bytes, free), (Some(min), Some(free)) if free < min);
+    report
+}
+
+/// Applies the retention rules and reports the outcome to the frontend.
+///
+/// Returns true while the free space is below `warn_free_bytes`, so callers
+/// can warn only once per crossing.
+pub async fn enforce_and_report(
+    app: &tauri::AppHandle,
+    folder: &Path,
+    config: &RetentionConfig,
+    keep: Vec<PathBuf>,
+    warned: bool,
+) -> bool {
+    let report = {
+        let folder = folder.to_path_buf();
+        let config = config.clone();
+        match tauri::async_runtime::spawn_blocking(move || enforce(&folder, &config, &keep)).await {
+            Ok(report) => report,
+            Err(e) => {
+                backend_log(app, &format!("Retention check failed: {}", e), "ERROR");
+                return warned;
+            }
+        }
+    };
+
+    let changed = report.removed.len() + report.archived.len();
+    if changed > 0 {
+        backend_log(
+            app,
+            &format!(
+                "Retention removed {} recording(s), freeing {} bytes.",
+                changed, report.freed_bytes
+            ),
+            "INFO",
+        );
+    }
+    if let Some(error) = &report.error {
+        backend_log(app, &format!("Retention rules not applied: {}", error), "ERROR");
+    }
+    if report.low_space {
+        backend_log(
+            app,
+            "Not enough free disk space left, even after applying the retention rules.",
+            "ERROR",
+        );
+    }
+
+    let low = match (config.warn_free_bytes, report.free_bytes) {
+        (Some(threshold), Some(free_bytes)) if free_bytes < threshold => {
+            if !warned {
+                backend_log(
+                    app,
+                    &format!("Disk space is running low: {} bytes free.", free_bytes),
+                    "WARNING",
+                );
+                let payload = DiskSpacePayload {
+                    folder: folder.to_string_lossy().to_string(),
+                    free_bytes,
+                    threshold_bytes: threshold,
+                };
+                if let Err(e) = app.emit_all("diskSpaceWarning", payload) {
+                    println!("Failed to emit disk space warning to the frontend: {:?}", e);
+                }
+            }
+            true
+        }
+        _ => false,
+    };
+
+    if changed > 0 || report.low_space || report.error.is_some() {
+        if let Err(e) = app.emit_all("retentionApplied", report) {
+            println!("Failed to emit retention report to the frontend: {:?}", e);
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recovery;
+
+    // Empty folder of a test, removed again when dropped.
+    struct TestFolder(PathBuf);
+
+    impl TestFolder {
+        fn new(name: &str) -> Self {
+            let name = format!("dcubedism-retention-{}-{}", name, std::process::id());
+            let path = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TestFolder(path)
+        }
+
+        // Writes `size` bytes into `name`; files are written oldest first.
+        fn file(&self, name: impl AsRef<Path>, size: usize) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, vec![b'x'; size]).unwrap();
+            // Keep the modification times apart, so the order is certain.
+            std::thread::sleep(Duration::from_millis(20));
+            path
+        }
+    }
+
+    impl Drop for TestFolder {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn rules(max_total_bytes: Option<u64>, max_age_hours: Option<u64>) -> RetentionConfig {
+        RetentionConfig {
+            max_total_bytes,
+            max_age_hours,
+            warn_free_bytes: None,
+            ..RetentionConfig::default()
+        }
+    }
+
+    fn names(paths: &[String]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| Path::new(path).file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn recording_names() {
+        assert!(is_recording_name("DCubedISM2024-06-15_10.00.00.txt"));
+        assert!(is_recording_name("DCubedISM2024-06-15_10.00.00.txt.zst"));
+        assert!(!is_recording_name("DCubedISM2024-06-15_10.00.00.txt.partial"));
+        assert!(!is_recording_name("DCubedISM2024-06-15_10.00.00.txt.meta.json"));
+        assert!(!is_recording_name("DCubedISM2024-06-15_10.00.00.csv"));
+        assert!(!is_recording_name("notes.txt"));
+    }
+
+    #[test]
+    fn oldest_files_go_until_the_size_limit_holds() {
+        let folder = TestFolder::new("size");
+        let oldest = folder.file("DCubedISM_1.txt", 100);
+        let sidecar = folder.file(sidecar::sidecar_path(Path::new("DCubedISM_1.txt")), 10);
+        folder.file("DCubedISM_2.txt", 100);
+        folder.file("DCubedISM_3.txt", 100);
+
+        let report = enforce(&folder.0, &rules(Some(250), None), &[]);
+        assert_eq!(names(&report.removed), vec!["DCubedISM_1.txt"]);
+        assert_eq!(report.freed_bytes, 100);
+        assert_eq!(report.total_bytes, 200);
+        assert!(!oldest.exists());
+        assert!(!sidecar.exists());
+        assert_eq!(list_recordings(&folder.0).len(), 2);
+    }
+
+    #[test]
+    fn kept_and_partial_files_are_never_removed() {
+        let folder = TestFolder::new("keep");
+        let active = recovery::partial_path(&folder.0.join("DCubedISM_3.txt"));
+        let waiting = folder.file(recovery::partial_path(Path::new("DCubedISM_1.txt")), 100);
+        let kept = folder.file("DCubedISM_2.txt", 100);
+        let old = folder.file("DCubedISM_4.txt", 100);
+        std::fs::write(&active, vec![b'x'; 100]).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+
+        // Everything is older than 0 hours.
+        let report = enforce(&folder.0, &rules(None, Some(0)), &[active.clone(), kept.clone()]);
+        assert_eq!(names(&report.removed), vec!["DCubedISM_4.txt"]);
+        assert!(active.exists());
+        assert!(waiting.exists());
+        assert!(kept.exists());
+        assert!(!old.exists());
+    }
+
+    #[test]
+    fn kept_files_count_towards_the_size_limit() {
+        let folder = TestFolder::new("kept-size");
+        let old = folder.file("DCubedISM_1.txt", 100);
+        let active = folder.file(recovery::partial_path(Path::new("DCubedISM_2.txt")), 200);
+
+        let report = enforce(&folder.0, &rules(Some(250), None), std::slice::from_ref(&active));
+        assert_eq!(names(&report.removed), vec!["DCubedISM_1.txt"]);
+        assert_eq!(report.total_bytes, 200);
+        assert!(!old.exists());
+        assert!(active.exists());
+    }
+
+    #[test]
+    fn channel_folders_go_with_their_recording_unless_kept() {
+        let folder = TestFolder::new("channels");
+        let first = folder.file("DCubedISM_1.txt", 100);
+        let live = folder.file("DCubedISM_2.txt", 100);
+        for recording in [&first, &live] {
+            let channels = split::channel_folder(recording);
+            std::fs::create_dir_all(&channels).unwrap();
+            std::fs::write(channels.join("channel.csv"), vec![b'x'; 50]).unwrap();
+        }
+
+        let config = rules(None, Some(0));
+        let report = enforce(&folder.0, &config, &[split::channel_folder(&live)]);
+        assert_eq!(names(&report.removed), vec!["DCubedISM_1.txt", "DCubedISM_2.txt"]);
+        assert_eq!(report.freed_bytes, 250);
+        assert_eq!(report.total_bytes, 50);
+        assert!(!split::channel_folder(&first).exists());
+        assert!(split::channel_folder(&live).exists());
+    }
+
+    #[test]
+    fn archive_without_folder_removes_nothing() {
+        let folder = TestFolder::new("no-archive");
+        let old = folder.file("DCubedISM_1.txt", 100);
+        let config = RetentionConfig {
+            action: RetentionAction::Archive,
+            ..rules(None, Some(0))
+        };
+
+        let report = enforce(&folder.0, &config, &[]);
+        assert_eq!(report.error.as_deref(), Some(NO_ARCHIVE_FOLDER));
+        assert!(report.removed.is_empty() && report.archived.is_empty());
+        assert!(old.exists());
+    }
+
+    #[test]
+    fn archive_moves_the_recording_with_its_companions() {
+        let folder = TestFolder::new("archive");
+        let archive = folder.0.join("archive");
+        let old = folder.file("DCubedISM_1.txt", 100);
+        let sidecar = sidecar::sidecar_path(&old);
+        std::fs::write(&sidecar, b"{}").unwrap();
+        std::fs::create_dir_all(split::channel_folder(&old)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let config = RetentionConfig {
+            action: RetentionAction::Archive,
+            archive_folder: Some(archive.clone()),
+            ..rules(None, Some(0))
+        };
+
+        let report = enforce(&folder.0, &config, &[]);
+        assert_eq!(names(&report.archived), vec!["DCubedISM_1.txt"]);
+        assert!(!old.exists() && !sidecar.exists() && !split::channel_folder(&old).exists());
+        let archived = archive.join("DCubedISM_1.txt");
+        assert!(archived.exists());
+        assert!(sidecar::sidecar_path(&archived).exists());
+        assert!(split::channel_folder(&archived).is_dir());
+    }
+}
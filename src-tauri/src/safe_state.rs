@@ -0,0 +1,163 @@
+// src/safe_state.rs
+
+// Importing necessary crates and modules.
+use std::future::Future; // Tasks run as part of the connection.
+use std::sync::TryLockError; // Error returned by `Mutex::try_lock`.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for accessing the state.
+use tokio::sync::oneshot; // Acknowledgement of the drained queue.
+
+use crate::protocol::{self, Frame};
+use crate::serial_wrapper::backend_log;
+use crate::writer::WriterHandle;
+use crate::AppData;
+
+/// Frames sent to leave the printer in a safe state before the port closes.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeStateConfig {
+    pub enabled: bool,
+    /// Motors switched off with MOTOR_ON_OFF = 0.
+    pub motor_ids: Vec<u8>,
+    /// LEDs switched off with LED_ON_OFF = 0.
+    pub led_ids: Vec<u8>,
+    /// Finish the sequence with a RESET frame (command 9).
+    pub send_reset: bool,
+    /// How long to wait for the sequence to be written, in milliseconds.
+    pub timeout_ms: u64,
+}
+
+impl Default for SafeStateConfig {
+    fn default() -> Self {
+        SafeStateConfig {
+            enabled: true,
+            motor_ids: vec![1, 2, 3, 4],
+            led_ids: vec![1, 2, 3, 4],
+            send_reset: false,
+            timeout_ms: 1000,
+        }
+    }
+}
+
+impl SafeStateConfig {
+    /// Encoded frames of the sequence, in sending order.
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        for &motor_id in &self.motor_ids {
+            frames.push(Frame::new(protocol::MOTOR_ON_OFF, motor_id, 0).encode().to_vec());
+        }
+        for &led_id in &self.led_ids {
+            frames.push(Frame::new(protocol::LED_ON_OFF, led_id, 0).encode().to_vec());
+        }
+        if self.send_reset {
+            frames.push(Frame::new(protocol::RESET, 0, 0).encode().to_vec());
+        }
+        frames
+    }
+}
+
+// Queues the sequence ahead of whatever the operator sends next.
+fn queue_frames(writer: &WriterHandle, config: &SafeStateConfig) -> usize {
+    config
+        .frames()
+        .into_iter()
+        .filter(|frame| writer.enqueue_quiet(frame.clone()).is_ok())
+        .count()
+}
+
+/// Sends the safe-state sequence and waits until it has been written.
+pub async fn send_safe_state(app: &tauri::AppHandle, writer: &WriterHandle, config: &SafeStateConfig) {
+    if !config.enabled {
+        return;
+    }
+
+    let queued = queue_frames(writer, config);
+    if writer.drain(Duration::from_millis(config.timeout_ms)).await {
+        backend_log(app, &format!("Safe state sent ({} frames).", queued), "SUCCESS");
+    } else {
+        backend_log(app, "Safe state could not be confirmed before the port closed.", "WARNING");
+    }
+}
+
+tokio::task_local! {
+    // Set while one of the tasks driving the port is being polled.
+    static CONNECTION_TASK: ();
+}
+
+/// Runs a task that drives the port, so a panic in it counts as losing the connection.
+pub async fn connection_task<F: Future>(task: F) -> F::Output {
+    CONNECTION_TASK.scope((), task).await
+}
+
+// Whether a panic on the current thread takes the connection down: it happens
+// on the main thread, which ends the app, or in a task driving the port.
+// Panics of other tasks and threads, e.g. exports, leave the port running.
+fn panic_ends_connection() -> bool {
+    std::thread::current().name() == Some("main") || CONNECTION_TASK.try_with(|_| ()).is_ok()
+}
+
+/// Installs a panic hook that sends the safe-state sequence before unwinding,
+/// for panics that take the connection down.
+///
+/// The writer task keeps running on the other runtime threads, so the hook
+/// only queues the frames and polls for completion without blocking on tokio.
+pub fn install_panic_hook(app: tauri::AppHandle) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        if !panic_ends_connection() {
+            return;
+        }
+
+        let state = app.state::<AppData>();
+        // The panicking thread may hold the lock; never wait for it here.
+        let (writer, config) = match state.0.try_lock() {
+            Ok(guard) => (
+                guard.connection.as_ref().map(|connection| connection.writer.clone()),
+                guard.safe_state_config.clone(),
+            ),
+            Err(TryLockError::Poisoned(poisoned)) => {
+                let guard = poisoned.into_inner();
+                (
+                    guard.connection.as_ref().map(|connection| connection.writer.clone()),
+                    guard.safe_state_config.clone(),
+                )
+            }
+            Err(TryLockError::WouldBlock) => return,
+        };
+        let writer = match writer {
+            Some(writer) if config.enabled => writer,
+            _ => return,
+        };
+
+        queue_frames(&writer, &config);
+        let mut done = match writer.drain_marker() {
+            Ok(done) => done,
+            Err(e) => {
+                println!("Safe state after panic not confirmed: {}", e);
+                return;
+            }
+        };
+        let deadline = Instant::now() + Duration::from_millis(config.timeout_ms);
+        loop {
+            match done.try_recv() {
+                Ok(()) => {
+                    println!("Safe state sent after panic.");
+                    return;
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    println!("Safe state after panic not confirmed: the writer stopped.");
+                    return;
+                }
+                Err(oneshot::error::TryRecvError::Empty) if Instant::now() >= deadline => {
+                    println!(
+                        "Safe state after panic not confirmed within {} ms.",
+                        config.timeout_ms
+                    );
+                    return;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => std::thread::sleep(Duration::from_millis(5)),
+            }
+        }
+    }));
+}
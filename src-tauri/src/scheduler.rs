@@ -0,0 +1,628 @@
+// src/scheduler.rs
+
+// Unattended recordings. A schedule is a one-off or daily window; shortly
+// before a window opens the configured port is connected, the recording runs
+// from the start to the end of the window, and the outcome of every run is
+// logged and kept with the schedule. Schedules are saved in the application's
+// config folder and survive restarts; a restart during a window resumes it.
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, SecondsFormat, TimeZone}; // Crate for date and time handling.
+use std::path::PathBuf; // Struct for handling filesystem paths.
+use std::sync::Mutex; // Mutex for the schedule list.
+use std::time::Duration; // Struct for the scheduler interval.
+use tauri::Manager; // Tauri Manager for state and events.
+
+use crate::serial_wrapper::backend_log;
+use crate::AppData;
+
+// Name of the file holding the schedules, in the app config folder.
+const SCHEDULES_FILE: &str = "schedules.json";
+// Runs kept in the history of a schedule.
+const MAX_RUNS: usize = 20;
+// Time between two checks of the schedules.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When a schedule records.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Window {
+    /// A single window between two RFC 3339 times.
+    Once { start: String, end: String },
+    /// Every day, or on the given ISO weekdays (1 = Monday), between two times
+    /// of day such as "22:00"; an end before the start falls on the next day.
+    Daily {
+        start: String,
+        end: String,
+        #[serde(default)]
+        weekdays: Vec<u32>,
+    },
+}
+
+// Parses a time of day, "22:00" or "22:00:30".
+fn parse_time_of_day(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .map_err(|_| format!("{} is not a time of day.", text))
+}
+
+fn parse_rfc3339(text: &str) -> Result<DateTime<Local>, String> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|time| time.with_timezone(&Local))
+        .map_err(|_| format!("{} is not an RFC 3339 time.", text))
+}
+
+// Local time of `time` on `date`, if it exists (not inside a DST gap).
+fn on_date(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+impl Window {
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Window::Once { start, end } => {
+                if parse_rfc3339(end)? <= parse_rfc3339(start)? {
+                    return Err("The window ends before it starts.".to_string());
+                }
+            }
+            Window::Daily { start, end, weekdays } => {
+                if parse_time_of_day(start)? == parse_time_of_day(end)? {
+                    return Err("The window starts and ends at the same time.".to_string());
+                }
+                if weekdays.iter().any(|day| !(1..=7).contains(day)) {
+                    return Err("Weekdays go from 1 (Monday) to 7 (Sunday).".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The occurrence open at `now`, or else the next one.
+    pub fn next(&self, now: DateTime<Local>) -> Option<(DateTime<Local>, DateTime<Local>)> {
+        match self {
+            Window::Once { start, end } => {
+                let (start, end) = (parse_rfc3339(start).ok()?, parse_rfc3339(end).ok()?);
+                (end > now).then_some((start, end))
+            }
+            Window::Daily { start, end, weekdays } => {
+                let (start, end) = (parse_time_of_day(start).ok()?, parse_time_of_day(end).ok()?);
+                // Yesterday's window may still be open past midnight.
+                (-1..=7).find_map(|offset| {
+                    let date = now.date_naive() + chrono::Duration::days(offset);
+                    if !weekdays.is_empty() && !weekdays.contains(&date.weekday().number_from_monday()) {
+                        return None;
+                    }
+                    let end_date = if end <= start { date.succ_opt()? } else { date };
+                    let (opens, closes) = (on_date(date, start)?, on_date(end_date, end)?);
+                    (closes > now).then_some((opens, closes))
+                })
+            }
+        }
+    }
+}
+
+/// Outcome of a scheduled run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunOutcome {
+    Running,
+    /// Recorded until the end of the window.
+    Completed,
+    /// The recording or the port was stopped during the window.
+    Interrupted,
+    /// The application stopped during the window; the run resumes on restart.
+    Aborted,
+    /// The port could not be connected or the recording not started.
+    Failed,
+    /// Another recording was already running.
+    Skipped,
+    /// The window passed while the application was not running.
+    Missed,
+}
+
+/// One run of a schedule.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleRun {
+    /// Bounds of the window, RFC 3339.
+    pub window_start: String,
+    pub window_end: String,
+    pub started: Option<String>,
+    pub ended: Option<String>,
+    pub outcome: RunOutcome,
+    pub detail: Option<String>,
+}
+
+/// A scheduled recording.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    /// Assigned when the schedule is added.
+    #[serde(default)]
+    pub id: u64,
+    pub name: String,
+    pub enabled: bool,
+    pub window: Window,
+    /// Port and baud rate connected before the window; the configured ones when `None`.
+    pub port: Option<String>,
+    pub baud_rate: Option<u32>,
+    /// Recording folder; the configured one when `None`.
+    pub folder: Option<PathBuf>,
+    /// How long before the window the port is connected, in seconds.
+    #[serde(default = "default_connect_before")]
+    pub connect_before_secs: u64,
+    /// Close the port again after the window, if the scheduler opened it.
+    #[serde(default)]
+    pub disconnect_after: bool,
+    /// Latest runs, oldest first.
+    #[serde(default)]
+    pub runs: Vec<ScheduleRun>,
+}
+
+fn default_connect_before() -> u64 {
+    60
+}
+
+impl Schedule {
+    // Whether the run of the window starting at `start` already took place.
+    fn handled(&self, start: &str) -> bool {
+        self.runs
+            .iter()
+            .any(|run| run.window_start == start && run.outcome != RunOutcome::Aborted)
+    }
+}
+
+// Payload of the `scheduleRun` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunPayload {
+    schedule_id: u64,
+    name: String,
+    run: ScheduleRun,
+}
+
+// The window being recorded.
+#[derive(Clone)]
+struct ActiveRun {
+    schedule_id: u64,
+    end: DateTime<Local>,
+    // The scheduler opened the port for this run.
+    connected: bool,
+}
+
+/// Schedules of the application, shared by the commands and the scheduler task.
+#[derive(Default)]
+pub struct Scheduler {
+    file: Mutex<Option<PathBuf>>,
+    schedules: Mutex<Vec<Schedule>>,
+    active: Mutex<Option<ActiveRun>>,
+    // Window whose port was already prepared ahead of time, and whether the scheduler opened it.
+    prepared: Mutex<Option<(u64, String, bool)>>,
+}
+
+fn rfc3339(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+impl Scheduler {
+    /// Loads the saved schedules; runs cut short by the last exit are marked as aborted.
+    pub fn load(&self, app: &tauri::AppHandle) {
+        let file = match app.path_resolver().app_config_dir() {
+            Some(dir) => dir.join(SCHEDULES_FILE),
+            None => {
+                backend_log(app, "No config folder: schedules will not be saved.", "WARNING");
+                return;
+            }
+        };
+        let mut schedules: Vec<Schedule> = match std::fs::read(&file) {
+            Ok(json) => match serde_json::from_slice(&json) {
+                Ok(schedules) => schedules,
+                Err(e) => {
+                    backend_log(app, &format!("Could not read {}: {}", file.display(), e), "ERROR");
+                    Vec::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                backend_log(app, &format!("Could not read {}: {}", file.display(), e), "ERROR");
+                Vec::new()
+            }
+        };
+        for run in schedules.iter_mut().flat_map(|schedule| schedule.runs.iter_mut()) {
+            if run.outcome == RunOutcome::Running {
+                run.outcome = RunOutcome::Aborted;
+                run.detail = Some("The application stopped during the window.".to_string());
+            }
+        }
+        if !schedules.is_empty() {
+            backend_log(app, &format!("{} recording schedule(s) loaded.", schedules.len()), "INFO");
+        }
+        *self.schedules.lock().unwrap() = schedules;
+        *self.file.lock().unwrap() = Some(file);
+    }
+
+    // Writes the schedules to their file, through a temporary file.
+    fn save(&self, app: &tauri::AppHandle) {
+        let file = match self.file.lock().unwrap().clone() {
+            Some(file) => file,
+            None => return,
+        };
+        let json = serde_json::to_vec_pretty(&*self.schedules.lock().unwrap()).unwrap_or_default();
+        let temporary = file.with_extension("json.tmp");
+        let result = file
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&temporary, json))
+            .and_then(|_| std::fs::rename(&temporary, &file));
+        if let Err(e) = result {
+            backend_log(app, &format!("Could not save the schedules to {}: {}", file.display(), e), "ERROR");
+        }
+    }
+
+    pub fn list(&self) -> Vec<Schedule> {
+        self.schedules.lock().unwrap().clone()
+    }
+
+    /// Adds a schedule and returns it with its ID.
+    pub fn add(&self, app: &tauri::AppHandle, mut schedule: Schedule) -> Result<Schedule, String> {
+        schedule.window.validate()?;
+        {
+            let mut schedules = self.schedules.lock().unwrap();
+            schedule.id = schedules.iter().map(|existing| existing.id).max().unwrap_or(0) + 1;
+            schedule.runs.clear();
+            schedules.push(schedule.clone());
+        }
+        self.save(app);
+        Ok(schedule)
+    }
+
+    /// Replaces a schedule; its run history is kept.
+    pub fn update(&self, app: &tauri::AppHandle, mut schedule: Schedule) -> Result<Schedule, String> {
+        schedule.window.validate()?;
+        {
+            let mut schedules = self.schedules.lock().unwrap();
+            let existing = schedules
+                .iter_mut()
+                .find(|existing| existing.id == schedule.id)
+                .ok_or_else(|| format!("There is no schedule {}.", schedule.id))?;
+            schedule.runs = std::mem::take(&mut existing.runs);
+            *existing = schedule.clone();
+        }
+        self.save(app);
+        Ok(schedule)
+    }
+
+    /// Removes a schedule; a run in progress ends at the next check.
+    pub fn remove(&self, app: &tauri::AppHandle, id: u64) -> Result<(), String> {
+        {
+            let mut schedules = self.schedules.lock().unwrap();
+            let count = schedules.len();
+            schedules.retain(|schedule| schedule.id != id);
+            if schedules.len() == count {
+                return Err(format!("There is no schedule {}.", id));
+            }
+        }
+        self.save(app);
+        Ok(())
+    }
+
+    // Records the outcome of a run: replaces the running entry of the window, or adds one.
+    fn record(&self, app: &tauri::AppHandle, schedule_id: u64, run: ScheduleRun) {
+        let name = {
+            let mut schedules = self.schedules.lock().unwrap();
+            let schedule = match schedules.iter_mut().find(|schedule| schedule.id == schedule_id) {
+                Some(schedule) => schedule,
+                None => return,
+            };
+            match schedule.runs.last_mut() {
+                Some(last) if last.window_start == run.window_start && last.outcome == RunOutcome::Running => {
+                    *last = run.clone();
+                }
+                _ => schedule.runs.push(run.clone()),
+            }
+            let excess = schedule.runs.len().saturating_sub(MAX_RUNS);
+            schedule.runs.drain(..excess);
+            // A one-off schedule is done once its window has been handled.
+            if matches!(schedule.window, Window::Once { .. }) && run.outcome != RunOutcome::Running {
+                schedule.enabled = false;
+            }
+            schedule.name.clone()
+        };
+        self.save(app);
+
+        let (level, what) = match run.outcome {
+            RunOutcome::Running => ("SUCCESS", "started"),
+            RunOutcome::Completed => ("SUCCESS", "completed"),
+            RunOutcome::Interrupted => ("WARNING", "interrupted"),
+            RunOutcome::Aborted => ("WARNING", "aborted"),
+            RunOutcome::Failed => ("ERROR", "failed"),
+            RunOutcome::Skipped => ("WARNING", "skipped"),
+            RunOutcome::Missed => ("WARNING", "missed"),
+        };
+        let detail = run.detail.as_deref().map(|detail| format!(": {}", detail)).unwrap_or_default();
+        backend_log(
+            app,
+            &format!(
+                "Scheduled recording '{}' ({} to {}) {}{}",
+                name, run.window_start, run.window_end, what, detail
+            ),
+            level,
+        );
+        let payload = RunPayload {
+            schedule_id,
+            name,
+            run,
+        };
+        if let Err(e) = app.emit_all("scheduleRun", payload) {
+            println!("Failed to emit schedule run to the frontend: {:?}", e);
+        }
+    }
+
+    // Ends the active run when its window closed, or when it was stopped or removed.
+    async fn check_active(&self, app: &tauri::AppHandle, active: ActiveRun, now: DateTime<Local>) {
+        let schedule = self.list().into_iter().find(|schedule| schedule.id == active.schedule_id);
+        let running = match schedule.as_ref().and_then(|schedule| schedule.runs.last().cloned()) {
+            Some(running) => running,
+            None => {
+                // The schedule was removed during its window.
+                *self.active.lock().unwrap() = None;
+                crate::stop_recording(app).await;
+                return;
+            }
+        };
+        let is_recording = app.state::<AppData>().0.lock().unwrap().is_recording;
+        let outcome = if !is_recording {
+            Some((RunOutcome::Interrupted, Some("The recording stopped before the end of the window.".to_string())))
+        } else if !schedule.is_some_and(|schedule| schedule.enabled) {
+            Some((RunOutcome::Interrupted, Some("The schedule was disabled.".to_string())))
+        } else if now >= active.end {
+            Some((RunOutcome::Completed, None))
+        } else {
+            None
+        };
+        let (outcome, detail) = match outcome {
+            Some(outcome) => outcome,
+            None => return,
+        };
+
+        *self.active.lock().unwrap() = None;
+        crate::stop_recording(app).await;
+        let disconnect_after = self
+            .list()
+            .iter()
+            .any(|schedule| schedule.id == active.schedule_id && schedule.disconnect_after);
+        if active.connected && disconnect_after {
+            crate::disconnect_port(app).await;
+        }
+        self.record(
+            app,
+            active.schedule_id,
+            ScheduleRun {
+                ended: Some(rfc3339(now)),
+                outcome,
+                detail,
+                ..running
+            },
+        );
+    }
+
+    // Connects the port of a schedule unless one is open. Returns whether it connected it.
+    //
+    // The schedule's port and baud rate apply to this connection only; the
+    // configured ones stay as the operator set them.
+    async fn connect(&self, app: &tauri::AppHandle, schedule: &Schedule) -> Result<bool, String> {
+        let (port_path, baud_rate) = {
+            let state = app.state::<AppData>();
+            let state_guard = state.0.lock().unwrap();
+            if state_guard.connection.is_some() {
+                return Ok(false);
+            }
+            (
+                schedule.port.clone().unwrap_or_else(|| state_guard.port_items.port_path.clone()),
+                schedule.baud_rate.unwrap_or(state_guard.port_items.baud_rate),
+            )
+        };
+        crate::connect_port(app, port_path, baud_rate).await.map(|_| true)
+    }
+
+    // Prepares or starts the window of a schedule that is due.
+    async fn check_schedule(&self, app: &tauri::AppHandle, schedule: Schedule, now: DateTime<Local>) -> bool {
+        let (start, end) = match schedule.window.next(now) {
+            Some(window) => window,
+            None => {
+                // A one-off window that passed while the application was not running.
+                if let Window::Once { start, end } = &schedule.window {
+                    self.record(
+                        app,
+                        schedule.id,
+                        ScheduleRun {
+                            window_start: start.clone(),
+                            window_end: end.clone(),
+                            started: None,
+                            ended: None,
+                            outcome: RunOutcome::Missed,
+                            detail: None,
+                        },
+                    );
+                }
+                return false;
+            }
+        };
+        let window_start = rfc3339(start);
+        if schedule.handled(&window_start) {
+            return false;
+        }
+        let prepare_at = start - chrono::Duration::seconds(schedule.connect_before_secs as i64);
+        if now < prepare_at {
+            return false;
+        }
+        let mut run = ScheduleRun {
+            window_start: window_start.clone(),
+            window_end: rfc3339(end),
+            started: None,
+            ended: None,
+            outcome: RunOutcome::Running,
+            detail: None,
+        };
+
+        // Before the window only connect, once.
+        let prepared = self
+            .prepared
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|(id, prepared_start, _)| *id == schedule.id && *prepared_start == window_start);
+        if now < start {
+            if prepared.is_none() {
+                let connected = match self.connect(app, &schedule).await {
+                    Ok(connected) => connected,
+                    Err(e) => {
+                        backend_log(
+                            app,
+                            &format!(
+                                "Could not connect for scheduled recording '{}', retrying at its start: {}",
+                                schedule.name, e
+                            ),
+                            "WARNING",
+                        );
+                        false
+                    }
+                };
+                *self.prepared.lock().unwrap() = Some((schedule.id, window_start, connected));
+            }
+            return true;
+        }
+        *self.prepared.lock().unwrap() = None;
+
+        let connected = match self.connect(app, &schedule).await {
+            Ok(connected) => connected,
+            Err(e) => {
+                run.outcome = RunOutcome::Failed;
+                run.detail = Some(e);
+                self.record(app, schedule.id, run);
+                return true;
+            }
+        };
+        let connected = connected || prepared.is_some_and(|(_, _, connected)| connected);
+        if app.state::<AppData>().0.lock().unwrap().is_recording {
+            run.outcome = RunOutcome::Skipped;
+            run.detail = Some("Another recording was already running.".to_string());
+            self.record(app, schedule.id, run);
+            return true;
+        }
+        match crate::start_recording(app, schedule.folder.clone()).await {
+            Ok(()) => {
+                run.started = Some(rfc3339(Local::now()));
+                *self.active.lock().unwrap() = Some(ActiveRun {
+                    schedule_id: schedule.id,
+                    end,
+                    connected,
+                });
+            }
+            Err((_, e)) => {
+                run.outcome = RunOutcome::Failed;
+                run.detail = Some(e);
+            }
+        }
+        self.record(app, schedule.id, run);
+        true
+    }
+
+    // Checks the active run, or else the enabled schedules in turn; one window records at a time.
+    async fn tick(&self, app: &tauri::AppHandle) {
+        let now = Local::now();
+        let active = self.active.lock().unwrap().clone();
+        if let Some(active) = active {
+            self.check_active(app, active, now).await;
+            return;
+        }
+        for schedule in self.list().into_iter().filter(|schedule| schedule.enabled) {
+            if self.check_schedule(app, schedule, now).await {
+                break;
+            }
+        }
+    }
+}
+
+/// Starts the task checking the schedules every second.
+pub fn start_scheduler_task(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            app.state::<Scheduler>().tick(&app).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn daily(start: &str, end: &str, weekdays: Vec<u32>) -> Window {
+        Window::Daily {
+            start: start.to_string(),
+            end: end.to_string(),
+            weekdays,
+        }
+    }
+
+    // 2024-06-14 is a Friday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 6, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn overnight_window_open_before_midnight() {
+        let window = daily("22:00", "06:00", vec![]);
+        assert_eq!(window.next(at(14, 23, 0)), Some((at(14, 22, 0), at(15, 6, 0))));
+    }
+
+    #[test]
+    fn overnight_window_still_open_after_midnight() {
+        let window = daily("22:00", "06:00", vec![]);
+        assert_eq!(window.next(at(15, 2, 0)), Some((at(14, 22, 0), at(15, 6, 0))));
+        // At the end the window is closed and the next one follows.
+        assert_eq!(window.next(at(15, 6, 0)), Some((at(15, 22, 0), at(16, 6, 0))));
+    }
+
+    #[test]
+    fn overnight_window_between_occurrences() {
+        let window = daily("22:00", "06:00", vec![]);
+        assert_eq!(window.next(at(15, 12, 0)), Some((at(15, 22, 0), at(16, 6, 0))));
+    }
+
+    #[test]
+    fn overnight_window_on_weekdays_belongs_to_its_start_day() {
+        // Fridays only: the window opened on Friday is still open on Saturday morning.
+        let window = daily("22:00", "06:00", vec![5]);
+        assert_eq!(window.next(at(15, 3, 0)), Some((at(14, 22, 0), at(15, 6, 0))));
+        assert_eq!(window.next(at(15, 7, 0)), Some((at(21, 22, 0), at(22, 6, 0))));
+    }
+
+    #[test]
+    fn same_day_window() {
+        let window = daily("09:00", "17:00", vec![]);
+        assert_eq!(window.next(at(14, 8, 0)), Some((at(14, 9, 0), at(14, 17, 0))));
+        assert_eq!(window.next(at(14, 12, 0)), Some((at(14, 9, 0), at(14, 17, 0))));
+        assert_eq!(window.next(at(14, 18, 0)), Some((at(15, 9, 0), at(15, 17, 0))));
+    }
+
+    #[test]
+    fn once_window_ends() {
+        let window = Window::Once {
+            start: at(14, 22, 0).to_rfc3339(),
+            end: at(15, 6, 0).to_rfc3339(),
+        };
+        assert_eq!(window.next(at(14, 12, 0)), Some((at(14, 22, 0), at(15, 6, 0))));
+        assert_eq!(window.next(at(15, 6, 0)), None);
+    }
+
+    #[test]
+    fn invalid_windows() {
+        assert!(daily("22:00", "22:00", vec![]).validate().is_err());
+        assert!(daily("22:00", "06:00", vec![0]).validate().is_err());
+        assert!(daily("25:00", "06:00", vec![]).validate().is_err());
+        assert!(daily("22:00", "06:00", vec![1, 7]).validate().is_ok());
+    }
+}
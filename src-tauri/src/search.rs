@@ -0,0 +1,281 @@
+// src/search.rs
+
+// Search through a recording for frames matching a command, hardware ID, value
+// range and time window. The file is decoded as a stream and matches are sent
+// to the frontend in batches, so a long recording is never held in memory:
+//
+//   recordingSearchResults  { searchId, hits: [...] }   zero or more times
+//   recordingSearchDone     { searchId, matches, ... }  once, at the end
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local, NaiveTime, TimeZone}; // Crate for date and time handling.
+use std::collections::HashMap; // Map of the running searches.
+use std::io::ErrorKind; // Error kind used to end a search early.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering}; // Flags shared with the search threads.
+use std::sync::{Arc, Mutex}; // Shared ownership of the cancel flags.
+use std::time::{Duration, Instant}; // Structs for batching the results.
+use tauri::Manager; // Tauri Manager for emitting events.
+
+use crate::convert::{self, Decoded};
+use crate::protocol;
+use crate::recording::{self, Direction};
+
+// Hits sent in one `recordingSearchResults` event at most, and the longest
+// time a hit waits before it is sent.
+const BATCH_HITS: usize = 500;
+const BATCH_INTERVAL: Duration = Duration::from_millis(250);
+// Hits returned when the query sets no limit.
+const DEFAULT_MAX_RESULTS: u64 = 100_000;
+
+/// Criteria of a search. Every criterion is optional; empty lists match anything.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchQuery {
+    pub command_ids: Vec<u8>,
+    pub hardware_ids: Vec<u8>,
+    pub direction: Option<Direction>,
+    pub min_value: Option<u32>,
+    pub max_value: Option<u32>,
+    /// Window in microseconds since the session start.
+    pub from_us: Option<u64>,
+    pub to_us: Option<u64>,
+    /// Wall-clock window: RFC 3339, or a time of day such as "14:02" on the
+    /// day the recording started.
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub max_results: Option<u64>,
+}
+
+/// A matching frame.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    /// Byte offset of the frame, in the decompressed text for structured recordings.
+    pub offset: u64,
+    /// Line number (from 1), for `read_recording_lines`.
+    pub line: Option<u64>,
+    pub t_us: Option<u64>,
+    /// Wall-clock time, RFC 3339.
+    pub time: Option<String>,
+    pub direction: Option<Direction>,
+    pub command: &'static str,
+    pub command_id: u8,
+    pub hardware_id: u8,
+    pub value: u32,
+    pub unit: &'static str,
+}
+
+// Payload of the `recordingSearchResults` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResults {
+    search_id: u64,
+    hits: Vec<SearchHit>,
+}
+
+/// Payload of the `recordingSearchDone` event.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchSummary {
+    pub search_id: u64,
+    pub path: String,
+    pub matches: u64,
+    pub frames_scanned: u64,
+    /// True when `max_results` was reached before the end of the file.
+    pub truncated: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
+}
+
+/// Searches that are running, so they can be cancelled.
+#[derive(Default)]
+pub struct Searches {
+    next_id: AtomicU64,
+    running: Mutex<HashMap<u64, Arc<AtomicBool>>>,
+}
+
+impl Searches {
+    /// Requests a running search to stop; returns false if it is not running.
+    pub fn cancel(&self, search_id: u64) -> bool {
+        match self.running.lock().unwrap().get(&search_id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Parses a wall-clock bound of the query.
+fn parse_time(text: &str, started: Option<DateTime<Local>>) -> Result<DateTime<Local>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Local));
+    }
+    let time_of_day = NaiveTime::parse_from_str(text, "%H:%M:%S%.f")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .map_err(|_| format!("{} is neither an RFC 3339 time nor a time of day.", text))?;
+    let started = started.ok_or_else(|| format!("The recording has no start time to place {} on.", text))?;
+    Local
+        .from_local_datetime(&started.date_naive().and_time(time_of_day))
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist on {}.", text, started.date_naive()))
+}
+
+// Time window of the query in microseconds since the session start.
+fn window(query: &SearchQuery, started: Option<DateTime<Local>>) -> Result<(Option<u64>, Option<u64>), String> {
+    let relative = |text: &Option<String>| -> Result<Option<u64>, String> {
+        match text {
+            Some(text) => {
+                let time = parse_time(text, started)?;
+                let started = started.ok_or_else(|| "The recording has no timestamps.".to_string())?;
+                Ok(Some((time - started).num_microseconds().unwrap_or(0).max(0) as u64))
+            }
+            None => Ok(None),
+        }
+    };
+    let from = [query.from_us, relative(&query.from)?].into_iter().flatten().max();
+    let to = [query.to_us, relative(&query.to)?].into_iter().flatten().min();
+    Ok((from, to))
+}
+
+// Whether a frame matches the criteria that do not depend on time.
+fn matches(query: &SearchQuery, frame: &protocol::Frame, direction: Option<Direction>) -> bool {
+    (query.command_ids.is_empty() || query.command_ids.contains(&frame.command_id))
+        && (query.hardware_ids.is_empty() || query.hardware_ids.contains(&frame.hardware_id))
+        && !matches!(query.direction, Some(wanted) if direction != Some(wanted))
+        && !matches!(query.min_value, Some(min) if frame.value < min)
+        && !matches!(query.max_value, Some(max) if frame.value > max)
+}
+
+/// Runs a search, calling `on_hits` with every batch of hits. Blocking.
+pub fn run(
+    search_id: u64,
+    path: &Path,
+    query: &SearchQuery,
+    cancelled: &AtomicBool,
+    mut on_hits: impl FnMut(Vec<SearchHit>),
+) -> SearchSummary {
+    let mut summary = SearchSummary {
+        search_id,
+        path: path.to_string_lossy().to_string(),
+        matches: 0,
+        frames_scanned: 0,
+        truncated: false,
+        cancelled: false,
+        error: None,
+    };
+
+    let structured = match convert::is_structured(path) {
+        Ok(structured) => structured,
+        Err(e) => {
+            summary.error = Some(format!("Could not open {}: {}", path.display(), e));
+            return summary;
+        }
+    };
+    let started = if structured { recording::read_started(path).ok().flatten() } else { None };
+    let timed = query.from_us.is_some() || query.to_us.is_some() || query.from.is_some() || query.to.is_some();
+    if timed && !structured {
+        summary.error = Some("Raw recordings carry no timestamps; search them without a time window.".to_string());
+        return summary;
+    }
+    let (from_us, to_us) = match window(query, started) {
+        Ok(window) => window,
+        Err(e) => {
+            summary.error = Some(e);
+            return summary;
+        }
+    };
+    let max_results = query.max_results.unwrap_or(DEFAULT_MAX_RESULTS);
+
+    let mut batch = Vec::new();
+    let mut last_sent = Instant::now();
+    // Ends the decoding early; not reported as an error.
+    let stop = |reason: &str| std::io::Error::new(ErrorKind::Interrupted, reason.to_string());
+    let result = convert::decode(path, |item| {
+        let (offset, line, t_us, direction, frame) = match item {
+            Decoded::Frame {
+                offset,
+                line,
+                t_us,
+                direction,
+                frame,
+            } => (offset, line, t_us, direction, frame),
+            _ => return Ok(()),
+        };
+        summary.frames_scanned += 1;
+        if cancelled.load(Ordering::Relaxed) {
+            summary.cancelled = true;
+            return Err(stop("cancelled"));
+        }
+        // Timestamps only grow, so nothing after the window can match.
+        if let (Some(t_us), Some(to_us)) = (t_us, to_us) {
+            if t_us > to_us {
+                return Err(stop("end of window"));
+            }
+        }
+        let in_window = match (from_us, t_us) {
+            (Some(from_us), Some(t_us)) => t_us >= from_us,
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if in_window && matches(query, &frame, direction) {
+            if summary.matches == max_results {
+                summary.truncated = true;
+                return Err(stop("max results"));
+            }
+            summary.matches += 1;
+            batch.push(SearchHit {
+                offset,
+                line,
+                t_us,
+                time: started.zip(t_us).map(|(started, t_us)| recording::wall_time(started, t_us)),
+                direction,
+                command: frame.command_name(),
+                command_id: frame.command_id,
+                hardware_id: frame.hardware_id,
+                value: frame.value,
+                unit: convert::unit(frame.command_id),
+            });
+        }
+        if batch.len() >= BATCH_HITS || (!batch.is_empty() && last_sent.elapsed() >= BATCH_INTERVAL) {
+            on_hits(std::mem::take(&mut batch));
+            last_sent = Instant::now();
+        }
+        Ok(())
+    });
+    if !batch.is_empty() {
+        on_hits(batch);
+    }
+
+    match result {
+        Err(e) if e.kind() != ErrorKind::Interrupted => {
+            summary.error = Some(format!("Search in {} failed: {}", path.display(), e));
+        }
+        _ => {}
+    }
+    summary
+}
+
+/// Starts a search on a blocking thread and returns its ID right away.
+///
+/// The hits and the summary arrive as events.
+pub fn start(app: tauri::AppHandle, searches: &Searches, path: PathBuf, query: SearchQuery) -> u64 {
+    let search_id = searches.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    searches.running.lock().unwrap().insert(search_id, cancelled.clone());
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let summary = run(search_id, &path, &query, &cancelled, |hits| {
+            if let Err(e) = app.emit_all("recordingSearchResults", SearchResults { search_id, hits }) {
+                println!("Failed to emit search results to the frontend: {:?}", e);
+            }
+        });
+        app.state::<Searches>().running.lock().unwrap().remove(&search_id);
+        if let Err(e) = app.emit_all("recordingSearchDone", summary) {
+            println!("Failed to emit search summary to the frontend: {:?}", e);
+        }
+    });
+    search_id
+}
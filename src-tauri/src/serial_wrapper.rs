@@ -1,27 +1,33 @@
 // src/serial_wrapper.rs
 
 // Importing necessary crates and modules.
-use serialport::*; // Serial port operations.
-use std::io::Write; // Trait for writing to streams.
-use std::sync::{
-    atomic::{AtomicBool, Ordering}, // AtomicBool for thread-safe boolean flags.
-    Arc, // Arc for shared ownership across threads.
-};
-use std::time::Duration; // Struct for handling time durations.
-use std::{io, thread}; // IO and threading modules.
 use tauri::Manager; // Tauri Manager for emitting events.
-use std::fs::File; // Struct for file operations.
-use std::time::SystemTime; // Struct for handling system time.
-use chrono::Local; // Crate para lidar com data/hora locais.
-use std::path::PathBuf; // Struct for handling filesystem paths.
-
-use std::collections::HashMap;
-use std::sync::Mutex;
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use std::time::Instant; // Struct for monotonic timestamps.
+use tokio::io::{AsyncReadExt, ReadHalf}; // Async read trait and the read half of a split stream.
+use tokio_serial::{SerialPortBuilderExt, SerialStream}; // Async serial stream on the tokio reactor.
+use tokio_util::sync::CancellationToken; // Token used to stop the serial tasks.
+
+use crate::barriers::BarrierMonitor;
+use crate::database::{self, DatabaseTask};
+use crate::heartbeat::{LinkMonitor, LossAction};
+use crate::metrics::MetricsTask;
+use crate::pipeline::{SinkEvent, SinkRegistry};
+use crate::port_lock;
+use crate::protocol::{DecodeEvent, FrameDecoder};
+use crate::recorder::RecordingTask;
+use crate::recording::Direction;
+use crate::safe_state;
+use crate::split::SplitTask;
+use crate::stats::StatsMonitor;
+use crate::trigger::TriggerTask;
+use crate::writer::WriterHandle;
+use std::sync::Arc; // Arc for shared ownership across tasks.
 
 // Struct for binary payload data to be sent to the frontend.
 #[derive(Clone, serde::Serialize)]
-struct BinaryPayload {
-    data: Vec<u8>,
+pub struct BinaryPayload {
+    pub data: Vec<u8>,
 }
 
 // Struct for simple message payloads.
@@ -40,6 +46,8 @@ pub fn backend_log(app: &tauri::AppHandle, message: &str, level: &str) {
     let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let detailed_message = format!("[{}] [{}] {}", timestamp, level, message);
     println!("{}", detailed_message);
+    // Keep the entry in the database of a running live export.
+    database::log_entry(app, level, message);
 
     // Emite apenas a mensagem e tipo para o frontend, sem timestamp
     let payload = serde_json::json!({
@@ -84,12 +92,95 @@ pub fn list_ports(app: tauri::AppHandle) -> Vec<String> {
     }
 }
 
+/// Read half of the serial stream, owned by the reader task.
+pub type SerialReader = ReadHalf<SerialStream>;
+
+/// Tasks using the port itself. A reconnect replaces them; the sinks stay.
+pub struct PortTasks {
+    /// Child of the connection's token.
+    pub cancel: CancellationToken,
+    pub reader_task: JoinHandle<()>,
+    pub writer_task: JoinHandle<()>,
+    pub heartbeat_task: Option<JoinHandle<()>>,
+}
+
+impl PortTasks {
+    /// Cancels the tasks and waits until they released the port.
+    pub async fn close(self) {
+        self.cancel.cancel();
+        let _ = self.reader_task.await;
+        let _ = self.writer_task.await;
+        if let Some(heartbeat_task) = self.heartbeat_task {
+            let _ = heartbeat_task.await;
+        }
+    }
+}
+
+/// Tasks and handles belonging to an open serial connection.
+///
+/// Cancelling `cancel` stops every task of the connection. The sinks outlive a
+/// reconnect of the port; recordings only add and remove a sink.
+pub struct SerialConnection {
+    /// Port and baud rate the connection was opened with.
+    pub port_path: String,
+    pub baud_rate: u32,
+    pub cancel: CancellationToken,
+    /// `None` while a reconnect reopens the port.
+    pub port: Option<PortTasks>,
+    pub writer: WriterHandle,
+    pub sinks: Arc<SinkRegistry>,
+    pub link: Arc<LinkMonitor>,
+    pub barriers: Arc<BarrierMonitor>,
+    pub barrier_task: JoinHandle<()>,
+    pub stats: Arc<StatsMonitor>,
+    pub stats_task: JoinHandle<()>,
+    pub recording: Option<RecordingTask>,
+    pub channels: Option<SplitTask>,
+    pub trigger: Option<TriggerTask>,
+    pub database: Option<DatabaseTask>,
+    pub metrics: Option<MetricsTask>,
+}
+
+impl SerialConnection {
+    /// Cancels all tasks of the connection and waits until they released the port.
+    ///
+    /// A running recording writes the remaining events and closes its file first.
+    pub async fn close(self) {
+        self.cancel.cancel();
+        if let Some(port) = self.port {
+            port.close().await;
+        }
+        let _ = self.barrier_task.await;
+        let _ = self.stats_task.await;
+        if let Some(recording) = self.recording {
+            recording.stop().await;
+        }
+        if let Some(channels) = self.channels {
+            channels.stop(&self.sinks).await;
+        }
+        if let Some(trigger) = self.trigger {
+            trigger.stop(&self.sinks).await;
+        }
+        if let Some(database) = self.database {
+            database.stop(&self.sinks).await;
+        }
+        if let Some(metrics) = self.metrics {
+            metrics.stop(&self.sinks).await;
+        }
+    }
+}
+
 /// Function to initialize a serial port with the given path and baud rate.
+///
+/// The port is opened exclusively; errors are returned as a human readable
+/// diagnostic naming the process holding the port or the missing permission.
+/// Must be called from within the Tauri async runtime, since the stream is
+/// registered with the tokio reactor.
 pub fn init_port(
     app: tauri::AppHandle,
     port_path: String,
     baud_rate: u32,
-) -> Result<Box<dyn SerialPort>> {
+) -> Result<SerialStream, String> {
     println!(
         "[{}] [INFO] Opening port: {}, baud: {}",
         chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
@@ -97,24 +188,38 @@ pub fn init_port(
         baud_rate
     );
 
-    let port_path_clone = port_path.clone();
-    let port = serialport::new(port_path, baud_rate)
-        .timeout(Duration::from_secs(5))
-        .open();
+    // Refuse to share the device: a second reader would see interleaved garbage.
+    let holders = port_lock::find_port_holders(&port_path);
+    let port = if holders.is_empty() {
+        tokio_serial::new(port_path.clone(), baud_rate)
+            .open_native_async()
+            .map_err(|e| {
+                println!(
+                    "[{}] [ERROR] Failed to open port '{}': {:?}",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    port_path,
+                    e
+                );
+                port_lock::describe_open_error(&port_path, &e)
+            })
+            .and_then(|mut p| port_lock::lock_exclusive(&mut p).map(|_| p))
+    } else {
+        Err(port_lock::describe_busy(&port_path, &holders))
+    };
 
     match port {
         Ok(p) => {
             println!(
                 "[{}] [SUCCESS] Port '{}' opened successfully with baud rate {}",
                 chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                port_path_clone,
+                port_path,
                 baud_rate
             );
 
             // Emitting success to the frontend
             if let Err(e) = app.emit_all("backendLog", {
                 serde_json::json!({
-                    "message": format!("Port '{}' opened successfully with baud rate {}", port_path_clone, baud_rate),
+                    "message": format!("Port '{}' opened exclusively with baud rate {}", port_path, baud_rate),
                     "type": "SUCCESS",
                 })
             }) {
@@ -127,18 +232,11 @@ pub fn init_port(
 
             Ok(p)
         }
-        Err(e) => {
-            println!(
-                "[{}] [ERROR] Failed to open port '{}': {:?}",
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                port_path_clone,
-                e
-            );
-
+        Err(msg) => {
             // Emitting error to the frontend
             if let Err(err) = app.emit_all("backendLog", {
                 serde_json::json!({
-                    "message": format!("Failed to open port '{}': {:?}", port_path_clone, e),
+                    "message": msg,
                     "type": "error",
                 })
             }) {
@@ -149,50 +247,65 @@ pub fn init_port(
                 );
             }
 
-            Err(e)
+            Err(msg)
         }
     }
 }
 
-/// Function to start a thread that continuously reads from the serial port.
-/// Modificação na thread para processar mensagens completas
-/// Function to start a thread that continuously reads from the serial port.
-pub fn start_clone_thread(
+/// Function to start a task that continuously reads from the serial port.
+///
+/// Every decoded frame is emitted on `updateSerial` and, together with bytes
+/// discarded while resynchronizing, published to the registered sinks.
+pub fn start_read_task(
     app: tauri::AppHandle,
-    mut port_clone: Box<dyn SerialPort>,
-    is_thread_open: Arc<AtomicBool>,
-) {
-    let mut serial_buf: Vec<u8> = Vec::new();
-
-    thread::spawn(move || {
-        is_thread_open.store(true, Ordering::Relaxed);
-        backend_log(&app, "Serial communication thread started.", "INFO");
-
-        while is_thread_open.load(Ordering::Relaxed) {
-            let mut byte = [0u8; 1];
-            match port_clone.read(&mut byte) {
-                Ok(_) => {
-                    // Log cada byte recebido
-                    backend_log(&app, &format!("Received 1 byte: {:?}", byte[0]), "INFO");
-
-                    // Adiciona byte ao buffer
-                    serial_buf.push(byte[0]);
-
-                    // Verifica se o buffer contém 7 bytes (mensagem completa)
-                    if serial_buf.len() == 7 {
-                        let command_id = serial_buf[0];
-                        let hardware_id = serial_buf[1];
-                        let value = (serial_buf[2] as u32)
-                            | ((serial_buf[3] as u32) << 8)
-                            | ((serial_buf[4] as u32) << 16)
-                            | ((serial_buf[5] as u32) << 24);
-                    
+    mut reader: SerialReader,
+    cancel: CancellationToken,
+    link: Arc<LinkMonitor>,
+    stats: Arc<StatsMonitor>,
+    sinks: Arc<SinkRegistry>,
+) -> JoinHandle<()> {
+    let mut decoder = FrameDecoder::new();
+
+    tauri::async_runtime::spawn(safe_state::connection_task(async move {
+        backend_log(&app, "Serial communication task started.", "INFO");
+        let mut chunk = [0u8; 64];
+
+        loop {
+            let size = tokio::select! {
+                _ = cancel.cancelled() => break,
+                result = reader.read(&mut chunk) => match result {
+                    Ok(0) => {
+                        backend_log(&app, "Serial port closed by the device.", "error");
+                        port_lost(&app);
+                        break;
+                    }
+                    Ok(size) => size,
+                    Err(e) => {
+                        backend_log(&app, &format!("Error reading from serial port: {:?}", e), "error");
+                        port_lost(&app);
+                        break;
+                    }
+                },
+            };
+            let received_at = Instant::now();
+            stats.on_read(size);
+
+            // Log os bytes recebidos
+            backend_log(&app, &format!("Received {} bytes: {:?}", size, &chunk[..size]), "INFO");
+
+            for event in decoder.feed(&chunk[..size]) {
+                sinks.publish(SinkEvent::received(received_at, &event));
+                match event {
+                    DecodeEvent::Frame(frame, raw) => {
+                        link.on_frame(&frame);
+                        stats.on_frame(Direction::Rx, &frame);
+
                         backend_log(
                             &app,
                             &format!(
                                 "Received complete message: {:?} (Hex: {})",
-                                serial_buf,
-                                serial_buf.iter()
+                                raw,
+                                raw.iter()
                                     .map(|b| format!("{:02X}", b))
                                     .collect::<Vec<_>>()
                                     .join(" ")
@@ -203,118 +316,71 @@ pub fn start_clone_thread(
                         backend_log(
                             &app,
                             &format!(
-                                "Parsed Message -> COMMAND_ID: {}, HARDWARE_ID: {}, VALUE: {}",
-                                command_id, hardware_id, value
+                                "Parsed Message -> COMMAND_ID: {} ({}), HARDWARE_ID: {}, VALUE: {}",
+                                frame.command_id, frame.command_name(), frame.hardware_id, frame.value
                             ),
                             "INFO"
                         );
 
                         // Envia os dados completos para o frontend
-                            if let Err(e) = app.emit_all("updateSerial", BinaryPayload { data: serial_buf.clone() }) {
-                                backend_log(
-                                    &app,
-                                    &format!("Failed to emit message to frontend: {:?}", e),
-                                    "error"
-                                );
-                            }
-
-                            // Limpa o buffer para a próxima mensagem
-                            serial_buf.clear();
+                        if let Err(e) = app.emit_all("updateSerial", BinaryPayload { data: raw.to_vec() }) {
+                            backend_log(
+                                &app,
+                                &format!("Failed to emit message to frontend: {:?}", e),
+                                "error"
+                            );
                         }
-                }
-                //backend_log(&app, "Read timed out.", "warning");
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
-                    
-                }
-                Err(e) => {
-                    backend_log(&app, &format!("Error reading from serial port: {:?}", e), "error");
-                    break;
+                    }
+                    DecodeEvent::Discarded(bytes) => {
+                        link.on_decode_error();
+                        stats.on_discarded(bytes.len());
+                        backend_log(
+                            &app,
+                            &format!("Discarded {} bytes while resynchronizing: {:?}", bytes.len(), bytes),
+                            "WARNING"
+                        );
+                    }
                 }
             }
         }
 
-        backend_log(&app, "Serial communication thread terminated.", "INFO");
-    });
+        backend_log(&app, "Serial communication task terminated.", "INFO");
+    }))
 }
 
+// Reopens the port when the heartbeat is set to reconnect, or else drops the connection.
+fn port_lost(app: &tauri::AppHandle) {
+    use crate::AppData;
+    let config = app.state::<AppData>().0.lock().unwrap().heartbeat_config;
+    if config.enabled && config.on_loss == LossAction::Reconnect {
+        let app = app.clone();
+        // The reconnect waits for this task to end, so run it detached from it.
+        tauri::async_runtime::spawn(async move {
+            crate::reconnect_port(&app).await;
+        });
+    } else {
+        drop_connection(app);
+    }
+}
 
-
-// Function to start a thread that records serial data to a file.
-pub fn start_record_on_port(
-    app: tauri::AppHandle,
-    mut port_clone: Box<dyn SerialPort>,
-    is_thread_open: Arc<AtomicBool>,
-    mut file: Option<File>,
-    path: PathBuf,
-) {
-    let mut serial_buf: Vec<u8> = vec![0; 32];
-    let mut start_time = SystemTime::now();
-
-    thread::spawn(move || {
-        is_thread_open.store(true, Ordering::Relaxed);
-        println!("Record thread spawned");
-
-        while is_thread_open.load(Ordering::Relaxed) {
-            match port_clone.read(serial_buf.as_mut_slice()) {
-                Ok(size) => {
-                    let bytes_read = &serial_buf[..size];
-                    println!("Received from record: {:?}", bytes_read);
-
-                    if let Some(ref mut file) = file {
-                        // Escrever diretamente bytes binários no arquivo
-                        file.write_all(bytes_read).expect("Could not write to file");
-                    }
-
-                    app.emit_all("updateSerial", BinaryPayload { data: bytes_read.to_vec() }).unwrap();
-                }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-                Err(_) => {
-                    let app_clone = app.clone();
-                    use crate::AppData;
-                    let state = app_clone.state::<AppData>();
-                    let mut state_guard = state.0.lock().unwrap();
-                    state_guard.port = None;
-                    state_guard.is_recording = false;
-                    is_thread_open.store(false, Ordering::Relaxed);
-                    app.emit_all("isConnected", MessagePayload {message: "disconnected".to_string()}).unwrap();
-                    app.emit_all("isRecording", MessagePayload {message: "not recording".to_string()}).unwrap();
-                }
-            }
-
-            // Rotate the recording file every 10 minutes.
-            if start_time.elapsed().unwrap() >= Duration::from_secs(600) {
-                if let Some(old_file) = file.take() {
-                    drop(old_file);
-                }
-
-                let formatted_date_time = Local::now().format("%Y-%m-%d_%H.%M.%S").to_string();
-                let file_name = format!("DCubedISM{}.txt", formatted_date_time);
-                let file_path = path.join(&file_name);
-
-                match File::create(&file_path) {
-                    Ok(new_file) => {
-                        file = Some(new_file);
-                        start_time = SystemTime::now();
-                    }
-                    Err(_) => {
-                        let app_clone = app.clone();
-                        use crate::AppData;
-                        let state = app_clone.state::<AppData>();
-                        let mut state_guard = state.0.lock().unwrap();
-                        state_guard.port = None;
-                        state_guard.is_recording = false;
-                        is_thread_open.store(false, Ordering::Relaxed);
-                        app.emit_all("isConnected", MessagePayload {message: "disconnected".to_string()}).unwrap();
-                        app.emit_all("isRecording", MessagePayload {message: "not recording".to_string()}).unwrap();
-                    }
-                }
-            }
-        }
-
-        println!("Terminating record thread and now enabling...");
-        is_thread_open.store(true, Ordering::Relaxed);
-    });
+// Marks the connection as lost after a fatal error in the reader.
+pub fn drop_connection(app: &tauri::AppHandle) {
+    use crate::AppData;
+    let state = app.state::<AppData>();
+    let mut state_guard = state.0.lock().unwrap();
+    if let Some(connection) = state_guard.connection.take() {
+        connection.cancel.cancel();
+    }
+    state_guard.is_recording = false;
+    drop(state_guard);
+    if let Err(err) = app.emit_all("isConnected", MessagePayload {message: "disconnected".to_string()}) {
+        println!("Failed to emit connection status to the frontend: {:?}", err);
+    }
+    if let Err(err) = app.emit_all("isRecording", MessagePayload {message: "not recording".to_string()}) {
+        println!("Failed to emit recording status to the frontend: {:?}", err);
+    }
 }
+
 /*
 pub fn write_serial_with_buffer(port: &mut Box<dyn SerialPort>, input: &[u8]) -> std::result::Result<usize, String> {
     match port.write(input) {
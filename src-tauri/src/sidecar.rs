@@ -0,0 +1,152 @@
+// src/sidecar.rs
+
+// Importing necessary crates and modules.
+use chrono::{Local, SecondsFormat}; // Crate for date and time handling.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::compression::Compression;
+use crate::recording::RecordingHeader;
+
+/// Extension appended to the full recording name, e.g. `DCubedISM<date>.txt.json`.
+pub const SIDECAR_EXTENSION: &str = ".json";
+/// Identifies the sidecar format.
+pub const SIDECAR_FORMAT: &str = "dcubedism-sidecar";
+pub const SIDECAR_VERSION: u32 = 1;
+
+/// Who recorded what, entered by the operator before a test run.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub operator: Option<String>,
+    pub unit_serial: Option<String>,
+    pub firmware: Option<String>,
+    pub test_run: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// Line settings of the port, as opened by `init_port`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: String,
+    pub stop_bits: u8,
+    pub flow_control: String,
+}
+
+/// Metadata written next to every recording file.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sidecar {
+    pub format: String,
+    pub version: u32,
+    /// File name of the recording this sidecar describes.
+    pub file: String,
+    /// Position of the file in its session, starting at 1.
+    pub part: u32,
+    pub port: String,
+    pub serial: SerialSettings,
+    pub app_version: String,
+    pub session: SessionInfo,
+    pub compression: Compression,
+    /// RFC 3339 timestamps.
+    pub session_started: String,
+    pub started: String,
+    pub ended: Option<String>,
+    /// Why the file was closed: "stopped" or the rotation reason.
+    pub end_reason: Option<String>,
+    /// Size of the file on disk.
+    pub bytes: u64,
+    /// Size of the recorded text before compression.
+    pub data_bytes: u64,
+    pub frames: u64,
+    /// SHA-256 of the file on disk, set once the file is complete.
+    pub sha256: Option<String>,
+}
+
+impl Sidecar {
+    /// Sidecar of a file that has just been created.
+    pub fn new(
+        recording: &Path,
+        part: u32,
+        header: &RecordingHeader,
+        app_version: &str,
+        session: &SessionInfo,
+        compression: Compression,
+    ) -> Self {
+        Sidecar {
+            format: SIDECAR_FORMAT.to_string(),
+            version: SIDECAR_VERSION,
+            file: recording
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            part,
+            port: header.port.clone(),
+            serial: SerialSettings {
+                baud_rate: header.baud_rate,
+                data_bits: 8,
+                parity: "none".to_string(),
+                stop_bits: 1,
+                flow_control: "none".to_string(),
+            },
+            app_version: app_version.to_string(),
+            session: session.clone(),
+            compression,
+            session_started: header.started.to_rfc3339_opts(SecondsFormat::Micros, false),
+            started: now_rfc3339(),
+            ended: None,
+            end_reason: None,
+            bytes: 0,
+            data_bytes: 0,
+            frames: 0,
+            sha256: None,
+        }
+    }
+
+    /// Writes the sidecar next to its recording.
+    ///
+    /// The JSON goes to a temporary file first, so a crash never leaves a
+    /// half-written sidecar behind.
+    pub async fn save(&self, recording: &Path) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let path = sidecar_path(recording);
+        let temporary = temporary_path(&path);
+        tokio::fs::write(&temporary, json).await?;
+        tokio::fs::rename(&temporary, &path).await
+    }
+}
+
+/// Blocking variant of `Sidecar::save`.
+pub fn save_blocking(sidecar: &Sidecar, recording: &Path) -> std::io::Result<()> {
+    let json = serde_json::to_vec_pretty(sidecar)?;
+    let path = sidecar_path(recording);
+    let temporary = temporary_path(&path);
+    std::fs::write(&temporary, json)?;
+    std::fs::rename(&temporary, &path)
+}
+
+// Path of the temporary file used while writing `path`.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+/// Current local time in the format used by the sidecar.
+pub fn now_rfc3339() -> String {
+    Local::now().to_rfc3339_opts(SecondsFormat::Micros, false)
+}
+
+/// Path of the sidecar belonging to `recording`.
+pub fn sidecar_path(recording: &Path) -> PathBuf {
+    let mut name = recording.as_os_str().to_os_string();
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Formats a digest as lower-case hex.
+pub fn hex_digest(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
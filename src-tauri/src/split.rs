@@ -0,0 +1,398 @@
+// src/split.rs
+
+// Splits decoded traffic into one stream per channel, i.e. per command and
+// hardware ID, so a single component can be followed without the rest. The
+// streams go into a folder next to the session, with a manifest:
+//
+//   <session>.channels/
+//     <session>_cmd02-MOTOR_SPEED_hw003.csv
+//     <session>_cmd20-LIGHT_BARRIER_TOGGLE_hw001.csv
+//     manifest.json
+//
+// The split runs offline over a recording, or live alongside a recording when
+// it is enabled in the split configuration. Also available without the UI:
+//
+//   app split <recording> [--format csv|ndjson] [--output <folder>]
+
+// Importing necessary crates and modules.
+use chrono::SecondsFormat; // Timestamps of the manifest.
+use std::collections::BTreeMap; // Channel files by command and hardware ID.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::convert::{self, Decoded, OutputFormat, Row, RowWriter};
+use crate::pipeline::SinkRegistry;
+use crate::protocol::{self, Frame};
+use crate::recording::{self, Direction, RecordingHeader};
+use crate::retention;
+use crate::serial_wrapper::backend_log;
+
+/// Name under which the live split registers on the connection's sinks.
+pub const CHANNELS_SINK: &str = "channels";
+/// Name of the manifest in a channel folder.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Configuration of the live split, applied when a recording starts.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitConfig {
+    /// Write the channel streams alongside every recording.
+    pub enabled: bool,
+    pub format: OutputFormat,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        SplitConfig {
+            enabled: false,
+            format: OutputFormat::Csv,
+        }
+    }
+}
+
+/// One channel of a split.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelEntry {
+    pub command_id: u8,
+    pub command: &'static str,
+    pub hardware_id: u8,
+    /// File name inside the channel folder.
+    pub file: String,
+    pub frames: u64,
+    pub first_t_us: Option<u64>,
+    pub last_t_us: Option<u64>,
+}
+
+/// Manifest of a channel folder, also returned by `split_recording`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitManifest {
+    /// "recording" for an offline split, "live" for one written while recording.
+    pub source: &'static str,
+    /// The split recording, or the first file of the live session.
+    pub session: String,
+    pub started: Option<String>,
+    pub format: OutputFormat,
+    pub folder: String,
+    /// What the `offset` column counts: bytes of the recording, or of the live traffic.
+    pub offsets: &'static str,
+    pub frames: u64,
+    /// Bytes or lines that formed no frame; they belong to no channel.
+    pub malformed: u64,
+    pub channels: Vec<ChannelEntry>,
+}
+
+/// Channel folder of a recording: its name with the extensions replaced by `.channels`.
+pub fn channel_folder(recording: &Path) -> PathBuf {
+    convert::output_with_extension(recording, "channels")
+}
+
+/// Moves the channel folder of `recording`, if it has one, next to `moved`,
+/// the new path of the recording, and points its manifest at the new paths.
+pub fn move_channel_folder(recording: &Path, moved: &Path) -> std::io::Result<()> {
+    let from = channel_folder(recording);
+    if !from.is_dir() {
+        return Ok(());
+    }
+    let to = channel_folder(moved);
+    if to.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    retention::move_folder(&from, &to)?;
+
+    // The manifest is rewritten as JSON so fields it does not know are kept.
+    let manifest = to.join(MANIFEST_FILE);
+    if let Ok(json) = std::fs::read(&manifest) {
+        let mut value: serde_json::Value = serde_json::from_slice(&json)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("session".to_string(), moved.to_string_lossy().into());
+            fields.insert("folder".to_string(), to.to_string_lossy().into());
+        }
+        std::fs::write(&manifest, serde_json::to_vec_pretty(&value)?)?;
+    }
+    Ok(())
+}
+
+/// Removes the channel folder of `recording`, if it has one.
+pub fn remove_channel_folder(recording: &Path) -> std::io::Result<()> {
+    let folder = channel_folder(recording);
+    if folder.is_dir() {
+        std::fs::remove_dir_all(&folder)?;
+    }
+    Ok(())
+}
+
+// Channel files of a split being written.
+struct ChannelFiles {
+    folder: PathBuf,
+    stem: String,
+    format: OutputFormat,
+    channels: BTreeMap<(u8, u8), (RowWriter, ChannelEntry)>,
+    frames: u64,
+    malformed: u64,
+}
+
+impl ChannelFiles {
+    fn create(folder: PathBuf, format: OutputFormat) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&folder)?;
+        let stem = folder
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(ChannelFiles {
+            folder,
+            stem,
+            format,
+            channels: BTreeMap::new(),
+            frames: 0,
+            malformed: 0,
+        })
+    }
+
+    // Writes a frame into the file of its channel, creating the file on first use.
+    fn write(
+        &mut self,
+        frame: &Frame,
+        offset: u64,
+        t_us: Option<u64>,
+        direction: Option<Direction>,
+    ) -> std::io::Result<()> {
+        let key = (frame.command_id, frame.hardware_id);
+        if !self.channels.contains_key(&key) {
+            let file = format!(
+                "{}_cmd{:02}-{}_hw{:03}.{}",
+                self.stem,
+                frame.command_id,
+                frame.command_name(),
+                frame.hardware_id,
+                self.format.extension()
+            );
+            let writer = RowWriter::create(&self.folder.join(&file), self.format)?;
+            let entry = ChannelEntry {
+                command_id: frame.command_id,
+                command: protocol::command_name(frame.command_id),
+                hardware_id: frame.hardware_id,
+                file,
+                frames: 0,
+                first_t_us: None,
+                last_t_us: None,
+            };
+            self.channels.insert(key, (writer, entry));
+        }
+        let (writer, entry) = self.channels.get_mut(&key).unwrap();
+        writer.write(&Row::new(frame, offset, t_us, direction))?;
+        entry.frames += 1;
+        entry.first_t_us = entry.first_t_us.or(t_us);
+        entry.last_t_us = t_us.or(entry.last_t_us);
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.channels.values_mut().try_for_each(|(writer, _)| writer.flush())
+    }
+
+    // Closes the channel files and writes the manifest.
+    fn finish(
+        self,
+        source: &'static str,
+        session: &Path,
+        started: Option<String>,
+    ) -> std::io::Result<SplitManifest> {
+        let mut channels = Vec::with_capacity(self.channels.len());
+        for (writer, entry) in self.channels.into_values() {
+            writer.finish()?;
+            channels.push(entry);
+        }
+        let manifest = SplitManifest {
+            source,
+            session: session.to_string_lossy().to_string(),
+            started,
+            format: self.format,
+            folder: self.folder.to_string_lossy().to_string(),
+            offsets: if source == "live" { "traffic" } else { "recording" },
+            frames: self.frames,
+            malformed: self.malformed,
+            channels,
+        };
+        let path = self.folder.join(MANIFEST_FILE);
+        let temporary = path.with_extension("json.tmp");
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        std::fs::write(&temporary, json)?;
+        std::fs::rename(&temporary, &path)?;
+        Ok(manifest)
+    }
+}
+
+/// Splits a recording, raw or structured, into `output`, or into its channel folder. Blocking.
+pub fn split(input: &Path, format: OutputFormat, output: Option<PathBuf>) -> Result<SplitManifest, String> {
+    std::fs::File::open(input).map_err(|e| format!("Could not open {}: {}", input.display(), e))?;
+    let folder = output.unwrap_or_else(|| channel_folder(input));
+    let mut files =
+        ChannelFiles::create(folder.clone(), format).map_err(|e| format!("Could not create {}: {}", folder.display(), e))?;
+
+    let structured = convert::decode(input, |item| match item {
+        Decoded::Frame {
+            offset,
+            t_us,
+            direction,
+            frame,
+            ..
+        } => files.write(&frame, offset, t_us, direction),
+        Decoded::Malformed(_) => {
+            files.malformed += 1;
+            Ok(())
+        }
+    })
+    .map_err(|e| format!("Split of {} failed: {}", input.display(), e))?;
+
+    let started = if structured {
+        recording::read_started(input)
+            .ok()
+            .flatten()
+            .map(|started| started.to_rfc3339_opts(SecondsFormat::Micros, false))
+    } else {
+        None
+    };
+    files
+        .finish("recording", input, started)
+        .map_err(|e| format!("Split of {} failed: {}", input.display(), e))
+}
+
+/// A live split running alongside a recording.
+pub struct SplitTask {
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl SplitTask {
+    /// Stops the split once every event received so far is written, and writes the manifest.
+    pub async fn stop(self, sinks: &SinkRegistry) {
+        sinks.unregister(CHANNELS_SINK);
+        let thread = self.thread;
+        let _ = tauri::async_runtime::spawn_blocking(move || thread.join()).await;
+    }
+}
+
+/// Starts splitting the live traffic into the channel folder of `session`, the
+/// first file of the recording.
+pub fn start_live_split(
+    app: tauri::AppHandle,
+    session: &Path,
+    header: &RecordingHeader,
+    format: OutputFormat,
+    sinks: &SinkRegistry,
+) -> Result<SplitTask, String> {
+    let folder = channel_folder(session);
+    let mut files =
+        ChannelFiles::create(folder.clone(), format).map_err(|e| format!("Could not create {}: {}", folder.display(), e))?;
+    let mut events = sinks.register(CHANNELS_SINK);
+    let session = session.to_path_buf();
+    let session_start = header.started_at;
+    let started = header.started.to_rfc3339_opts(SecondsFormat::Micros, false);
+
+    let thread = std::thread::spawn(move || {
+        // Offsets count the bytes of traffic since the recording started.
+        let mut offset = 0;
+        let mut failed = false;
+        // Ends once the sink is unregistered and its pending events are read.
+        while let Some(event) = events.blocking_recv() {
+            let mut batch = vec![event];
+            while let Ok(event) = events.try_recv() {
+                batch.push(event);
+            }
+            let result = batch
+                .iter()
+                .try_for_each(|event| {
+                    let t_us = recording::elapsed_us(session_start, event.at);
+                    let result = match &event.frame {
+                        Some(frame) => files.write(frame, offset, Some(t_us), Some(event.direction)),
+                        None => {
+                            files.malformed += 1;
+                            Ok(())
+                        }
+                    };
+                    offset += event.raw.len() as u64;
+                    result
+                })
+                .and_then(|_| files.flush());
+            // Report the first failure only.
+            if let Err(e) = result {
+                if !failed {
+                    backend_log(&app, &format!("Could not write the channel streams: {}", e), "ERROR");
+                }
+                failed = true;
+            }
+        }
+
+        match files.finish("live", &session, Some(started)) {
+            Ok(manifest) => backend_log(
+                &app,
+                &format!(
+                    "Channel streams written to {}: {} channel(s), {} frames.",
+                    manifest.folder,
+                    manifest.channels.len(),
+                    manifest.frames
+                ),
+                "SUCCESS",
+            ),
+            Err(e) => backend_log(&app, &format!("Could not finish the channel streams: {}", e), "ERROR"),
+        }
+    });
+
+    Ok(SplitTask { thread })
+}
+
+/// Runs `split` from the command line and returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut format = OutputFormat::Csv;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("csv") => format = OutputFormat::Csv,
+                Some("ndjson") => format = OutputFormat::Ndjson,
+                other => {
+                    eprintln!("Unknown format {:?}; use csv or ndjson.", other.unwrap_or_default());
+                    return 2;
+                }
+            },
+            "--output" => output = args.next().map(PathBuf::from),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument {}.", arg);
+                return 2;
+            }
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("Usage: app split <recording> [--format csv|ndjson] [--output <folder>]");
+            return 2;
+        }
+    };
+
+    match split(&input, format, output) {
+        Ok(manifest) => {
+            for channel in &manifest.channels {
+                println!("{}\t{} frames", channel.file, channel.frames);
+            }
+            println!(
+                "{} frames in {} channel(s) written to {}.",
+                manifest.frames,
+                manifest.channels.len(),
+                manifest.folder
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
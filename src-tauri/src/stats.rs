@@ -0,0 +1,245 @@
+// src/stats.rs
+
+// Importing necessary crates and modules.
+use chrono::{DateTime, Local, SecondsFormat}; // Crate for date and time handling.
+use std::collections::BTreeMap; // Frame counts by command ID.
+use std::sync::{Arc, Mutex}; // Shared, thread-safe counters.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio_util::sync::CancellationToken; // Token used to stop the statistics task.
+
+use crate::protocol::{self, Frame};
+use crate::recording::Direction;
+
+/// Configuration of the link statistics, applied on connect.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialStatsConfig {
+    /// Time between two `serialStats` events, in milliseconds.
+    pub emit_interval_ms: u64,
+    /// Silence on the port after which a read timeout is counted, in milliseconds.
+    pub read_timeout_ms: u64,
+}
+
+impl Default for SerialStatsConfig {
+    fn default() -> Self {
+        SerialStatsConfig {
+            emit_interval_ms: 1000,
+            read_timeout_ms: 2000,
+        }
+    }
+}
+
+/// Frames of one command ID.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandCount {
+    pub command_id: u8,
+    pub command: &'static str,
+    pub rx: u64,
+    pub tx: u64,
+}
+
+/// Payload of the `serialStats` event and of `get_serial_stats`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialStatsReport {
+    /// When counting started: on connect or on the last reset, RFC 3339.
+    pub since: String,
+    pub elapsed_ms: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Throughput over the last emit interval.
+    pub bytes_in_per_sec: f64,
+    pub bytes_out_per_sec: f64,
+    pub frames_in: u64,
+    pub frames_out: u64,
+    pub commands: Vec<CommandCount>,
+    /// Bytes skipped while resynchronizing, and how often that happened.
+    pub discarded_bytes: u64,
+    pub desync_events: u64,
+    pub write_errors: u64,
+    pub read_timeouts: u64,
+    pub ms_since_last_byte: Option<u64>,
+}
+
+// Counters of the current session.
+struct Counters {
+    since: DateTime<Local>,
+    started_at: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+    frames_in: u64,
+    frames_out: u64,
+    commands: BTreeMap<u8, (u64, u64)>,
+    discarded_bytes: u64,
+    desync_events: u64,
+    write_errors: u64,
+    read_timeouts: u64,
+    last_byte_at: Option<Instant>,
+    // The current silence was already counted as a timeout.
+    timed_out: bool,
+    // Totals and time of the previous tick, for the throughput.
+    tick: (Instant, u64, u64),
+    rates: (f64, f64),
+}
+
+impl Counters {
+    fn new() -> Self {
+        let now = Instant::now();
+        Counters {
+            since: Local::now(),
+            started_at: now,
+            bytes_in: 0,
+            bytes_out: 0,
+            frames_in: 0,
+            frames_out: 0,
+            commands: BTreeMap::new(),
+            discarded_bytes: 0,
+            desync_events: 0,
+            write_errors: 0,
+            read_timeouts: 0,
+            last_byte_at: None,
+            timed_out: false,
+            tick: (now, 0, 0),
+            rates: (0.0, 0.0),
+        }
+    }
+}
+
+/// Link and traffic counters shared between the reader, the writer and the statistics task.
+pub struct StatsMonitor {
+    counters: Mutex<Counters>,
+}
+
+impl StatsMonitor {
+    pub fn new() -> Arc<Self> {
+        Arc::new(StatsMonitor {
+            counters: Mutex::new(Counters::new()),
+        })
+    }
+
+    /// Called by the reader for every chunk read from the port.
+    pub fn on_read(&self, bytes: usize) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.bytes_in += bytes as u64;
+        counters.last_byte_at = Some(Instant::now());
+        counters.timed_out = false;
+    }
+
+    /// Called for every decoded frame, received or sent.
+    pub fn on_frame(&self, direction: Direction, frame: &Frame) {
+        let mut counters = self.counters.lock().unwrap();
+        match direction {
+            Direction::Rx => {
+                counters.frames_in += 1;
+                counters.commands.entry(frame.command_id).or_default().0 += 1;
+            }
+            Direction::Tx => {
+                counters.frames_out += 1;
+                counters.commands.entry(frame.command_id).or_default().1 += 1;
+            }
+        }
+    }
+
+    /// Called by the reader for bytes skipped while resynchronizing.
+    pub fn on_discarded(&self, bytes: usize) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.discarded_bytes += bytes as u64;
+        counters.desync_events += 1;
+    }
+
+    /// Called by the writer after every write attempt.
+    pub fn on_write(&self, bytes: &[u8], result: &std::io::Result<()>) {
+        match result {
+            Ok(()) => {
+                self.counters.lock().unwrap().bytes_out += bytes.len() as u64;
+                if let Some(frame) = Frame::parse(bytes) {
+                    self.on_frame(Direction::Tx, &frame);
+                }
+            }
+            Err(_) => self.counters.lock().unwrap().write_errors += 1,
+        }
+    }
+
+    /// Starts counting again from zero.
+    pub fn reset(&self) {
+        *self.counters.lock().unwrap() = Counters::new();
+    }
+
+    // Updates the throughput and counts a read timeout once per silence.
+    fn tick(&self, read_timeout: Duration) {
+        let mut counters = self.counters.lock().unwrap();
+        let now = Instant::now();
+        let (at, bytes_in, bytes_out) = counters.tick;
+        let seconds = now.duration_since(at).as_secs_f64();
+        if seconds > 0.0 {
+            counters.rates = (
+                (counters.bytes_in - bytes_in) as f64 / seconds,
+                (counters.bytes_out - bytes_out) as f64 / seconds,
+            );
+        }
+        counters.tick = (now, counters.bytes_in, counters.bytes_out);
+
+        let silent_since = counters.last_byte_at.unwrap_or(counters.started_at);
+        if !counters.timed_out && now.duration_since(silent_since) >= read_timeout {
+            counters.timed_out = true;
+            counters.read_timeouts += 1;
+        }
+    }
+
+    /// Current statistics.
+    pub fn report(&self) -> SerialStatsReport {
+        let counters = self.counters.lock().unwrap();
+        SerialStatsReport {
+            since: counters.since.to_rfc3339_opts(SecondsFormat::Millis, false),
+            elapsed_ms: counters.started_at.elapsed().as_millis() as u64,
+            bytes_in: counters.bytes_in,
+            bytes_out: counters.bytes_out,
+            bytes_in_per_sec: counters.rates.0,
+            bytes_out_per_sec: counters.rates.1,
+            frames_in: counters.frames_in,
+            frames_out: counters.frames_out,
+            commands: counters
+                .commands
+                .iter()
+                .map(|(&command_id, &(rx, tx))| CommandCount {
+                    command_id,
+                    command: protocol::command_name(command_id),
+                    rx,
+                    tx,
+                })
+                .collect(),
+            discarded_bytes: counters.discarded_bytes,
+            desync_events: counters.desync_events,
+            write_errors: counters.write_errors,
+            read_timeouts: counters.read_timeouts,
+            ms_since_last_byte: counters.last_byte_at.map(|at| at.elapsed().as_millis() as u64),
+        }
+    }
+}
+
+/// Starts the task emitting `serialStats` periodically.
+pub fn start_stats_task(
+    app: tauri::AppHandle,
+    config: SerialStatsConfig,
+    monitor: Arc<StatsMonitor>,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(config.emit_interval_ms.max(100)));
+        let read_timeout = Duration::from_millis(config.read_timeout_ms);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = ticker.tick() => {
+                    monitor.tick(read_timeout);
+                    if let Err(e) = app.emit_all("serialStats", monitor.report()) {
+                        println!("Failed to emit serial stats to the frontend: {:?}", e);
+                    }
+                }
+            }
+        }
+    })
+}
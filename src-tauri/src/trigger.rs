@@ -0,0 +1,344 @@
+// src/trigger.rs
+
+// Trigger-based recording. While armed, the last seconds of traffic are kept
+// in memory; when a rule matches a frame, or the trigger is fired by hand, a
+// recording starts with that history and runs until the post-trigger window
+// has passed. A trigger during the window extends it. The files are ordinary
+// recordings, written by the record task with the configured policies.
+
+// Importing necessary crates and modules.
+use chrono::Local; // Crate for date and time handling.
+use std::collections::VecDeque; // Ring buffer of the pre-trigger history.
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio::sync::mpsc::{self, Sender, UnboundedSender}; // Channels of the pipeline and the manual triggers.
+use tokio_util::sync::CancellationToken; // Token used to stop the trigger task.
+
+use crate::pipeline::{SinkEvent, SinkRegistry, SINK_CAPACITY};
+use crate::recorder::{self, RecordingOptions, RecordingTask};
+use crate::recording::{self, Direction};
+use crate::serial_wrapper::backend_log;
+
+/// Name under which the trigger task registers on the connection's sinks.
+pub const TRIGGER_SINK: &str = "trigger";
+
+/// A condition on the live traffic that fires the trigger.
+///
+/// Every criterion is optional; a rule without any matches every frame.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TriggerRule {
+    /// Shown in the log and written into the recording as a marker.
+    pub name: Option<String>,
+    pub command_id: Option<u8>,
+    pub hardware_id: Option<u8>,
+    pub direction: Option<Direction>,
+    pub min_value: Option<u32>,
+    pub max_value: Option<u32>,
+    /// Fire on bytes discarded while resynchronizing instead of on frames.
+    pub discarded: bool,
+}
+
+impl TriggerRule {
+    fn matches(&self, event: &SinkEvent) -> bool {
+        if matches!(self.direction, Some(direction) if direction != event.direction) {
+            return false;
+        }
+        match &event.frame {
+            None => self.discarded,
+            Some(_) if self.discarded => false,
+            Some(frame) => {
+                !matches!(self.command_id, Some(id) if frame.command_id != id)
+                    && !matches!(self.hardware_id, Some(id) if frame.hardware_id != id)
+                    && !matches!(self.min_value, Some(min) if frame.value < min)
+                    && !matches!(self.max_value, Some(max) if frame.value > max)
+            }
+        }
+    }
+
+    // Text of the marker written when the rule fires.
+    fn describe(&self, event: &SinkEvent) -> String {
+        let what = match &event.frame {
+            Some(frame) => format!(
+                "{} {} hw {} = {}",
+                event.direction.as_str(),
+                frame.command_name(),
+                frame.hardware_id,
+                frame.value
+            ),
+            None => format!("{} {} discarded byte(s)", event.direction.as_str(), event.raw.len()),
+        };
+        match &self.name {
+            Some(name) => format!("{} ({})", name, what),
+            None => what,
+        }
+    }
+}
+
+/// Configuration of the trigger-based recording, applied when it is armed.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerConfig {
+    /// History kept in memory and written before the trigger, in seconds.
+    pub pre_trigger_secs: u64,
+    /// Time recorded after the last trigger, in seconds.
+    pub post_trigger_secs: u64,
+    /// Upper bound of the history, in events, whatever its duration.
+    pub max_buffered_events: usize,
+    /// Empty for manual triggers only.
+    pub rules: Vec<TriggerRule>,
+}
+
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        TriggerConfig {
+            pre_trigger_secs: 10,
+            post_trigger_secs: 30,
+            max_buffered_events: 100_000,
+            rules: Vec::new(),
+        }
+    }
+}
+
+// Payload of the `recordingTriggered` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TriggeredPayload {
+    reason: String,
+    path: String,
+    /// False when the trigger extended a recording already running.
+    started: bool,
+    pre_trigger_ms: u64,
+}
+
+// Payload of the `triggeredRecordingDone` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DonePayload {
+    path: String,
+    triggers: u64,
+}
+
+// The recording started by a trigger.
+struct Triggered {
+    recording: RecordingTask,
+    events: Sender<SinkEvent>,
+    /// Events lost because the record task fell behind.
+    dropped: u64,
+    path: String,
+    until: Instant,
+    triggers: u64,
+}
+
+/// The armed trigger of a connection.
+pub struct TriggerTask {
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+    manual: UnboundedSender<String>,
+}
+
+impl TriggerTask {
+    /// Fires the trigger by hand.
+    pub fn fire(&self, reason: String) -> Result<(), String> {
+        self.manual
+            .send(reason)
+            .map_err(|_| "The trigger is no longer armed.".to_string())
+    }
+
+    /// Disarms the trigger; a triggered recording is closed first.
+    pub async fn stop(self, sinks: &SinkRegistry) {
+        sinks.unregister(TRIGGER_SINK);
+        self.cancel.cancel();
+        let _ = self.task.await;
+    }
+}
+
+// State of the trigger task.
+struct Armed {
+    app: tauri::AppHandle,
+    config: TriggerConfig,
+    template: RecordingOptions,
+    cancel: CancellationToken,
+    buffer: VecDeque<SinkEvent>,
+    active: Option<Triggered>,
+}
+
+impl Armed {
+    // Passes an event to the running recording and keeps it in the history.
+    fn push(&mut self, event: SinkEvent) {
+        if let Some(triggered) = self.active.as_mut() {
+            if triggered.events.try_send(event.clone()).is_err() {
+                triggered.dropped += 1;
+            }
+        }
+        // The history always covers the last seconds, also while recording.
+        let history = Duration::from_secs(self.config.pre_trigger_secs);
+        let at = event.at;
+        self.buffer.push_back(event);
+        while self.buffer.len() > self.config.max_buffered_events
+            || self.buffer.front().is_some_and(|oldest| at.duration_since(oldest.at) > history)
+        {
+            self.buffer.pop_front();
+        }
+    }
+
+    // Starts a recording holding the buffered history, or extends the running one.
+    async fn fire(&mut self, at: Instant, reason: String) {
+        let app = &self.app;
+        let until = at + Duration::from_secs(self.config.post_trigger_secs);
+        if let Some(triggered) = self.active.as_mut() {
+            triggered.until = triggered.until.max(until);
+            triggered.triggers += 1;
+            let _ = triggered.recording.add_marker(&format!("Trigger: {}", reason));
+            emit_triggered(app, reason, triggered.path.clone(), false, 0);
+            return;
+        }
+
+        // The session starts with the oldest buffered event.
+        let started_at = self.buffer.front().map_or(at, |event| event.at.min(at));
+        let mut options = self.template.clone();
+        options.header.started_at = started_at;
+        options.header.started =
+            Local::now() - chrono::Duration::from_std(started_at.elapsed()).unwrap_or_default();
+        let file = match recorder::open_session_file(&options).await {
+            Ok(file) => file,
+            Err(e) => {
+                backend_log(
+                    app,
+                    &format!("Trigger fired ({}), but no recording could be created: {}", reason, e),
+                    "ERROR",
+                );
+                return;
+            }
+        };
+        let path = file.path().to_string_lossy().to_string();
+        // Room for the whole history plus as much lag as any other sink.
+        let (events, receiver) = mpsc::channel(self.buffer.len() + SINK_CAPACITY);
+        for event in &self.buffer {
+            let _ = events.try_send(event.clone());
+        }
+        let recording = recorder::start_record_task(app.clone(), file, options, receiver, self.cancel.child_token());
+        let _ = recording.add_marker(&format!("Trigger: {}", reason));
+
+        let pre_trigger_ms = recording::elapsed_us(started_at, at) / 1000;
+        backend_log(
+            app,
+            &format!("Trigger fired ({}): recording to {} with {} ms of history.", reason, path, pre_trigger_ms),
+            "SUCCESS",
+        );
+        emit_triggered(app, reason, path.clone(), true, pre_trigger_ms);
+        self.active = Some(Triggered {
+            recording,
+            events,
+            dropped: 0,
+            path,
+            until,
+            triggers: 1,
+        });
+    }
+
+    // Closes the triggered recording, if any.
+    async fn finish(&mut self) {
+        let triggered = match self.active.take() {
+            Some(triggered) => triggered,
+            None => return,
+        };
+        // Closing the channel lets the record task write everything sent so far.
+        drop(triggered.events);
+        triggered.recording.stop().await;
+        if triggered.dropped > 0 {
+            backend_log(
+                &self.app,
+                &format!(
+                    "Triggered recording {} lost {} event(s) while it fell behind.",
+                    triggered.path, triggered.dropped
+                ),
+                "WARNING",
+            );
+        }
+        backend_log(
+            &self.app,
+            &format!("Triggered recording finished: {} ({} trigger(s)).", triggered.path, triggered.triggers),
+            "INFO",
+        );
+        let payload = DonePayload {
+            path: triggered.path,
+            triggers: triggered.triggers,
+        };
+        if let Err(e) = self.app.emit_all("triggeredRecordingDone", payload) {
+            println!("Failed to emit triggered recording to the frontend: {:?}", e);
+        }
+    }
+}
+
+fn emit_triggered(app: &tauri::AppHandle, reason: String, path: String, started: bool, pre_trigger_ms: u64) {
+    let payload = TriggeredPayload {
+        reason,
+        path,
+        started,
+        pre_trigger_ms,
+    };
+    if let Err(e) = app.emit_all("recordingTriggered", payload) {
+        println!("Failed to emit recording trigger to the frontend: {:?}", e);
+    }
+}
+
+/// Arms the trigger on a connection.
+///
+/// `template` holds the folder and policies of the recordings; their header
+/// is filled in when the trigger fires.
+pub fn start_trigger_task(
+    app: tauri::AppHandle,
+    config: TriggerConfig,
+    template: RecordingOptions,
+    sinks: &SinkRegistry,
+    cancel: CancellationToken,
+) -> TriggerTask {
+    let mut events = sinks.register(TRIGGER_SINK);
+    let (manual, mut manual_triggers) = mpsc::unbounded_channel::<String>();
+    let stop = cancel.clone();
+
+    let mut armed = Armed {
+        app,
+        config,
+        template,
+        cancel,
+        buffer: VecDeque::new(),
+        active: None,
+    };
+
+    let task = tauri::async_runtime::spawn(async move {
+        loop {
+            let until = armed.active.as_ref().map(|triggered| triggered.until);
+            tokio::select! {
+                _ = armed.cancel.cancelled() => break,
+                // The post-trigger window has passed.
+                _ = recorder::sleep_until(until) => armed.finish().await,
+                Some(reason) = manual_triggers.recv() => armed.fire(Instant::now(), reason).await,
+                event = events.recv() => {
+                    let event = match event {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    let at = event.at;
+                    let reason = armed
+                        .config
+                        .rules
+                        .iter()
+                        .find(|rule| rule.matches(&event))
+                        .map(|rule| rule.describe(&event));
+                    armed.push(event);
+                    if let Some(reason) = reason {
+                        armed.fire(at, reason).await;
+                    }
+                }
+            }
+        }
+
+        armed.finish().await;
+        println!("Terminating trigger task.");
+    });
+
+    TriggerTask { cancel: stop, task, manual }
+}
@@ -0,0 +1,291 @@
+// src/vcd.rs
+
+// Exports a structured recording as a Value Change Dump, the trace format read
+// by GTKWave and by PulseView's VCD import. Every device state carried by the
+// protocol becomes a signal, in one scope per component:
+//
+//   led_<hw>      on (1 bit), intensity (32 bits, %)
+//   motor_<hw>    on (1 bit), speed (32 bits, Hz), direction (1 bit, 1 = CCW)
+//   barrier_<hw>  active (1 bit)
+//
+// Times are the recording timestamps, in microseconds since the session start
+// given in the `$date` section. Also available without the UI:
+//
+//   app vcd <recording> [--direction rx|tx] [--output <path>]
+
+// Importing necessary crates and modules.
+use std::collections::BTreeMap; // Signals in scope order.
+use std::fs::File; // Struct for writing the trace.
+use std::io::{BufWriter, Write}; // Sync I/O traits.
+use std::path::{Path, PathBuf}; // Structs for handling filesystem paths.
+
+use crate::convert::{self, Decoded};
+use crate::protocol::{self, Frame};
+use crate::recording::{self, Direction};
+
+/// Result of an export, returned by `export_recording_vcd`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VcdReport {
+    pub input: String,
+    pub output: String,
+    /// Signals of the trace, as `scope.name`.
+    pub signals: Vec<String>,
+    pub frames: u64,
+    /// Value changes written.
+    pub changes: u64,
+    pub duration_us: u64,
+}
+
+// A signal of the trace; the field order is the order of the scopes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Signal {
+    component: &'static str,
+    hardware_id: u8,
+    name: &'static str,
+    width: u8,
+}
+
+impl Signal {
+    // Signal set by a frame, if the command carries a device state.
+    fn of(frame: &Frame) -> Option<Self> {
+        let (component, name, width) = match frame.command_id {
+            protocol::LED_ON_OFF => ("led", "on", 1),
+            protocol::LED_INTENSITY => ("led", "intensity", 32),
+            protocol::MOTOR_ON_OFF => ("motor", "on", 1),
+            protocol::MOTOR_SPEED => ("motor", "speed", 32),
+            protocol::MOTOR_DIRECTION => ("motor", "direction", 1),
+            protocol::LIGHT_BARRIER_TOGGLE => ("barrier", "active", 1),
+            _ => return None,
+        };
+        Some(Signal {
+            component,
+            hardware_id: frame.hardware_id,
+            name,
+            width,
+        })
+    }
+
+    fn scope(&self) -> String {
+        format!("{}_{}", self.component, self.hardware_id)
+    }
+
+    // Value of the signal for a frame value; single bits are on for anything but 0.
+    fn value(&self, value: u32) -> u32 {
+        if self.width == 1 {
+            (value != 0) as u32
+        } else {
+            value
+        }
+    }
+
+    // Value change line for the signal with identifier `id`.
+    fn change(&self, value: u32, id: &str) -> String {
+        if self.width == 1 {
+            format!("{}{}\n", value, id)
+        } else {
+            format!("b{:b} {}\n", value, id)
+        }
+    }
+}
+
+// Identifier code of the n-th signal, in the printable characters '!' to '~'.
+fn identifier(mut n: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return id;
+        }
+        n -= 1;
+    }
+}
+
+// Calls `on` with the time and frame of every exported frame.
+fn frames(
+    path: &Path,
+    direction: Option<Direction>,
+    mut on: impl FnMut(u64, &Frame) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    convert::decode(path, |item| match item {
+        Decoded::Frame {
+            t_us: Some(t_us),
+            direction: frame_direction,
+            frame,
+            ..
+        } if !matches!(direction, Some(wanted) if frame_direction != Some(wanted)) => on(t_us, &frame),
+        _ => Ok(()),
+    })
+    .map(|_| ())
+}
+
+// Writes the definitions section of the trace.
+fn write_header(
+    writer: &mut impl Write,
+    input: &Path,
+    started: Option<String>,
+    direction: Option<Direction>,
+    signals: &BTreeMap<Signal, String>,
+) -> std::io::Result<()> {
+    writeln!(writer, "$date\n  {}\n$end", started.as_deref().unwrap_or("unknown"))?;
+    writeln!(writer, "$version\n  DCubedISM recording export\n$end")?;
+    writeln!(
+        writer,
+        "$comment\n  {}, {} frames\n$end",
+        input.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+        direction.map_or("RX and TX", |direction| direction.as_str())
+    )?;
+    writeln!(writer, "$timescale 1us $end")?;
+    writeln!(writer, "$scope module dcubedism $end")?;
+    let mut scope: Option<String> = None;
+    for (signal, id) in signals {
+        if scope.as_deref() != Some(signal.scope().as_str()) {
+            if scope.is_some() {
+                writeln!(writer, "$upscope $end")?;
+            }
+            writeln!(writer, "$scope module {} $end", signal.scope())?;
+            scope = Some(signal.scope());
+        }
+        let kind = if signal.width == 1 { "wire" } else { "reg" };
+        writeln!(writer, "$var {} {} {} {} $end", kind, signal.width, id, signal.name)?;
+    }
+    if scope.is_some() {
+        writeln!(writer, "$upscope $end")?;
+    }
+    writeln!(writer, "$upscope $end\n$enddefinitions $end")?;
+
+    // States are unknown until the first frame sets them.
+    writeln!(writer, "#0\n$dumpvars")?;
+    for (signal, id) in signals {
+        if signal.width == 1 {
+            writeln!(writer, "x{}", id)?;
+        } else {
+            writeln!(writer, "bx {}", id)?;
+        }
+    }
+    writeln!(writer, "$end")
+}
+
+/// Exports a structured recording into `output`, or next to it as `.vcd`. Blocking.
+///
+/// With a direction, only the frames of that direction set the signals.
+pub fn export(input: &Path, output: Option<PathBuf>, direction: Option<Direction>) -> Result<VcdReport, String> {
+    let output = output.unwrap_or_else(|| convert::output_with_extension(input, "vcd"));
+    if output == input {
+        return Err("The output would overwrite the recording.".to_string());
+    }
+    if !convert::is_structured(input).map_err(|e| format!("Could not open {}: {}", input.display(), e))? {
+        return Err("Raw recordings carry no timestamps; convert them first.".to_string());
+    }
+    let read_error = |e: std::io::Error| format!("Could not read {}: {}", input.display(), e);
+
+    // A first pass finds the signals, so the definitions can precede the changes.
+    let mut signals: BTreeMap<Signal, String> = BTreeMap::new();
+    frames(input, direction, |_, frame| {
+        if let Some(signal) = Signal::of(frame) {
+            signals.entry(signal).or_default();
+        }
+        Ok(())
+    })
+    .map_err(read_error)?;
+    for (n, id) in signals.values_mut().enumerate() {
+        *id = identifier(n);
+    }
+
+    let started = recording::read_started(input)
+        .map_err(read_error)?
+        .map(|started| started.to_rfc3339_opts(chrono::SecondsFormat::Micros, false));
+    let write_error = |e: std::io::Error| format!("Could not write {}: {}", output.display(), e);
+    let mut writer = BufWriter::new(File::create(&output).map_err(write_error)?);
+    write_header(&mut writer, input, started, direction, &signals).map_err(write_error)?;
+
+    let mut last: BTreeMap<Signal, u32> = BTreeMap::new();
+    let (mut now_us, mut end_us) = (0, 0);
+    let (mut frame_count, mut changes) = (0, 0);
+    frames(input, direction, |t_us, frame| {
+        frame_count += 1;
+        end_us = end_us.max(t_us);
+        let signal = match Signal::of(frame) {
+            Some(signal) => signal,
+            None => return Ok(()),
+        };
+        let value = signal.value(frame.value);
+        if last.insert(signal, value) == Some(value) {
+            return Ok(());
+        }
+        // A trace never goes back in time; late lines join the current timestamp.
+        if t_us > now_us {
+            now_us = t_us;
+            writeln!(writer, "#{}", t_us)?;
+        }
+        changes += 1;
+        writer.write_all(signal.change(value, &signals[&signal]).as_bytes())
+    })
+    // The trace lasts until the last frame, even if it changed nothing.
+    .and_then(|_| if end_us > now_us { writeln!(writer, "#{}", end_us) } else { Ok(()) })
+    .and_then(|_| writer.flush())
+    .map_err(|e| format!("Export of {} failed: {}", input.display(), e))?;
+
+    Ok(VcdReport {
+        input: input.to_string_lossy().to_string(),
+        output: output.to_string_lossy().to_string(),
+        signals: signals
+            .keys()
+            .map(|signal| format!("{}.{}", signal.scope(), signal.name))
+            .collect(),
+        frames: frame_count,
+        changes,
+        duration_us: end_us,
+    })
+}
+
+/// Runs `export` from the command line and returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    let mut input = None;
+    let mut direction = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--direction" => match args.next().map(String::as_str) {
+                Some("rx") => direction = Some(Direction::Rx),
+                Some("tx") => direction = Some(Direction::Tx),
+                other => {
+                    eprintln!("Unknown direction {:?}; use rx or tx.", other.unwrap_or_default());
+                    return 2;
+                }
+            },
+            "--output" => output = args.next().map(PathBuf::from),
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument {}.", arg);
+                return 2;
+            }
+        }
+    }
+    let input = match input {
+        Some(input) => input,
+        None => {
+            eprintln!("Usage: app vcd <recording> [--direction rx|tx] [--output <path>]");
+            return 2;
+        }
+    };
+
+    match export(&input, output, direction) {
+        Ok(report) => {
+            println!(
+                "{} signal(s), {} value changes over {} us written to {}.",
+                report.signals.len(),
+                report.changes,
+                report.duration_us,
+                report.output
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,358 @@
+// src/writer.rs
+
+// Importing necessary crates and modules.
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering}, // Atomics shared between the UI path and the writer task.
+    Arc, // Arc for shared ownership across threads.
+};
+use std::time::{Duration, Instant}; // Structs for handling time durations and instants.
+use tauri::Manager; // Tauri Manager for emitting events.
+use tauri::async_runtime::JoinHandle; // Handle of a task spawned on the Tauri runtime.
+use tokio::io::{AsyncWriteExt, WriteHalf}; // Async write trait and the write half of a split stream.
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender}; // Bounded channel for the outgoing queue.
+use tokio::sync::oneshot; // Acknowledgement once the queue has been drained.
+use tokio_serial::SerialStream; // Async serial stream.
+use tokio_util::sync::CancellationToken; // Token used to stop the writer task.
+
+use crate::pipeline::{SinkEvent, SinkRegistry};
+use crate::safe_state;
+use crate::serial_wrapper::backend_log;
+use crate::stats::StatsMonitor;
+
+/// Configuration of the outgoing command queue.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriterConfig {
+    /// Maximum number of commands waiting to be written.
+    pub queue_capacity: usize,
+    /// Pause between two consecutive frames, in milliseconds.
+    pub frame_spacing_ms: u64,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        WriterConfig {
+            queue_capacity: 64,
+            frame_spacing_ms: 5,
+        }
+    }
+}
+
+// A command waiting in the outgoing queue.
+struct OutgoingCommand {
+    id: u64,
+    bytes: Vec<u8>,
+    quiet: bool, // Internal traffic (e.g. heartbeats) is neither logged nor reported.
+    ack: Option<oneshot::Sender<()>>, // Signalled once everything queued before it was written.
+}
+
+// Payload of the `commandStatus` event.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CommandStatusPayload {
+    id: u64,
+    status: &'static str, // "queued", "sent" or "failed".
+    data: Vec<u8>,
+    error: Option<String>,
+    queue_depth: usize,
+}
+
+/// Snapshot of the outgoing queue returned to the frontend.
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueStatus {
+    pub depth: usize,
+    pub capacity: usize,
+}
+
+/// Handle used by the command path to feed the writer task.
+///
+/// Cloning the handle is cheap; the task stops once `cancel` fires
+/// or every handle has been dropped.
+#[derive(Clone)]
+pub struct WriterHandle {
+    sender: Sender<OutgoingCommand>,
+    depth: Arc<AtomicUsize>,
+    next_id: Arc<AtomicU64>,
+    cancel: CancellationToken,
+    capacity: usize,
+}
+
+impl WriterHandle {
+    /// Queues `bytes` for writing and returns the id used in `commandStatus` events.
+    pub fn enqueue(&self, app: &tauri::AppHandle, bytes: Vec<u8>) -> Result<u64, String> {
+        if self.cancel.is_cancelled() {
+            return Err("Writer task is not running.".to_string());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // Count the command before sending so the writer never sees a negative depth.
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+
+        match self.sender.try_send(OutgoingCommand { id, bytes: bytes.clone(), quiet: false, ack: None }) {
+            Ok(()) => {
+                emit_status(app, id, "queued", bytes, None, depth);
+                Ok(id)
+            }
+            Err(TrySendError::Full(_)) => {
+                let depth = self.depth.fetch_sub(1, Ordering::Relaxed) - 1;
+                let msg = format!("Outgoing queue is full ({} commands).", self.capacity);
+                emit_status(app, id, "failed", bytes, Some(msg.clone()), depth);
+                Err(msg)
+            }
+            Err(TrySendError::Closed(_)) => {
+                let depth = self.depth.fetch_sub(1, Ordering::Relaxed) - 1;
+                let msg = "Writer task is not running.".to_string();
+                emit_status(app, id, "failed", bytes, Some(msg.clone()), depth);
+                Err(msg)
+            }
+        }
+    }
+
+    /// Queues internal traffic that is not logged and emits no `commandStatus` events.
+    pub fn enqueue_quiet(&self, bytes: Vec<u8>) -> Result<u64, String> {
+        if self.cancel.is_cancelled() {
+            return Err("Writer task is not running.".to_string());
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.sender
+            .try_send(OutgoingCommand { id, bytes, quiet: true, ack: None })
+            .map(|_| id)
+            .map_err(|e| {
+                self.depth.fetch_sub(1, Ordering::Relaxed);
+                format!("Could not queue command: {}", e)
+            })
+    }
+
+    /// Queues an empty marker whose receiver fires once every command queued
+    /// before it has been written (or dropped because the port closed).
+    pub fn drain_marker(&self) -> Result<oneshot::Receiver<()>, String> {
+        let (ack, done) = oneshot::channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        self.sender
+            .try_send(OutgoingCommand { id, bytes: Vec::new(), quiet: true, ack: Some(ack) })
+            .map(|_| done)
+            .map_err(|e| {
+                self.depth.fetch_sub(1, Ordering::Relaxed);
+                format!("Could not queue command: {}", e)
+            })
+    }
+
+    /// Waits up to `timeout` until everything queued so far has been written.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        match self.drain_marker() {
+            Ok(done) => matches!(tokio::time::timeout(timeout, done).await, Ok(Ok(()))),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the current depth and capacity of the queue.
+    pub fn status(&self) -> QueueStatus {
+        QueueStatus {
+            depth: self.depth.load(Ordering::Relaxed),
+            capacity: self.capacity,
+        }
+    }
+}
+
+// Emits a `commandStatus` event to the frontend.
+fn emit_status(
+    app: &tauri::AppHandle,
+    id: u64,
+    status: &'static str,
+    data: Vec<u8>,
+    error: Option<String>,
+    queue_depth: usize,
+) {
+    let payload = CommandStatusPayload {
+        id,
+        status,
+        data,
+        error,
+        queue_depth,
+    };
+    if let Err(e) = app.emit_all("commandStatus", payload) {
+        println!("Failed to emit command status to the frontend: {:?}", e);
+    }
+}
+
+/// Function to start the task that owns the write half of the serial port.
+///
+/// The task stops when `cancel` fires; pending commands are then reported as failed.
+pub fn start_writer_task(
+    app: tauri::AppHandle,
+    port: WriteHalf<SerialStream>,
+    config: WriterConfig,
+    cancel: CancellationToken,
+    stats: Arc<StatsMonitor>,
+    sinks: Arc<SinkRegistry>,
+) -> (WriterHandle, JoinHandle<()>) {
+    let (sender, receiver) = mpsc::channel(config.queue_capacity.max(1));
+    let handle = WriterHandle {
+        sender,
+        depth: Arc::new(AtomicUsize::new(0)),
+        next_id: Arc::new(AtomicU64::new(1)),
+        cancel: cancel.clone(),
+        capacity: config.queue_capacity.max(1),
+    };
+
+    let depth = handle.depth.clone();
+    let task = tauri::async_runtime::spawn(safe_state::connection_task(run_writer(
+        app,
+        port,
+        receiver,
+        depth,
+        cancel,
+        config,
+        Traffic { stats, sinks },
+    )));
+
+    (handle, task)
+}
+
+// Where the writer task reports what it wrote.
+struct Traffic {
+    stats: Arc<StatsMonitor>,
+    sinks: Arc<SinkRegistry>,
+}
+
+impl Traffic {
+    // Sent bytes go to the statistics and the sinks, internal traffic included.
+    fn report(&self, bytes: &[u8], result: &std::io::Result<()>) {
+        self.stats.on_write(bytes, result);
+        if result.is_ok() {
+            self.sinks.publish(SinkEvent::sent(Instant::now(), bytes));
+        }
+    }
+}
+
+// Body of the writer task.
+async fn run_writer(
+    app: tauri::AppHandle,
+    mut port: WriteHalf<SerialStream>,
+    mut receiver: Receiver<OutgoingCommand>,
+    depth: Arc<AtomicUsize>,
+    cancel: CancellationToken,
+    config: WriterConfig,
+    traffic: Traffic,
+) {
+    backend_log(&app, "Serial writer task started.", "INFO");
+    let spacing = Duration::from_millis(config.frame_spacing_ms);
+
+    loop {
+        let command = tokio::select! {
+            _ = cancel.cancelled() => break,
+            command = receiver.recv() => match command {
+                Some(command) => command,
+                None => break,
+            },
+        };
+        let remaining = depth.fetch_sub(1, Ordering::Relaxed) - 1;
+
+        // A drain marker carries no data: acknowledge it and move on.
+        if let Some(ack) = command.ack {
+            let _ = ack.send(());
+            continue;
+        }
+
+        let result = tokio::select! {
+            _ = cancel.cancelled() => Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "port disconnected during write",
+            )),
+            result = write_frame(&mut port, &command.bytes) => result,
+        };
+
+        traffic.report(&command.bytes, &result);
+
+        match result {
+            Ok(()) if command.quiet => {}
+            Err(e) if command.quiet => {
+                backend_log(&app, &format!("Failed to send internal frame: {:?}", e), "WARNING");
+            }
+            Ok(()) => {
+                log_sent(&app, &command.bytes);
+                emit_status(&app, command.id, "sent", command.bytes, None, remaining);
+            }
+            Err(e) => {
+                let msg = format!("Failed to send message: {:?}", e);
+                backend_log(&app, &msg, "ERROR");
+                emit_status(&app, command.id, "failed", command.bytes, Some(msg), remaining);
+            }
+        }
+
+        if !spacing.is_zero() {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(spacing) => {}
+            }
+        }
+    }
+
+    // Report whatever is still queued so the frontend does not wait forever.
+    receiver.close();
+    while let Ok(command) = receiver.try_recv() {
+        let remaining = depth.fetch_sub(1, Ordering::Relaxed) - 1;
+        if command.quiet {
+            continue;
+        }
+        emit_status(
+            &app,
+            command.id,
+            "failed",
+            command.bytes,
+            Some("Port disconnected before the command was sent.".to_string()),
+            remaining,
+        );
+    }
+
+    backend_log(&app, "Serial writer task terminated.", "INFO");
+}
+
+// Writes one frame and flushes it to the device.
+async fn write_frame(port: &mut WriteHalf<SerialStream>, bytes: &[u8]) -> std::io::Result<()> {
+    port.write_all(bytes).await?;
+    port.flush().await
+}
+
+// Logs a successfully written frame, including its decoded fields.
+fn log_sent(app: &tauri::AppHandle, input: &[u8]) {
+    backend_log(app, &format!("Sent {} bytes.", input.len()), "SUCCESS");
+
+    let hex_content = input
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    backend_log(
+        app,
+        &format!("Message content (hex): {}", hex_content),
+        "SUCCESS",
+    );
+
+    // Decode the values.
+    let command_id = input.first().copied().unwrap_or_default();
+    let hardware_id = input.get(1).copied().unwrap_or_default();
+    let value = (input.get(2).copied().unwrap_or_default() as u32)
+        | ((input.get(3).copied().unwrap_or_default() as u32) << 8)
+        | ((input.get(4).copied().unwrap_or_default() as u32) << 16)
+        | ((input.get(5).copied().unwrap_or_default() as u32) << 24);
+
+    backend_log(
+        app,
+        &format!(
+            "Parsed Sent Message -> COMMAND_ID: {}, HARDWARE_ID: {}, VALUE: {}",
+            command_id, hardware_id, value
+        ),
+        "INFO",
+    );
+
+    backend_log(
+        app,
+        &format!("Message details -> Decimal: {:?}, Hex: {}", input, hex_content),
+        "DEBUG",
+    );
+}